@@ -0,0 +1,576 @@
+//! Document anchoring.
+//!
+//! An anchor binds a document root to an anchor id. Anchoring is a two step process: the
+//! owner of a document first `pre_commit`s the signing root of the document, which reserves the
+//! anchor id for a short period, and then `commit`s the document root together with a proof
+//! linking the signing root to the document root.
+//!
+//! Anchors are only stored until the expiry chosen on `commit`. Their owner pays a deposit per
+//! stored byte, which is returned, minus a rent share, when the anchor is evicted. Eviction
+//! happens in bounded batches at the start of every block.
+
+use codec::{Decode, Encode};
+use frame_support::{
+    decl_error, decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure,
+    traits::{Currency, Get, OnUnbalanced, ReservableCurrency}, weights::{SimpleDispatchInfo, Weight},
+};
+use frame_system::{self as system, ensure_signed};
+use sp_runtime::{Perbill, traits::{Hash, One, Saturating, Zero}};
+use sp_std::vec::Vec;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+    <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::NegativeImbalance;
+
+/// Weight of evicting a single expired entry.
+pub const EVICTION_WEIGHT: Weight = 100_000;
+
+/// Number of blocks from its expiry searched for room to queue the eviction of an entry.
+pub const MAX_EVICTION_SEARCH: u32 = 16;
+
+/// A reservation of an anchor id made by `pre_commit`.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct PreCommitData<Hash, AccountId, BlockNumber, Balance> {
+    /// Signing root of the document that is going to be anchored.
+    pub signing_root: Hash,
+    /// Account that made the pre-commit.
+    pub identity: AccountId,
+    /// Block after which the pre-commit is no longer valid.
+    pub expiration_block: BlockNumber,
+    /// Deposit reserved from `identity`.
+    pub deposit: Balance,
+}
+
+/// A committed anchor.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct AnchorData<Hash, BlockNumber> {
+    /// Anchor id, the hash of the id preimage passed to `commit`.
+    pub id: Hash,
+    /// Root of the anchored document.
+    pub doc_root: Hash,
+    /// Block the anchor was committed in.
+    pub anchored_block: BlockNumber,
+    /// Block after which the anchor may be removed from storage.
+    pub expires_at: BlockNumber,
+}
+
+pub trait Trait: frame_system::Trait {
+    type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+
+    /// Currency used for anchor deposits.
+    type Currency: ReservableCurrency<Self::AccountId>;
+
+    /// Deposit reserved for the lifetime of a pre-commit.
+    type PreCommitDeposit: Get<BalanceOf<Self>>;
+
+    /// Deposit reserved per byte of a stored anchor.
+    type ByteDeposit: Get<BalanceOf<Self>>;
+
+    /// Number of blocks a pre-commit stays valid.
+    type PreCommitExpiration: Get<Self::BlockNumber>;
+
+    /// Maximum number of blocks an anchor may be stored for.
+    type MaxAnchorLifetime: Get<Self::BlockNumber>;
+
+    /// Maximum number of expired entries evicted in a single block. This is also the number of
+    /// entries that may expire at the same block.
+    type MaxEvictionsPerBlock: Get<u32>;
+
+    /// Share of an anchor deposit that is kept as storage rent when the anchor is evicted.
+    type RentFraction: Get<Perbill>;
+
+    /// Handler for the storage rent taken from anchor deposits.
+    type Slash: OnUnbalanced<NegativeImbalanceOf<Self>>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Anchor {
+        /// Pre-commits by anchor id.
+        PreCommits get(fn get_pre_commit):
+            map hasher(blake2_256) T::Hash => Option<PreCommitData<T::Hash, T::AccountId, T::BlockNumber, BalanceOf<T>>>;
+
+        /// Committed anchors by anchor id.
+        Anchors get(fn get_anchor_by_id):
+            map hasher(blake2_256) T::Hash => Option<AnchorData<T::Hash, T::BlockNumber>>;
+
+        /// Owner of an anchor and the deposit reserved for it.
+        AnchorDeposits get(fn anchor_deposit):
+            map hasher(blake2_256) T::Hash => Option<(T::AccountId, BalanceOf<T>)>;
+
+        /// Ids of the anchors and pre-commits expiring at a block.
+        Expiries get(fn expiries): map hasher(twox_64_concat) T::BlockNumber => Vec<T::Hash>;
+
+        /// First block whose expiries have not been fully evicted yet.
+        EvictionCursor get(fn eviction_cursor): T::BlockNumber;
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        <T as frame_system::Trait>::AccountId,
+        <T as frame_system::Trait>::Hash,
+    {
+        /// An anchor id has been reserved by the account.
+        PreCommitted(AccountId, Hash),
+        /// An anchor has been committed by the account.
+        Committed(AccountId, Hash),
+        /// An expired anchor has been removed from storage.
+        Evicted(Hash),
+    }
+);
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// An anchor with this id already exists.
+        AnchorExists,
+        /// The anchor id is reserved by a valid pre-commit of another account.
+        PreCommitExists,
+        /// The anchor id is reserved by a pre-commit of another account.
+        NotPreCommitOwner,
+        /// The proof does not link the pre-committed signing root to the document root.
+        InvalidProof,
+        /// The expiry of the anchor is not in the future or too far in the future.
+        InvalidExpiry,
+        /// The evictions of the blocks around the expiry are full.
+        ExpiriesFull,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        #[weight = SimpleDispatchInfo::FixedOperational(
+            EVICTION_WEIGHT.saturating_mul(T::MaxEvictionsPerBlock::get() as Weight)
+        )]
+        fn on_initialize(now: T::BlockNumber) {
+            Self::evict_expired(now);
+        }
+
+        /// Reserve `anchor_id` for the signing root of a document.
+        ///
+        /// The reservation is valid for `PreCommitExpiration` blocks. An expired pre-commit of
+        /// another account is replaced and its deposit is returned.
+        #[weight = SimpleDispatchInfo::FixedNormal(500_000)]
+        fn pre_commit(origin, anchor_id: T::Hash, signing_root: T::Hash) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(!<Anchors<T>>::contains_key(anchor_id), Error::<T>::AnchorExists);
+            ensure!(!Self::has_valid_pre_commit(anchor_id), Error::<T>::PreCommitExists);
+
+            let expiration_block = <system::Module<T>>::block_number() + T::PreCommitExpiration::get();
+            let eviction_block = Self::eviction_block(expiration_block)?;
+
+            let deposit = T::PreCommitDeposit::get();
+            T::Currency::reserve(&who, deposit)?;
+            Self::remove_pre_commit(anchor_id);
+
+            <PreCommits<T>>::insert(anchor_id, PreCommitData {
+                signing_root,
+                identity: who.clone(),
+                expiration_block,
+                deposit,
+            });
+            <Expiries<T>>::append_or_insert(eviction_block, &[anchor_id][..]);
+
+            Self::deposit_event(RawEvent::PreCommitted(who, anchor_id));
+            Ok(())
+        }
+
+        /// Anchor `doc_root` under the hash of `anchor_id_preimage` until `expires_at`.
+        ///
+        /// If the anchor id has a valid pre-commit, it must belong to the sender and `proof` must
+        /// link the pre-committed signing root to `doc_root`. `expires_at` may be at most
+        /// `MaxAnchorLifetime` blocks in the future.
+        #[weight = SimpleDispatchInfo::FixedNormal(1_000_000)]
+        fn commit(
+            origin,
+            anchor_id_preimage: T::Hash,
+            doc_root: T::Hash,
+            proof: T::Hash,
+            expires_at: T::BlockNumber,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            let now = <system::Module<T>>::block_number();
+            ensure!(
+                expires_at > now && expires_at <= now.saturating_add(T::MaxAnchorLifetime::get()),
+                Error::<T>::InvalidExpiry
+            );
+
+            let anchor_id = T::Hashing::hash_of(&anchor_id_preimage);
+            ensure!(!<Anchors<T>>::contains_key(anchor_id), Error::<T>::AnchorExists);
+
+            if Self::has_valid_pre_commit(anchor_id) {
+                let pre_commit = <PreCommits<T>>::get(anchor_id)
+                    .expect("valid pre-commit exists; qed");
+                ensure!(pre_commit.identity == who, Error::<T>::NotPreCommitOwner);
+                ensure!(
+                    Self::validate_proof(doc_root, pre_commit.signing_root, proof),
+                    Error::<T>::InvalidProof
+                );
+            }
+
+            let anchor = AnchorData {
+                id: anchor_id,
+                doc_root,
+                anchored_block: now,
+                expires_at,
+            };
+            let eviction_block = Self::eviction_block(expires_at)?;
+            let deposit = Self::deposit_for(&anchor);
+            T::Currency::reserve(&who, deposit)?;
+            Self::remove_pre_commit(anchor_id);
+
+            <Anchors<T>>::insert(anchor_id, anchor);
+            <AnchorDeposits<T>>::insert(anchor_id, (who.clone(), deposit));
+            <Expiries<T>>::append_or_insert(eviction_block, &[anchor_id][..]);
+
+            Self::deposit_event(RawEvent::Committed(who, anchor_id));
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Whether `anchor_id` has a pre-commit that has not expired yet.
+    pub fn has_valid_pre_commit(anchor_id: T::Hash) -> bool {
+        match <PreCommits<T>>::get(anchor_id) {
+            Some(pre_commit) => pre_commit.expiration_block > <system::Module<T>>::block_number(),
+            None => false,
+        }
+    }
+
+    /// Remove the pre-commit of `anchor_id`, if any, and return its deposit.
+    fn remove_pre_commit(anchor_id: T::Hash) {
+        if let Some(pre_commit) = <PreCommits<T>>::take(anchor_id) {
+            if !pre_commit.deposit.is_zero() {
+                T::Currency::unreserve(&pre_commit.identity, pre_commit.deposit);
+            }
+        }
+    }
+
+    /// Deposit required to store `anchor` and its owner.
+    pub fn deposit_for(anchor: &AnchorData<T::Hash, T::BlockNumber>) -> BalanceOf<T> {
+        let bytes = anchor.encode().len() + T::AccountId::default().encode().len();
+        T::ByteDeposit::get().saturating_mul((bytes as u32).into())
+    }
+
+    /// Block to queue the eviction of an entry expiring at `at`: the first block from `at`, and
+    /// among the next `MAX_EVICTION_SEARCH`, whose evictions are not full.
+    fn eviction_block(mut at: T::BlockNumber) -> Result<T::BlockNumber, Error<T>> {
+        let max = T::MaxEvictionsPerBlock::get() as usize;
+        for _ in 0..MAX_EVICTION_SEARCH {
+            if <Expiries<T>>::decode_len(at).unwrap_or(0) < max {
+                return Ok(at);
+            }
+            at += One::one();
+        }
+        Err(Error::<T>::ExpiriesFull)
+    }
+
+    /// Evict up to `MaxEvictionsPerBlock` entries that expired before `now`.
+    ///
+    /// Every visited block counts against the limit, so the cursor catches up with `now` in
+    /// bounded steps after a long stretch of blocks without expiries.
+    fn evict_expired(now: T::BlockNumber) {
+        let mut remaining = T::MaxEvictionsPerBlock::get();
+        let mut block = Self::eviction_cursor();
+        while block < now && remaining > 0 {
+            let mut expired = <Expiries<T>>::take(block);
+            let count = remaining.min(expired.len() as u32);
+            for id in expired.drain(..count as usize) {
+                Self::evict(id, now);
+            }
+            remaining = remaining.saturating_sub(count.max(1));
+            if !expired.is_empty() {
+                <Expiries<T>>::insert(block, expired);
+                break;
+            }
+            block += One::one();
+        }
+        <EvictionCursor<T>>::put(block);
+    }
+
+    /// Remove the expired anchor or pre-commit stored under `id`, if any.
+    ///
+    /// An anchor deposit is returned to its owner minus the `RentFraction` share, which is
+    /// slashed. A pre-commit deposit is returned in full.
+    fn evict(id: T::Hash, now: T::BlockNumber) {
+        if <Anchors<T>>::get(id).map_or(false, |anchor| anchor.expires_at < now) {
+            <Anchors<T>>::remove(id);
+            if let Some((owner, deposit)) = <AnchorDeposits<T>>::take(id) {
+                let rent = T::RentFraction::get() * deposit;
+                let (imbalance, _) = T::Currency::slash_reserved(&owner, rent);
+                T::Slash::on_unbalanced(imbalance);
+                T::Currency::unreserve(&owner, deposit.saturating_sub(rent));
+            }
+            Self::deposit_event(RawEvent::Evicted(id));
+        }
+
+        if <PreCommits<T>>::get(id).map_or(false, |pre_commit| pre_commit.expiration_block <= now) {
+            Self::remove_pre_commit(id);
+        }
+    }
+
+    /// Check that `doc_root` is the hash of the sorted pair of `signing_root` and `proof`.
+    fn validate_proof(doc_root: T::Hash, signing_root: T::Hash, proof: T::Hash) -> bool {
+        let pair = if signing_root < proof {
+            (signing_root, proof)
+        } else {
+            (proof, signing_root)
+        };
+        T::Hashing::hash_of(&pair) == doc_root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_support::{assert_noop, assert_ok, impl_outer_origin, parameter_types, weights::Weight};
+    use sp_core::H256;
+    use sp_runtime::{
+        testing::Header,
+        traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+        Perbill,
+    };
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    type Anchor = super::Module<Test>;
+
+    type System = frame_system::Module<Test>;
+
+    type Balances = pallet_balances::Module<Test>;
+
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: Weight = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+        pub const ExistentialDeposit: u64 = 1;
+        pub const PreCommitDeposit: u64 = 10;
+        pub const ByteDeposit: u64 = 1;
+        pub const PreCommitExpiration: u64 = 5;
+        pub const MaxAnchorLifetime: u64 = 100;
+        pub const MaxEvictionsPerBlock: u32 = 2;
+        pub const RentFraction: Perbill = Perbill::from_percent(50);
+    }
+
+    impl frame_system::Trait for Test {
+        type AccountId = u64;
+        type Call = ();
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Header = Header;
+        type Event = ();
+        type Origin = Origin;
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type MaximumBlockLength = MaximumBlockLength;
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type Version = ();
+        type ModuleToIndex = ();
+        type AccountData = pallet_balances::AccountData<u64>;
+        type OnNewAccount = ();
+        type OnKilledAccount = ();
+    }
+
+    impl pallet_balances::Trait for Test {
+        type Balance = u64;
+        type DustRemoval = ();
+        type Event = ();
+        type ExistentialDeposit = ExistentialDeposit;
+        type AccountStore = System;
+    }
+
+    impl Trait for Test {
+        type Event = ();
+        type Currency = Balances;
+        type PreCommitDeposit = PreCommitDeposit;
+        type ByteDeposit = ByteDeposit;
+        type PreCommitExpiration = PreCommitExpiration;
+        type MaxAnchorLifetime = MaxAnchorLifetime;
+        type MaxEvictionsPerBlock = MaxEvictionsPerBlock;
+        type RentFraction = RentFraction;
+        type Slash = ();
+    }
+
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        pallet_balances::GenesisConfig::<Test> {
+            balances: vec![(1, 1000), (2, 1000)],
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    fn doc_root(signing_root: H256, proof: H256) -> H256 {
+        let pair = if signing_root < proof { (signing_root, proof) } else { (proof, signing_root) };
+        BlakeTwo256::hash_of(&pair)
+    }
+
+    #[test]
+    fn commit_without_pre_commit() {
+        new_test_ext().execute_with(|| {
+            let preimage = H256::from_low_u64_be(1);
+            let anchor_id = BlakeTwo256::hash_of(&preimage);
+            let root = H256::from_low_u64_be(2);
+
+            assert_ok!(Anchor::commit(Origin::signed(1), preimage, root, H256::from_low_u64_be(3), 10));
+
+            let anchor = Anchor::get_anchor_by_id(anchor_id).unwrap();
+            assert_eq!(anchor.doc_root, root);
+            assert_eq!(anchor.expires_at, 10);
+            assert_eq!(Balances::reserved_balance(1), 88);
+            assert_noop!(
+                Anchor::commit(Origin::signed(2), preimage, root, H256::from_low_u64_be(4), 10),
+                Error::<Test>::AnchorExists
+            );
+        });
+    }
+
+    #[test]
+    fn commit_with_pre_commit() {
+        new_test_ext().execute_with(|| {
+            let preimage = H256::from_low_u64_be(5);
+            let anchor_id = BlakeTwo256::hash_of(&preimage);
+            let signing_root = H256::from_low_u64_be(6);
+            let proof = H256::from_low_u64_be(7);
+
+            assert_ok!(Anchor::pre_commit(Origin::signed(1), anchor_id, signing_root));
+            assert_eq!(Balances::reserved_balance(1), 10);
+            assert_noop!(
+                Anchor::pre_commit(Origin::signed(2), anchor_id, signing_root),
+                Error::<Test>::PreCommitExists
+            );
+            assert_noop!(
+                Anchor::commit(Origin::signed(2), preimage, doc_root(signing_root, proof), proof, 10),
+                Error::<Test>::NotPreCommitOwner
+            );
+            assert_noop!(
+                Anchor::commit(Origin::signed(1), preimage, H256::from_low_u64_be(8), proof, 10),
+                Error::<Test>::InvalidProof
+            );
+
+            assert_ok!(Anchor::commit(Origin::signed(1), preimage, doc_root(signing_root, proof), proof, 10));
+            assert!(Anchor::get_pre_commit(anchor_id).is_none());
+            assert_eq!(Balances::reserved_balance(1), 88);
+        });
+    }
+
+    #[test]
+    fn expired_pre_commit_is_replaced() {
+        new_test_ext().execute_with(|| {
+            let anchor_id = H256::from_low_u64_be(9);
+
+            assert_ok!(Anchor::pre_commit(Origin::signed(1), anchor_id, H256::from_low_u64_be(10)));
+            System::set_block_number(6);
+            assert_ok!(Anchor::pre_commit(Origin::signed(2), anchor_id, H256::from_low_u64_be(11)));
+
+            assert_eq!(Anchor::get_pre_commit(anchor_id).unwrap().identity, 2);
+            assert_eq!(Balances::reserved_balance(1), 0);
+            assert_eq!(Balances::reserved_balance(2), 10);
+        });
+    }
+
+    #[test]
+    fn commit_requires_bounded_future_expiry() {
+        new_test_ext().execute_with(|| {
+            System::set_block_number(10);
+            assert_noop!(
+                Anchor::commit(Origin::signed(1), H256::from_low_u64_be(12), H256::from_low_u64_be(13), H256::from_low_u64_be(14), 10),
+                Error::<Test>::InvalidExpiry
+            );
+            assert_noop!(
+                Anchor::commit(Origin::signed(1), H256::from_low_u64_be(12), H256::from_low_u64_be(13), H256::from_low_u64_be(14), 111),
+                Error::<Test>::InvalidExpiry
+            );
+        });
+    }
+
+    #[test]
+    fn expired_anchors_are_evicted_in_batches() {
+        new_test_ext().execute_with(|| {
+            let preimages: Vec<H256> = (20..23).map(H256::from_low_u64_be).collect();
+            for preimage in &preimages {
+                assert_ok!(Anchor::commit(Origin::signed(1), *preimage, H256::from_low_u64_be(1), H256::from_low_u64_be(2), 3));
+            }
+            // the bucket of block 3 holds two entries, the third anchor is queued at block 4.
+            assert_eq!(Anchor::expiries(3).len(), 2);
+            assert_eq!(Anchor::expiries(4).len(), 1);
+            assert_eq!(Balances::reserved_balance(1), 3 * 88);
+
+            // the cursor only walks two empty blocks before block 3.
+            Anchor::on_initialize(3);
+            assert_eq!(Anchor::eviction_cursor(), 2);
+
+            // one block visited and one anchor evicted exhaust the batch.
+            Anchor::on_initialize(4);
+            let ids: Vec<H256> = preimages.iter().map(BlakeTwo256::hash_of).collect();
+            assert_eq!(Anchor::eviction_cursor(), 3);
+            assert_eq!(Anchor::expiries(3), vec![ids[1]]);
+            assert!(Anchor::get_anchor_by_id(ids[0]).is_none());
+            assert!(Anchor::get_anchor_by_id(ids[1]).is_some());
+
+            // half of the evicted deposit is taken as rent.
+            assert_eq!(Balances::reserved_balance(1), 2 * 88);
+            assert_eq!(Balances::free_balance(1), 1000 - 3 * 88 + 44);
+
+            Anchor::on_initialize(5);
+            assert_eq!(Anchor::eviction_cursor(), 5);
+            assert!(Anchor::get_anchor_by_id(ids[1]).is_none());
+            assert!(Anchor::get_anchor_by_id(ids[2]).is_none());
+            assert_eq!(Balances::reserved_balance(1), 0);
+            assert_eq!(Balances::free_balance(1), 1000 - 3 * 44);
+        });
+    }
+
+    #[test]
+    fn eviction_search_is_bounded() {
+        new_test_ext().execute_with(|| {
+            // pre-commits expire at block 6.
+            System::set_block_number(1);
+            for block in 3..6 + MAX_EVICTION_SEARCH as u64 {
+                <Expiries<Test>>::insert(block, vec![H256::zero(); 2]);
+            }
+            assert_noop!(
+                Anchor::commit(Origin::signed(1), H256::from_low_u64_be(20), H256::from_low_u64_be(1), H256::from_low_u64_be(2), 3),
+                Error::<Test>::ExpiriesFull
+            );
+            assert_noop!(
+                Anchor::pre_commit(Origin::signed(1), H256::from_low_u64_be(21), H256::from_low_u64_be(1)),
+                Error::<Test>::ExpiriesFull
+            );
+
+            assert_ok!(Anchor::commit(Origin::signed(1), H256::from_low_u64_be(20), H256::from_low_u64_be(1), H256::from_low_u64_be(2), 7));
+            assert_eq!(Anchor::expiries(6 + MAX_EVICTION_SEARCH as u64).len(), 1);
+        });
+    }
+
+    #[test]
+    fn expired_pre_commits_are_evicted() {
+        new_test_ext().execute_with(|| {
+            let anchor_id = H256::from_low_u64_be(30);
+            assert_ok!(Anchor::pre_commit(Origin::signed(1), anchor_id, H256::from_low_u64_be(31)));
+
+            for n in 1..=6 {
+                Anchor::on_initialize(n);
+            }
+            assert!(Anchor::get_pre_commit(anchor_id).is_none());
+            assert_eq!(Balances::reserved_balance(1), 0);
+            assert_eq!(Balances::free_balance(1), 1000);
+        });
+    }
+}
@@ -0,0 +1,299 @@
+//! Incremental Merkle trees of note commitments.
+//!
+//! Only the frontier of a tree is kept: the rightmost filled node at every level and the current
+//! root. That is enough to append new commitments and to compute the new root.
+
+use codec::{Decode, Encode};
+use hex_literal::hex;
+use sp_std::vec::Vec;
+use crate::host_functions::shielded_crypto;
+
+/// Depth of the Sapling and Orchard note commitment trees.
+pub const TREE_DEPTH: usize = 32;
+
+/// Roots of empty Sapling subtrees of height 0 to `TREE_DEPTH`, the first one being
+/// Uncommitted^Sapling.
+const SAPLING_EMPTY_ROOTS: [[u8; 32]; TREE_DEPTH + 1] = [
+        hex!("0100000000000000000000000000000000000000000000000000000000000000"),
+        hex!("817de36ab2d57feb077634bca77819c8e0bd298c04f6fed0e6a83cc1356ca155"),
+        hex!("ffe9fc03f18b176c998806439ff0bb8ad193afdb27b2ccbc88856916dd804e34"),
+        hex!("d8283386ef2ef07ebdbb4383c12a739a953a4d6e0d6fb1139a4036d693bfbb6c"),
+        hex!("e110de65c907b9dea4ae0bd83a4b0a51bea175646a64c12b4c9f931b2cb31b49"),
+        hex!("912d82b2c2bca231f71efcf61737fbf0a08befa0416215aeef53e8bb6d23390a"),
+        hex!("8ac9cf9c391e3fd42891d27238a81a8a5c1d3a72b1bcbea8cf44a58ce7389613"),
+        hex!("d6c639ac24b46bd19341c91b13fdcab31581ddaf7f1411336a271f3d0aa52813"),
+        hex!("7b99abdc3730991cc9274727d7d82d28cb794edbc7034b4f0053ff7c4b680444"),
+        hex!("43ff5457f13b926b61df552d4e402ee6dc1463f99a535f9a713439264d5b616b"),
+        hex!("ba49b659fbd0b7334211ea6a9d9df185c757e70aa81da562fb912b84f49bce72"),
+        hex!("4777c8776a3b1e69b73a62fa701fa4f7a6282d9aee2c7a6b82e7937d7081c23c"),
+        hex!("ec677114c27206f5debc1c1ed66f95e2b1885da5b7be3d736b1de98579473048"),
+        hex!("1b77dac4d24fb7258c3c528704c59430b630718bec486421837021cf75dab651"),
+        hex!("bd74b25aacb92378a871bf27d225cfc26baca344a1ea35fdd94510f3d157082c"),
+        hex!("d6acdedf95f608e09fa53fb43dcd0990475726c5131210c9e5caeab97f0e642f"),
+        hex!("1ea6675f9551eeb9dfaaa9247bc9858270d3d3a4c5afa7177a984d5ed1be2451"),
+        hex!("6edb16d01907b759977d7650dad7e3ec049af1a3d875380b697c862c9ec5d51c"),
+        hex!("cd1c8dbf6e3acc7a80439bc4962cf25b9dce7c896f3a5bd70803fc5a0e33cf00"),
+        hex!("6aca8448d8263e547d5ff2950e2ed3839e998d31cbc6ac9fd57bc6002b159216"),
+        hex!("8d5fa43e5a10d11605ac7430ba1f5d81fb1b68d29a640405767749e841527673"),
+        hex!("08eeab0c13abd6069e6310197bf80f9c1ea6de78fd19cbae24d4a520e6cf3023"),
+        hex!("0769557bc682b1bf308646fd0b22e648e8b9e98f57e29f5af40f6edb833e2c49"),
+        hex!("4c6937d78f42685f84b43ad3b7b00f81285662f85c6a68ef11d62ad1a3ee0850"),
+        hex!("fee0e52802cb0c46b1eb4d376c62697f4759f6c8917fa352571202fd778fd712"),
+        hex!("16d6252968971a83da8521d65382e61f0176646d771c91528e3276ee45383e4a"),
+        hex!("d2e1642c9a462229289e5b0e3b7f9008e0301cbb93385ee0e21da2545073cb58"),
+        hex!("a5122c08ff9c161d9ca6fc462073396c7d7d38e8ee48cdb3bea7e2230134ed6a"),
+        hex!("28e7b841dcbc47cceb69d7cb8d94245fb7cb2ba3a7a6bc18f13f945f7dbd6e2a"),
+        hex!("e1f34b034d4a3cd28557e2907ebf990c918f64ecb50a94f01d6fda5ca5c7ef72"),
+        hex!("12935f14b676509b81eb49ef25f39269ed72309238b4c145803544b646dca62d"),
+        hex!("b2eed031d4d6a4f02a097f80b54cc1541d4163c6b6f5971f88b6e41d35c53814"),
+        hex!("fbc2f4300c01f0b7820d00e3347c8da4ee614674376cbc45359daa54f9b5493e"),
+];
+
+/// Roots of empty Orchard subtrees of height 0 to `TREE_DEPTH`, the first one being
+/// Uncommitted^Orchard.
+const ORCHARD_EMPTY_ROOTS: [[u8; 32]; TREE_DEPTH + 1] = [
+        hex!("0200000000000000000000000000000000000000000000000000000000000000"),
+        hex!("d1ab2507c809c2713c000f525e9fbdcb06c958384e51b9cc7f792dde6c97f411"),
+        hex!("c7413f4614cd64043abbab7cc1095c9bb104231cea89e2c3e0df83769556d030"),
+        hex!("2111fc397753e5fd50ec74816df27d6ada7ed2a9ac3816aab2573c8fac794204"),
+        hex!("806afbfeb45c64d4f2384c51eff30764b84599ae56a7ab3d4a46d9ce3aeab431"),
+        hex!("873e4157f2c0f0c645e899360069fcc9d2ed9bc11bf59827af0230ed52edab18"),
+        hex!("27ab1320953ae1ad70c8c15a1253a0a86fbc8a0aa36a84207293f8a495ffc402"),
+        hex!("4e14563df191a2a65b4b37113b5230680555051b22d74a8e1f1d706f90f3133b"),
+        hex!("b3bbe4f993d18a0f4eb7f4174b1d8555ce3396855d04676f1ce4f06dda07371f"),
+        hex!("4ef5bde9c6f0d76aeb9e27e93fba28c679dfcb991cbcb8395a2b57924cbd170e"),
+        hex!("a3c02568acebf5ca1ec30d6a7d7cd217a47d6a1b8311bf9462a5f939c6b74307"),
+        hex!("3ef9b30bae6122da1605bad6ec5d49b41d4d40caa96c1cf6302b66c5d2d10d39"),
+        hex!("22ae2800cb93abe63b70c172de70362d9830e53800398884a7a64ff68ed99e0b"),
+        hex!("187110d92672c24cedb0979cdfc917a6053b310d145c031c7292bb1d65b7661b"),
+        hex!("3f98adbe364f148b0cc2042cafc6be1166fae39090ab4b354bfb6217b964453b"),
+        hex!("63f8dbd10df936f1734973e0b3bd25f4ed440566c923085903f696bc6347ec0f"),
+        hex!("2182163eac4061885a313568148dfae564e478066dcbe389a0ddb1ecb7f5dc34"),
+        hex!("bd9dc0681918a3f3f9cd1f9e06aa1ad68927da63acc13b92a2578b2738a6d331"),
+        hex!("ca2ced953b7fb95e3ba986333da9e69cd355223c929731094b6c2174c7638d2e"),
+        hex!("55354b96b56f9e45aae1e0094d71ee248dabf668117778bdc3c19ca5331a4e1a"),
+        hex!("7097b04c2aa045a0deffcaca41c5ac92e694466578f5909e72bb78d33310f705"),
+        hex!("e81d6821ff813bd410867a3f22e8e5cb7ac5599a610af5c354eb392877362e01"),
+        hex!("157de8567f7c4996b8c4fdc94938fd808c3b2a5ccb79d1a63858adaa9a6dd824"),
+        hex!("fe1fce51cd6120c12c124695c4f98b275918fceae6eb209873ed73fe73775d0b"),
+        hex!("1f91982912012669f74d0cfa1030ff37b152324e5b8346b3335a0aaeb63a0a2d"),
+        hex!("5dec15f52af17da3931396183cbbbfbea7ed950714540aec06c645c754975522"),
+        hex!("e8ae2ad91d463bab75ee941d33cc5817b613c63cda943a4c07f600591b088a25"),
+        hex!("d53fdee371cef596766823f4a518a583b1158243afe89700f0da76da46d0060f"),
+        hex!("15d2444cefe7914c9a61e829c730eceb216288fee825f6b3b6298f6f6b6bd62e"),
+        hex!("4c57a617a0aa10ea7a83aa6b6b0ed685b6a3d9e5b8fd14f56cdc18021b12253f"),
+        hex!("3fd4915c19bd831a7920be55d969b2ac23359e2559da77de2373f06ca014ba27"),
+        hex!("87d063cd07ee4944222b7762840eb94c688bec743fa8bdf7715c8fe29f104c2a"),
+        hex!("ae2935f1dfd8a24aed7c70df7de3a668eb7a49b1319880dde2bbd9031ae5d82f"),
+];
+
+/// Reasons an append to a note commitment tree fails.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum TreeError {
+    /// The tree holds `2^TREE_DEPTH` leaves.
+    Full,
+    /// A leaf or node is not an element of the field the tree is hashed over.
+    InvalidNode,
+}
+
+/// Hash function of a note commitment tree.
+pub trait MerkleHasher {
+    /// Root of an empty subtree of height `level`, the empty leaf at level 0.
+    fn empty_root(level: usize) -> [u8; 32];
+
+    /// Parent of `lhs` and `rhs`, where `level` is the level of the children, leaves being at
+    /// level 0. `None` if a child is not a valid node.
+    fn combine(level: u8, lhs: &[u8; 32], rhs: &[u8; 32]) -> Option<[u8; 32]>;
+}
+
+/// Sapling tree, hashed with the Pedersen hash.
+pub struct SaplingHasher;
+
+impl MerkleHasher for SaplingHasher {
+    fn empty_root(level: usize) -> [u8; 32] {
+        SAPLING_EMPTY_ROOTS[level]
+    }
+
+    fn combine(level: u8, lhs: &[u8; 32], rhs: &[u8; 32]) -> Option<[u8; 32]> {
+        Some(shielded_crypto::sapling_merkle_hash(level, lhs, rhs))
+    }
+}
+
+/// Orchard tree, hashed with Sinsemilla.
+pub struct OrchardHasher;
+
+impl MerkleHasher for OrchardHasher {
+    fn empty_root(level: usize) -> [u8; 32] {
+        ORCHARD_EMPTY_ROOTS[level]
+    }
+
+    fn combine(level: u8, lhs: &[u8; 32], rhs: &[u8; 32]) -> Option<[u8; 32]> {
+        shielded_crypto::orchard_merkle_hash(level, lhs, rhs)
+    }
+}
+
+/// Root of the tree in which `leaf` is at `position` and `path` holds its siblings, from the
+/// leaves up. `None` if the path is not `TREE_DEPTH` long or holds an invalid node.
+pub fn root_of_path<H: MerkleHasher>(leaf: [u8; 32], position: u64, path: &[[u8; 32]]) -> Option<[u8; 32]> {
+    if path.len() != TREE_DEPTH || position >= 1u64 << TREE_DEPTH {
+        return None;
+    }
+    path.iter().enumerate().try_fold(leaf, |current, (level, sibling)| {
+        if (position >> level) % 2 == 0 {
+            H::combine(level as u8, &current, sibling)
+        } else {
+            H::combine(level as u8, sibling, &current)
+        }
+    })
+}
+
+/// The frontier of a note commitment tree.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Frontier {
+    /// Number of leaves in the tree.
+    pub size: u64,
+    /// Rightmost left child at every level, as of the last append.
+    pub filled: Vec<[u8; 32]>,
+    /// Root of the tree, `None` while the tree is empty.
+    pub root: Option<[u8; 32]>,
+}
+
+impl Frontier {
+    /// Current root of the tree.
+    pub fn root<H: MerkleHasher>(&self) -> [u8; 32] {
+        self.root.unwrap_or_else(|| H::empty_root(TREE_DEPTH))
+    }
+
+    /// Append `leaf` to the tree and update the root. The tree is left untouched on failure.
+    pub fn append<H: MerkleHasher>(&mut self, leaf: [u8; 32]) -> Result<(), TreeError> {
+        if self.size >= 1u64 << TREE_DEPTH {
+            return Err(TreeError::Full);
+        }
+
+        let mut filled = self.filled.clone();
+        filled.resize(TREE_DEPTH, [0u8; 32]);
+
+        let mut index = self.size;
+        let mut current = leaf;
+        for level in 0..TREE_DEPTH {
+            current = if index % 2 == 0 {
+                filled[level] = current;
+                H::combine(level as u8, &current, &H::empty_root(level))
+            } else {
+                H::combine(level as u8, &filled[level], &current)
+            }.ok_or(TreeError::InvalidNode)?;
+            index /= 2;
+        }
+
+        self.filled = filled;
+        self.root = Some(current);
+        self.size += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_io::hashing::blake2_256;
+
+    struct TestHasher;
+
+    impl MerkleHasher for TestHasher {
+        fn empty_root(level: usize) -> [u8; 32] {
+            (0..level).fold([0u8; 32], |root, level| Self::combine(level as u8, &root, &root).unwrap())
+        }
+
+        fn combine(level: u8, lhs: &[u8; 32], rhs: &[u8; 32]) -> Option<[u8; 32]> {
+            Some(blake2_256(&(level, lhs, rhs).encode()))
+        }
+    }
+
+    fn naive_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+        let mut nodes = leaves.to_vec();
+        for level in 0..TREE_DEPTH {
+            if nodes.len() % 2 == 1 {
+                nodes.push(TestHasher::empty_root(level));
+            }
+            nodes = nodes.chunks(2)
+                .map(|pair| TestHasher::combine(level as u8, &pair[0], &pair[1]).unwrap())
+                .collect();
+        }
+        nodes.first().cloned().unwrap_or_else(|| TestHasher::empty_root(TREE_DEPTH))
+    }
+
+    fn naive_subtree_root(leaves: &[[u8; 32]], height: usize) -> [u8; 32] {
+        let mut nodes = leaves.to_vec();
+        nodes.resize(1 << height, TestHasher::empty_root(0));
+        for level in 0..height {
+            nodes = nodes.chunks(2)
+                .map(|pair| TestHasher::combine(level as u8, &pair[0], &pair[1]).unwrap())
+                .collect();
+        }
+        nodes[0]
+    }
+
+    #[test]
+    fn empty_frontier_has_empty_root() {
+        let frontier = Frontier::default();
+        assert_eq!(frontier.root::<TestHasher>(), naive_root(&[]));
+    }
+
+    #[test]
+    fn append_matches_naive_root() {
+        let mut frontier = Frontier::default();
+        let mut leaves = Vec::new();
+        for i in 1..=9u8 {
+            let leaf = [i; 32];
+            frontier.append::<TestHasher>(leaf).unwrap();
+            leaves.push(leaf);
+            assert_eq!(frontier.root::<TestHasher>(), naive_root(&leaves));
+        }
+        assert_eq!(frontier.size, 9);
+    }
+
+    #[test]
+    fn path_leads_to_root() {
+        let leaves: Vec<[u8; 32]> = (1..=5u8).map(|i| [i; 32]).collect();
+        let root = naive_root(&leaves);
+
+        // siblings of the leaf at position 2: leaf 3, the parent of leaves 0 and 1, the parent
+        // of leaf 4 and empty subtrees.
+        let mut path = vec![
+            leaves[3],
+            TestHasher::combine(0, &leaves[0], &leaves[1]).unwrap(),
+            naive_subtree_root(&leaves[4..], 2),
+        ];
+        path.extend((3..TREE_DEPTH).map(TestHasher::empty_root));
+        assert_eq!(root_of_path::<TestHasher>(leaves[2], 2, &path), Some(root));
+        assert_ne!(root_of_path::<TestHasher>(leaves[2], 3, &path), Some(root));
+        assert_eq!(root_of_path::<TestHasher>(leaves[2], 2, &path[1..]), None);
+    }
+
+    #[test]
+    fn empty_roots_match_hashers() {
+        for level in 0..TREE_DEPTH {
+            let sapling = SaplingHasher::empty_root(level);
+            assert_eq!(
+                SaplingHasher::combine(level as u8, &sapling, &sapling),
+                Some(SaplingHasher::empty_root(level + 1))
+            );
+            let orchard = OrchardHasher::empty_root(level);
+            assert_eq!(
+                OrchardHasher::combine(level as u8, &orchard, &orchard),
+                Some(OrchardHasher::empty_root(level + 1))
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_orchard_leaf_is_rejected() {
+        let mut frontier = Frontier::default();
+        frontier.append::<OrchardHasher>(OrchardHasher::empty_root(0)).unwrap();
+        let before = frontier.clone();
+        assert_eq!(frontier.append::<OrchardHasher>([0xff; 32]), Err(TreeError::InvalidNode));
+        assert_eq!(frontier, before);
+    }
+}
@@ -0,0 +1,209 @@
+//! Governance parameters of transaction fees.
+//!
+//! The parameters of the fee multiplier update, `TargetedFeeAdjustment`, can be changed by the
+//! council or by referendum without a runtime upgrade.
+//!
+//! Transaction fees are split between the treasury and the block author according to a share
+//! set by the council, while tips always go to the author. `pallet_transaction_payment` hands
+//! fees and tips to its `OnTransactionPayment` handler as a single imbalance, so fees are charged
+//! by this module's `ChargeTransactionPayment` instead, which computes them the same way but
+//! keeps the tip apart.
+
+use codec::{Decode, Encode};
+use frame_support::{
+    decl_error, decl_event, decl_module, decl_storage, ensure,
+    traits::{Currency, EnsureOrigin, ExistenceRequirement, Get, Imbalance, OnUnbalanced, WithdrawReason},
+    weights::{DispatchInfo, SimpleDispatchInfo},
+};
+use frame_system::ensure_root;
+use sp_runtime::{
+    Fixed64, Perbill,
+    traits::{SaturatedConversion, SignedExtension, Zero},
+    transaction_validity::{
+        InvalidTransaction, TransactionPriority, TransactionValidity, TransactionValidityError,
+        ValidTransaction,
+    },
+};
+
+type BalanceOf<T> = <<T as pallet_transaction_payment::Trait>::Currency as Currency<
+    <T as frame_system::Trait>::AccountId,
+>>::Balance;
+type NegativeImbalanceOf<T> = <<T as pallet_transaction_payment::Trait>::Currency as Currency<
+    <T as frame_system::Trait>::AccountId,
+>>::NegativeImbalance;
+
+pub trait Trait: pallet_transaction_payment::Trait {
+    type Event: From<Event> + Into<<Self as frame_system::Trait>::Event>;
+
+    /// Origin allowed to change the fee parameters, besides root.
+    type SetOrigin: EnsureOrigin<Self::Origin>;
+
+    /// Handler for the tips. Fees go to `OnTransactionPayment`.
+    type OnTip: OnUnbalanced<NegativeImbalanceOf<Self>>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Fees {
+        /// Share of transaction fees, tips excluded, that goes to the block author. The rest goes
+        /// to the treasury.
+        AuthorFeeShare get(fn author_fee_share): Perbill = Perbill::from_percent(20);
+
+        /// How fast the fee multiplier follows the fullness of blocks, `v` in
+        /// `TargetedFeeAdjustment`. It is positive and below 2, where the update stops being
+        /// monotonic.
+        FeeAdjustmentSpeed get(fn fee_adjustment_speed): Fixed64 = Fixed64::from_rational(4, 100_000);
+
+        /// Fullness of blocks the fee multiplier targets. It is positive and less than
+        /// `AvailableBlockRatio`, or normal transactions could never reach it.
+        TargetBlockFullness get(fn target_block_fullness): Perbill = Perbill::from_percent(25);
+
+        /// Value the fee multiplier never goes below. At `-1`, transactions pay no weight fee.
+        MinimumMultiplier get(fn minimum_multiplier): Fixed64 = Fixed64::from_rational(-1, 1);
+    }
+}
+
+decl_event!(
+    pub enum Event {
+        /// The share of transaction fees of the block author has changed.
+        AuthorFeeShareSet(Perbill),
+        /// The speed of the fee multiplier has changed.
+        FeeAdjustmentSpeedSet(Fixed64),
+        /// The target block fullness has changed.
+        TargetBlockFullnessSet(Perbill),
+        /// The minimum fee multiplier has changed.
+        MinimumMultiplierSet(Fixed64),
+    }
+);
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// The speed of the fee multiplier must be positive.
+        InvalidSpeed,
+        /// The speed of the fee multiplier must be below 2.
+        SpeedTooHigh,
+        /// The target block fullness must be positive and less than `AvailableBlockRatio`.
+        InvalidTargetFullness,
+        /// The minimum fee multiplier must be between -1 and 0.
+        InvalidMinimumMultiplier,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        /// Set the share of transaction fees that goes to the block author.
+        #[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+        fn set_author_fee_share(origin, share: Perbill) {
+            T::SetOrigin::try_origin(origin).map(|_| ()).or_else(ensure_root)?;
+            AuthorFeeShare::put(share);
+            Self::deposit_event(Event::AuthorFeeShareSet(share));
+        }
+
+        /// Set how fast the fee multiplier follows the fullness of blocks.
+        #[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+        fn set_fee_adjustment_speed(origin, speed: Fixed64) {
+            T::SetOrigin::try_origin(origin).map(|_| ()).or_else(ensure_root)?;
+            ensure!(speed > Fixed64::default(), Error::<T>::InvalidSpeed);
+            ensure!(speed < Fixed64::from_rational(2, 1), Error::<T>::SpeedTooHigh);
+            FeeAdjustmentSpeed::put(speed);
+            Self::deposit_event(Event::FeeAdjustmentSpeedSet(speed));
+        }
+
+        /// Set the fullness of blocks the fee multiplier targets.
+        #[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+        fn set_target_block_fullness(origin, fullness: Perbill) {
+            T::SetOrigin::try_origin(origin).map(|_| ()).or_else(ensure_root)?;
+            ensure!(
+                fullness > Perbill::zero() && fullness < <T as frame_system::Trait>::AvailableBlockRatio::get(),
+                Error::<T>::InvalidTargetFullness
+            );
+            TargetBlockFullness::put(fullness);
+            Self::deposit_event(Event::TargetBlockFullnessSet(fullness));
+        }
+
+        /// Set the value the fee multiplier never goes below.
+        #[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+        fn set_minimum_multiplier(origin, minimum: Fixed64) {
+            T::SetOrigin::try_origin(origin).map(|_| ()).or_else(ensure_root)?;
+            ensure!(
+                minimum >= Fixed64::from_rational(-1, 1) && minimum <= Fixed64::default(),
+                Error::<T>::InvalidMinimumMultiplier
+            );
+            MinimumMultiplier::put(minimum);
+            Self::deposit_event(Event::MinimumMultiplierSet(minimum));
+        }
+    }
+}
+
+/// Charges the transaction fee and the tip, like `pallet_transaction_payment`'s extension of
+/// the same name, and hands them to `OnTransactionPayment` and `OnTip` separately.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct ChargeTransactionPayment<T: Trait + Send + Sync>(#[codec(compact)] BalanceOf<T>);
+
+impl<T: Trait + Send + Sync> ChargeTransactionPayment<T> {
+    /// Utility constructor. Used only in client/factory code.
+    pub fn from(tip: BalanceOf<T>) -> Self {
+        Self(tip)
+    }
+}
+
+impl<T: Trait + Send + Sync> sp_std::fmt::Debug for ChargeTransactionPayment<T> {
+    #[cfg(feature = "std")]
+    fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+        write!(f, "ChargeTransactionPayment<{:?}>", self.0)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+        Ok(())
+    }
+}
+
+impl<T: Trait + Send + Sync> SignedExtension for ChargeTransactionPayment<T> {
+    const IDENTIFIER: &'static str = "ChargeTransactionPayment";
+    type AccountId = T::AccountId;
+    type Call = <T as frame_system::Trait>::Call;
+    type AdditionalSigned = ();
+    type DispatchInfo = DispatchInfo;
+    type Pre = ();
+
+    fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+        Ok(())
+    }
+
+    fn validate(
+        &self,
+        who: &Self::AccountId,
+        _call: &Self::Call,
+        info: Self::DispatchInfo,
+        len: usize,
+    ) -> TransactionValidity {
+        let tip = self.0;
+        let fee = pallet_transaction_payment::ChargeTransactionPayment::<T>::compute_fee(len as u32, info, tip);
+        let reason = if tip.is_zero() {
+            WithdrawReason::TransactionPayment.into()
+        } else {
+            WithdrawReason::TransactionPayment | WithdrawReason::Tip
+        };
+        let imbalance = match <T as pallet_transaction_payment::Trait>::Currency::withdraw(
+            who,
+            fee,
+            reason,
+            ExistenceRequirement::KeepAlive,
+        ) {
+            Ok(imbalance) => imbalance,
+            Err(_) => return InvalidTransaction::Payment.into(),
+        };
+        let (tip, fee_without_tip) = imbalance.split(tip);
+        T::OnTransactionPayment::on_unbalanced(fee_without_tip);
+        T::OnTip::on_unbalanced(tip);
+
+        Ok(ValidTransaction {
+            priority: fee.saturated_into::<TransactionPriority>(),
+            ..Default::default()
+        })
+    }
+}
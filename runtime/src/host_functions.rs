@@ -0,0 +1,165 @@
+//! Host functions of the shielded pools.
+//!
+//! The Orchard and Zcash primitives only build with `std`, so the runtime reaches them through
+//! this interface. The node registers `shielded_crypto::HostFunctions` with its executor.
+
+use codec::{Decode, Encode};
+use sp_runtime_interface::runtime_interface;
+use sp_std::vec::Vec;
+#[cfg(feature = "std")]
+use orchard::{
+    bundle::{Authorized, Flags},
+    circuit::VerifyingKey,
+    note::{ExtractedNoteCommitment, Nullifier, TransmittedNoteCiphertext},
+    primitives::redpallas::{self, SpendAuth},
+    value::ValueCommitment,
+    Action, Bundle, Proof,
+};
+#[cfg(feature = "std")]
+use std::convert::TryFrom;
+#[cfg(feature = "std")]
+use crate::nfts::{OrchardAction, OrchardBundle, SaplingOutputDescription};
+
+/// Length of a raw Sapling payment address.
+#[cfg(feature = "std")]
+const PAYMENT_ADDRESS_LEN: usize = 43;
+
+/// Reasons an Orchard bundle is rejected.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum OrchardError {
+    /// The bundle does not decode or holds an invalid encoding of a field.
+    InvalidBundle,
+    /// The proof or a signature of the bundle does not verify.
+    VerificationFailed,
+}
+
+/// Verifying key of the Orchard circuit, built once per process.
+#[cfg(feature = "std")]
+static ORCHARD_VK: once_cell::sync::Lazy<VerifyingKey> = once_cell::sync::Lazy::new(VerifyingKey::build);
+
+#[cfg(feature = "std")]
+fn orchard_action(action: &OrchardAction) -> Option<Action<redpallas::Signature<SpendAuth>>> {
+    let nullifier = Option::from(Nullifier::from_bytes(&action.nullifier))?;
+    let randomized_key = redpallas::VerificationKey::<SpendAuth>::try_from(action.randomized_key).ok()?;
+    let note_commitment = Option::from(ExtractedNoteCommitment::from_bytes(&action.note_commitment))?;
+    let value_commitment = Option::from(ValueCommitment::from_bytes(&action.value_commitment))?;
+    let encrypted_note = TransmittedNoteCiphertext {
+        epk_bytes: action.ephemeral_key,
+        enc_ciphertext: action.enc_cipher_text,
+        out_ciphertext: action.out_cipher_text,
+    };
+
+    Some(Action::from_parts(
+        nullifier,
+        randomized_key,
+        note_commitment,
+        encrypted_note,
+        value_commitment,
+        redpallas::Signature::from(action.spend_auth_sig),
+    ))
+}
+
+#[cfg(feature = "std")]
+fn orchard_bundle(bundle: &OrchardBundle) -> Option<Bundle<Authorized, i64>> {
+    let actions = bundle.actions.iter()
+        .map(orchard_action)
+        .collect::<Option<Vec<_>>>()?;
+    let authorization = Authorized::from_parts(
+        Proof::new(bundle.zkproof.clone()),
+        redpallas::Signature::from(bundle.binding_sig),
+    );
+
+    Some(Bundle::from_parts(
+        nonempty::NonEmpty::from_vec(actions)?,
+        Flags::from_byte(bundle.flags)?,
+        bundle.value_balance,
+        Option::from(orchard::tree::Anchor::from_bytes(bundle.anchor))?,
+        authorization,
+    ))
+}
+
+#[runtime_interface]
+pub trait ShieldedCrypto {
+    /// Parent of the Sapling tree nodes `lhs` and `rhs` at `level`, hashed with the Pedersen hash.
+    fn sapling_merkle_hash(level: u8, lhs: &[u8; 32], rhs: &[u8; 32]) -> [u8; 32] {
+        zcash_primitives::sapling::merkle_hash(level as usize, lhs, rhs)
+    }
+
+    /// Parent of the Orchard tree nodes `lhs` and `rhs` at `level`, hashed with Sinsemilla.
+    /// `None` if a node is not a valid field element.
+    fn orchard_merkle_hash(level: u8, lhs: &[u8; 32], rhs: &[u8; 32]) -> Option<[u8; 32]> {
+        use incrementalmerkletree::Hashable;
+        use orchard::tree::MerkleHashOrchard;
+
+        let lhs: Option<MerkleHashOrchard> = MerkleHashOrchard::from_bytes(lhs).into();
+        let rhs: Option<MerkleHashOrchard> = MerkleHashOrchard::from_bytes(rhs).into();
+        Some(MerkleHashOrchard::combine(level.into(), &lhs?, &rhs?).to_bytes())
+    }
+
+    /// Verify the Halo 2 proof, spend authorization signatures and binding signature of the
+    /// encoded Orchard `bundle` against `sighash`.
+    fn verify_orchard_bundle(bundle: &[u8], sighash: &[u8; 32]) -> Result<(), OrchardError> {
+        let bundle = OrchardBundle::decode(&mut &bundle[..]).ok()
+            .and_then(|bundle| orchard_bundle(&bundle))
+            .ok_or(OrchardError::InvalidBundle)?;
+
+        bundle.verify_proof(&ORCHARD_VK)
+            .map_err(|_| OrchardError::VerificationFailed)?;
+        for action in bundle.actions().iter() {
+            action.rk().verify(&sighash[..], action.authorization())
+                .map_err(|_| OrchardError::VerificationFailed)?;
+        }
+        bundle.binding_validating_key()
+            .verify(&sighash[..], bundle.authorization().binding_signature())
+            .map_err(|_| OrchardError::VerificationFailed)
+    }
+
+    /// The commitment of the Sapling note of `value` to the raw payment `address` with `rseed`,
+    /// under the ZIP 212 rules. `None` if `address` is not a valid payment address.
+    fn sapling_note_commitment(address: &[u8], value: u64, rseed: &[u8; 32]) -> Option<[u8; 32]> {
+        use zcash_primitives::sapling::{PaymentAddress, Rseed};
+
+        let address = <[u8; PAYMENT_ADDRESS_LEN]>::try_from(address).ok()?;
+        let note = PaymentAddress::from_bytes(&address)?.create_note(value, Rseed::AfterZip212(*rseed))?;
+        Some(note.cmu().to_bytes())
+    }
+
+    /// Decrypt the encoded Sapling `output` with its outgoing cipher key and return the value and
+    /// raw recipient address of its note. Only note plaintexts of `plaintext_version` are
+    /// accepted: 1 before ZIP 212 and 2 after. `None` if the output does not decrypt or the note
+    /// does not open the note commitment of the output.
+    fn recover_sapling_payment(output: &[u8], ock: &[u8; 32], plaintext_version: u8) -> Option<(u64, Vec<u8>)> {
+        use zcash_note_encryption::{EphemeralKeyBytes, OutgoingCipherKey};
+        use zcash_primitives::{
+            consensus::{MainNetwork, NetworkUpgrade, Parameters, ZIP212_GRACE_PERIOD},
+            sapling::note_encryption::try_sapling_output_recovery_with_ock,
+            transaction::components::OutputDescription,
+        };
+
+        let output = SaplingOutputDescription::decode(&mut &output[..]).ok()?.inner;
+        let output = OutputDescription {
+            cv: Option::from(jubjub::ExtendedPoint::from_bytes(&output.value_commitment))?,
+            cmu: Option::from(bls12_381::Scalar::from_bytes(&output.note_commitment))?,
+            ephemeral_key: EphemeralKeyBytes(output.ephemeral_key),
+            enc_ciphertext: output.enc_cipher_text,
+            out_ciphertext: output.out_cipher_text,
+            zkproof: output.zkproof,
+        };
+        // The Zcash rules accept a single plaintext version before Canopy and after its grace
+        // period, so decrypt at the height where only `plaintext_version` is valid.
+        let canopy = MainNetwork.activation_height(NetworkUpgrade::Canopy)?;
+        let height = match plaintext_version {
+            1 => canopy - 1,
+            2 => canopy + ZIP212_GRACE_PERIOD,
+            _ => return None,
+        };
+        let (note, address, _) = try_sapling_output_recovery_with_ock(
+            &MainNetwork,
+            height,
+            &OutgoingCipherKey(*ock),
+            &output,
+        )?;
+        Some((note.value, address.to_bytes().to_vec()))
+    }
+}
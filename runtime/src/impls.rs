@@ -1,10 +1,14 @@
 //! Some configurable implementations as associated type for the substrate runtime.
 
-use node_primitives::Balance;
+use codec::{Decode, Encode};
+use node_primitives::{AccountId, Balance};
+use pallet_collective::MemberCount;
 use sp_runtime::traits::{Convert, Saturating};
-use sp_runtime::{Fixed64, Perbill};
-use frame_support::{traits::{OnUnbalanced, Currency, Get}, weights::Weight};
-use crate::{Balances, System, Authorship, MaximumBlockWeight, NegativeImbalance};
+use sp_runtime::{Fixed64, RuntimeDebug};
+use sp_std::cmp::Ordering;
+use frame_support::{traits::{OnUnbalanced, Currency, EnsureOrigin, Get, Imbalance}, weights::Weight};
+use crate::{Balances, System, Authorship, Fees, Treasury, MaximumBlockWeight, NegativeImbalance};
+use crate::{Call, CouncilCollective, Origin, proxy::ProxyFilter, vesting};
 
 pub struct Author;
 impl OnUnbalanced<NegativeImbalance> for Author {
@@ -13,6 +17,19 @@ impl OnUnbalanced<NegativeImbalance> for Author {
 	}
 }
 
+/// Splits transaction fees between the block author and the treasury according to the
+/// governance set `Fees::author_fee_share`. Tips don't go through here, they always go to the
+/// author.
+pub struct DealWithFees;
+impl OnUnbalanced<NegativeImbalance> for DealWithFees {
+	fn on_nonzero_unbalanced(amount: NegativeImbalance) {
+		let to_author = Fees::author_fee_share() * amount.peek();
+		let (to_author, to_treasury) = amount.split(to_author);
+		Author::on_unbalanced(to_author);
+		Treasury::on_unbalanced(to_treasury);
+	}
+}
+
 /// Struct that handles the conversion of Balance -> `u64`. This is used for staking's election
 /// calculation.
 pub struct CurrencyToVoteHandler;
@@ -45,18 +62,18 @@ impl<C: Get<Balance>> Convert<Weight, Balance> for LinearWeightToFee<C> {
 /// Update the given multiplier based on the following formula
 ///
 ///   diff = (previous_block_weight - target_weight)
-///   v = 0.00004
 ///   next_weight = weight * (1 + (v . diff) + (v . diff)^2 / 2)
 ///
-/// Where `target_weight` must be given as the `Get` implementation of the `T` generic type.
+/// Where `v`, the target fullness of blocks and the value the multiplier never goes below are
+/// set in the `Fees` module.
 /// https://research.web3.foundation/en/latest/polkadot/Token%20Economics/#relay-chain-transaction-fees
-pub struct TargetedFeeAdjustment<T>(sp_std::marker::PhantomData<T>);
+pub struct TargetedFeeAdjustment;
 
-impl<T: Get<Perbill>> Convert<Fixed64, Fixed64> for TargetedFeeAdjustment<T> {
+impl Convert<Fixed64, Fixed64> for TargetedFeeAdjustment {
 	fn convert(multiplier: Fixed64) -> Fixed64 {
 		let block_weight = System::all_extrinsics_weight();
 		let max_weight = MaximumBlockWeight::get();
-		let target_weight = (T::get() * max_weight) as u128;
+		let target_weight = (Fees::target_block_fullness() * max_weight) as u128;
 		let block_weight = block_weight as u128;
 
 		// determines if the first_term is positive
@@ -66,11 +83,14 @@ impl<T: Get<Perbill>> Convert<Fixed64, Fixed64> for TargetedFeeAdjustment<T> {
 		let diff = Fixed64::from_rational(diff_abs as i64, max_weight as u64);
 		let diff_squared = diff.saturating_mul(diff);
 
-		// 0.00004 = 4/100_000 = 40_000/10^9
-		let v = Fixed64::from_rational(4, 100_000);
-		// 0.00004^2 = 16/10^10 ~= 2/10^9. Taking the future /2 into account, then it is just 1
-		// parts from a billionth.
-		let v_squared_2 = Fixed64::from_rational(1, 1_000_000_000);
+		let v = Fees::fee_adjustment_speed();
+		// v^2 / 2 is rounded up: for a sane `v` it is a few parts of a billionth at most, and
+		// would otherwise vanish. With the default `v = 0.00004` it is 1 part of a billionth.
+		let billion = Fixed64::accuracy() as i128;
+		let v_parts = v.into_inner() as i128;
+		let v_squared_2 = Fixed64::from_parts(
+			((v_parts * v_parts + 2 * billion - 1) / (2 * billion)) as i64
+		);
 
 		let first_term = v.saturating_mul(diff);
 		// It is very unlikely that this will exist (in our poor perbill estimate) but we are giving
@@ -83,109 +103,256 @@ impl<T: Get<Perbill>> Convert<Fixed64, Fixed64> for TargetedFeeAdjustment<T> {
 			let excess = first_term.saturating_add(second_term);
 			multiplier.saturating_add(excess)
 		} else {
-			// Proof: first_term > second_term. Safe subtraction.
-			let negative = first_term - second_term;
+			// Proof: first_term > second_term for `v < 2`. Saturating all the same.
+			let negative = first_term.saturating_sub(second_term);
 			multiplier.saturating_sub(negative)
 				// despite the fact that apply_to saturates weight (final fee cannot go below 0)
 				// it is crucially important to stop here and don't further reduce the weight fee
 				// multiplier. While at -1, it means that the network is so un-congested that all
 				// transactions have no weight fee. We stop here and only increase if the network
 				// became more busy.
-				.max(Fixed64::from_rational(-1, 1))
+				.max(Fees::minimum_multiplier())
+		}
+	}
+}
+
+/// Origin a task of the `Scheduler` is scheduled and dispatched with.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum TaskOrigin {
+	Root,
+	/// A motion carried by the given number of council members, out of the whole council.
+	Council(MemberCount, MemberCount),
+}
+
+/// Root holds more power than the council. Council motions compare by the share of the council
+/// that carried them, and then by the number of members that did.
+impl PartialOrd for TaskOrigin {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(match (self, other) {
+			(TaskOrigin::Root, TaskOrigin::Root) => Ordering::Equal,
+			(TaskOrigin::Root, TaskOrigin::Council(..)) => Ordering::Greater,
+			(TaskOrigin::Council(..), TaskOrigin::Root) => Ordering::Less,
+			(TaskOrigin::Council(ayes, total), TaskOrigin::Council(other_ayes, other_total)) => {
+				let share = *ayes as u64 * *other_total as u64;
+				let other_share = *other_ayes as u64 * *total as u64;
+				share.cmp(&other_share).then(ayes.cmp(other_ayes))
+			},
+		})
+	}
+}
+
+impl From<TaskOrigin> for Origin {
+	fn from(origin: TaskOrigin) -> Origin {
+		match origin {
+			TaskOrigin::Root => frame_system::RawOrigin::Root.into(),
+			TaskOrigin::Council(ayes, total) =>
+				pallet_collective::RawOrigin::<AccountId, CouncilCollective>::Members(ayes, total).into(),
 		}
 	}
 }
 
+/// Ensures the origin is root or a motion carried by more than half of the council.
+pub struct EnsureRootOrHalfCouncil;
+
+impl EnsureOrigin<Origin> for EnsureRootOrHalfCouncil {
+	type Success = TaskOrigin;
+
+	fn try_origin(o: Origin) -> Result<TaskOrigin, Origin> {
+		let o = match Into::<Result<frame_system::RawOrigin<AccountId>, Origin>>::into(o) {
+			Ok(frame_system::RawOrigin::Root) => return Ok(TaskOrigin::Root),
+			Ok(o) => return Err(Origin::from(o)),
+			Err(o) => o,
+		};
+		match Into::<Result<pallet_collective::RawOrigin<AccountId, CouncilCollective>, Origin>>::into(o) {
+			Ok(pallet_collective::RawOrigin::Members(ayes, total)) if ayes * 2 > total =>
+				Ok(TaskOrigin::Council(ayes, total)),
+			Ok(o) => Err(Origin::from(o)),
+			Err(o) => Err(o),
+		}
+	}
+}
+
+/// The types of proxies of the `Proxy` module.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum ProxyType {
+	/// Any call.
+	Any,
+	/// Any call that can't move funds.
+	NonTransfer,
+	/// Council, elections, democracy and treasury calls.
+	Governance,
+	/// Staking and session key calls.
+	Staking,
+	/// Calls of the shielded pools and rewards.
+	Shielded,
+}
+
+impl ProxyFilter<Call> for ProxyType {
+	fn filter(&self, call: &Call) -> bool {
+		match self {
+			ProxyType::Any => true,
+			// calls that dispatch other calls could move funds too.
+			ProxyType::NonTransfer => !matches!(call,
+				Call::Balances(..) | Call::Vesting(vesting::Call::vested_transfer(..)) | Call::Indices(..) |
+				Call::Nfts(..) | Call::ShieldedRewards(..) |
+				Call::Utility(..) | Call::MultiAccount(..) | Call::Proxy(..) | Call::Recovery(..)
+			),
+			ProxyType::Governance => matches!(call,
+				Call::Democracy(..) | Call::Council(..) | Call::Elections(..) | Call::Treasury(..)
+			),
+			ProxyType::Staking => matches!(call, Call::Staking(..) | Call::Session(..)),
+			ProxyType::Shielded => matches!(call, Call::Nfts(..) | Call::ShieldedRewards(..)),
+		}
+	}
+
+	fn is_high_risk(&self) -> bool {
+		// shielded proxies can spend notes and redirect shielded rewards.
+		matches!(self, ProxyType::Any | ProxyType::Shielded)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use sp_runtime::assert_eq_error_rate;
+	use sp_runtime::{assert_eq_error_rate, Perbill};
 	use crate::{MaximumBlockWeight, AvailableBlockRatio, Runtime};
-	use crate::{constants::currency::*, TransactionPayment, TargetBlockFullness};
-	use frame_support::weights::Weight;
+	use crate::{constants::currency::*, TransactionPayment};
+	use crate::{fees::{self, ChargeTransactionPayment}, AccountId, Call, CouncilCollective, Origin};
+	use frame_support::weights::{DispatchClass, DispatchInfo, Weight};
+	use sp_runtime::traits::SignedExtension;
+
+	/// Parameters of `TargetedFeeAdjustment`, as set in the `Fees` module.
+	#[derive(Clone, Copy, Debug)]
+	struct FeeParameters {
+		v: Fixed64,
+		target: Perbill,
+		minimum: Fixed64,
+	}
+
+	impl Default for FeeParameters {
+		fn default() -> Self {
+			FeeParameters {
+				v: Fixed64::from_rational(4, 100_000),
+				target: Perbill::from_percent(25),
+				minimum: Fixed64::from_rational(-1, 1),
+			}
+		}
+	}
+
+	impl FeeParameters {
+		fn target(&self) -> Weight {
+			self.target * max()
+		}
+	}
+
+	/// The default parameters and a few alternatives they may be tuned to.
+	fn parameter_sets() -> Vec<FeeParameters> {
+		vec![
+			FeeParameters::default(),
+			FeeParameters {
+				v: Fixed64::from_rational(8, 100_000),
+				target: Perbill::from_percent(50),
+				minimum: Fixed64::from_rational(-1, 2),
+			},
+			FeeParameters {
+				v: Fixed64::from_rational(2, 100_000),
+				target: Perbill::from_percent(10),
+				minimum: Fixed64::from_rational(-1, 4),
+			},
+		]
+	}
 
 	fn max() -> Weight {
 		MaximumBlockWeight::get()
 	}
 
 	fn target() -> Weight {
-		TargetBlockFullness::get() * max()
+		FeeParameters::default().target()
 	}
 
 	// poc reference implementation.
-	fn fee_multiplier_update(block_weight: Weight, previous: Fixed64) -> Fixed64  {
+	fn fee_multiplier_update(params: FeeParameters, block_weight: Weight, previous: Fixed64) -> Fixed64  {
 		let block_weight = block_weight as f32;
-		let v: f32 = 0.00004;
+		let v = params.v.into_inner() as f32 / Fixed64::accuracy() as f32;
 
 		// maximum tx weight
 		let m = max() as f32;
 		// Ideal saturation in terms of weight
-		let ss = target() as f32;
+		let ss = params.target() as f32;
 		// Current saturation in terms of weight
 		let s = block_weight;
 
 		let fm = v * (s/m - ss/m) + v.powi(2) * (s/m - ss/m).powi(2) / 2.0;
 		let addition_fm = Fixed64::from_parts((fm * 1_000_000_000_f32).round() as i64);
-		previous.saturating_add(addition_fm)
+		previous.saturating_add(addition_fm).max(params.minimum)
 	}
 
 	fn feemul(parts: i64) -> Fixed64 {
 		Fixed64::from_parts(parts)
 	}
 
-	fn run_with_system_weight<F>(w: Weight, assertions: F) where F: Fn() -> () {
+	fn run_with_fee_parameters<F>(params: FeeParameters, w: Weight, assertions: F) where F: Fn() -> () {
 		let mut t: sp_io::TestExternalities =
 			frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap().into();
 		t.execute_with(|| {
+			assert!(Fees::set_fee_adjustment_speed(Origin::ROOT, params.v).is_ok());
+			assert!(Fees::set_target_block_fullness(Origin::ROOT, params.target).is_ok());
+			assert!(Fees::set_minimum_multiplier(Origin::ROOT, params.minimum).is_ok());
 			System::set_block_limits(w, 0);
 			assertions()
 		});
 	}
 
+	fn run_with_system_weight<F>(w: Weight, assertions: F) where F: Fn() -> () {
+		run_with_fee_parameters(FeeParameters::default(), w, assertions)
+	}
+
 	#[test]
 	fn fee_multiplier_update_poc_works() {
 		let fm = Fixed64::from_rational(0, 1);
-		let test_set = vec![
-			(0, fm.clone()),
-			(100, fm.clone()),
-			(target(), fm.clone()),
-			(max() / 2, fm.clone()),
-			(max(), fm.clone()),
-		];
-		test_set.into_iter().for_each(|(w, fm)| {
-			run_with_system_weight(w, || {
-				assert_eq_error_rate!(
-					fee_multiplier_update(w, fm).into_inner(),
-					TargetedFeeAdjustment::<TargetBlockFullness>::convert(fm).into_inner(),
-					5,
-				);
+		for params in parameter_sets() {
+			let test_set = vec![
+				(0, fm.clone()),
+				(100, fm.clone()),
+				(params.target(), fm.clone()),
+				(max() / 2, fm.clone()),
+				(max(), fm.clone()),
+			];
+			test_set.into_iter().for_each(|(w, fm)| {
+				run_with_fee_parameters(params, w, || {
+					assert_eq_error_rate!(
+						fee_multiplier_update(params, w, fm).into_inner(),
+						TargetedFeeAdjustment::convert(fm).into_inner(),
+						5,
+					);
+				})
 			})
-		})
+		}
 	}
 
 	#[test]
 	fn empty_chain_simulation() {
 		// just a few txs per_block.
 		let block_weight = 0;
-		run_with_system_weight(block_weight, || {
-			let mut fm = Fixed64::default();
-			let mut iterations: u64 = 0;
-			loop {
-				let next = TargetedFeeAdjustment::<TargetBlockFullness>::convert(fm);
-				fm = next;
-				if fm == Fixed64::from_rational(-1, 1) { break; }
-				iterations += 1;
-			}
-			println!("iteration {}, new fm = {:?}. Weight fee is now zero", iterations, fm);
-			assert!(iterations > 50_000, "This assertion is just a warning; Don't panic. \
-				Current substrate/polkadot node are configured with a _slow adjusting fee_ \
-				mechanism. Hence, it is really unlikely that fees collapse to zero even on an \
-				empty chain in less than at least of couple of thousands of empty blocks. But this \
-				simulation indicates that fees collapsed to zero after {} almost-empty blocks. \
-				Check it",
-				iterations,
-			);
-		})
+		for params in parameter_sets() {
+			run_with_fee_parameters(params, block_weight, || {
+				let mut fm = Fixed64::default();
+				let mut iterations: u64 = 0;
+				loop {
+					let next = TargetedFeeAdjustment::convert(fm);
+					fm = next;
+					if fm == params.minimum { break; }
+					iterations += 1;
+				}
+				println!("iteration {}, new fm = {:?}. Weight fee is now at its minimum", iterations, fm);
+
+				// every empty block lowers the multiplier by about `v * target`.
+				let step = params.v.into_inner() as u128 * params.target.deconstruct() as u128 /
+					1_000_000_000;
+				let expected = params.minimum.into_inner().abs() as u128 / step;
+				assert_eq_error_rate!(iterations as u128, expected, expected / 100);
+			});
+		}
 	}
 
 	#[test]
@@ -199,32 +366,35 @@ mod tests {
 		// Default substrate minimum.
 		let tx_weight = 10_000;
 
-		run_with_system_weight(block_weight, || {
-			// initial value configured on module
-			let mut fm = Fixed64::default();
-			assert_eq!(fm, TransactionPayment::next_fee_multiplier());
-
-			let mut iterations: u64 = 0;
-			loop {
-				let next = TargetedFeeAdjustment::<TargetBlockFullness>::convert(fm);
-				// if no change, panic. This should never happen in this case.
-				if fm == next { panic!("The fee should ever increase"); }
-				fm = next;
-				iterations += 1;
-				let fee = <Runtime as pallet_transaction_payment::Trait>::WeightToFee::convert(tx_weight);
-				let adjusted_fee = fm.saturated_multiply_accumulate(fee);
-				println!(
-					"iteration {}, new fm = {:?}. Fee at this point is: {} units / {} milli RAD, \
-					{} centi RAD, {} RAD",
-					iterations,
-					fm,
-					adjusted_fee,
-					adjusted_fee / MILLI_RAD,
-					adjusted_fee / CENTI_RAD,
-					adjusted_fee / RAD,
-				);
-			}
-		});
+		for params in parameter_sets() {
+			run_with_fee_parameters(params, block_weight, || {
+				// initial value configured on module
+				let mut fm = Fixed64::default();
+				assert_eq!(fm, TransactionPayment::next_fee_multiplier());
+
+				let mut iterations: u64 = 0;
+				loop {
+					let next = TargetedFeeAdjustment::convert(fm);
+					// if no change, panic. This should never happen in this case.
+					if fm == next { panic!("The fee should ever increase"); }
+					fm = next;
+					iterations += 1;
+					let fee = <Runtime as pallet_transaction_payment::Trait>::WeightToFee::convert(tx_weight);
+					let adjusted_fee = fm.saturated_multiply_accumulate(fee);
+					println!(
+						"{:?}: iteration {}, new fm = {:?}. Fee at this point is: {} units / {} milli RAD, \
+						{} centi RAD, {} RAD",
+						params,
+						iterations,
+						fm,
+						adjusted_fee,
+						adjusted_fee / MILLI_RAD,
+						adjusted_fee / CENTI_RAD,
+						adjusted_fee / RAD,
+					);
+				}
+			});
+		}
 	}
 
 	#[test]
@@ -232,14 +402,14 @@ mod tests {
 		run_with_system_weight(target() / 4, || {
 			// Light block. Fee is reduced a little.
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(Fixed64::default()),
+				TargetedFeeAdjustment::convert(Fixed64::default()),
 				feemul(-7500),
 			);
 		});
 		run_with_system_weight(target() / 2, || {
 			// a bit more. Fee is decreased less, meaning that the fee increases as the block grows.
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(Fixed64::default()),
+				TargetedFeeAdjustment::convert(Fixed64::default()),
 				feemul(-5000),
 			);
 
@@ -247,62 +417,87 @@ mod tests {
 		run_with_system_weight(target(), || {
 			// ideal. Original fee. No changes.
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(Fixed64::default()),
+				TargetedFeeAdjustment::convert(Fixed64::default()),
 				feemul(0),
 			);
 		});
 		run_with_system_weight(target() * 2, || {
 			// // More than ideal. Fee is increased.
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(Fixed64::default()),
+				TargetedFeeAdjustment::convert(Fixed64::default()),
 				feemul(10000),
 			);
 		});
+
+		// twice the speed and a target twice as high: an empty block lowers the fee four times
+		// as much, give or take the second order term.
+		let params = FeeParameters {
+			v: Fixed64::from_rational(8, 100_000),
+			target: Perbill::from_percent(50),
+			..Default::default()
+		};
+		run_with_fee_parameters(params, 0, || {
+			assert_eq_error_rate!(
+				TargetedFeeAdjustment::convert(Fixed64::default()).into_inner(),
+				-40000,
+				1,
+			);
+		});
 	}
 
 	#[test]
 	fn stateful_weight_mul_grow_to_infinity() {
 		run_with_system_weight(target() * 2, || {
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(Fixed64::default()),
+				TargetedFeeAdjustment::convert(Fixed64::default()),
 				feemul(10000)
 			);
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(feemul(10000)),
+				TargetedFeeAdjustment::convert(feemul(10000)),
 				feemul(20000)
 			);
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(feemul(20000)),
+				TargetedFeeAdjustment::convert(feemul(20000)),
 				feemul(30000)
 			);
 			// ...
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(feemul(1_000_000_000)),
+				TargetedFeeAdjustment::convert(feemul(1_000_000_000)),
 				feemul(1_000_000_000 + 10000)
 			);
 		});
 	}
 
 	#[test]
-	fn stateful_weight_mil_collapse_to_minus_one() {
+	fn stateful_weight_mil_collapse_to_minimum() {
+		for params in parameter_sets() {
+			run_with_fee_parameters(params, 0, || {
+				let step = TargetedFeeAdjustment::convert(Fixed64::default());
+				assert!(step < Fixed64::default());
+				assert_eq!(
+					TargetedFeeAdjustment::convert(step),
+					step.saturating_add(step)
+				);
+				// ...
+				assert_eq!(
+					TargetedFeeAdjustment::convert(params.minimum),
+					params.minimum
+				);
+				assert_eq!(
+					TargetedFeeAdjustment::convert(feemul(1_000_000_000 * -1)),
+					params.minimum
+				);
+			});
+		}
 		run_with_system_weight(0, || {
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(Fixed64::default()),
+				TargetedFeeAdjustment::convert(Fixed64::default()),
 				feemul(-10000)
 			);
 			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(feemul(-10000)),
+				TargetedFeeAdjustment::convert(feemul(-10000)),
 				feemul(-20000)
 			);
-			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(feemul(-20000)),
-				feemul(-30000)
-			);
-			// ...
-			assert_eq!(
-				TargetedFeeAdjustment::<TargetBlockFullness>::convert(feemul(1_000_000_000 * -1)),
-				feemul(-1_000_000_000)
-			);
 		})
 	}
 
@@ -312,37 +507,162 @@ mod tests {
 		let mb = kb * kb;
 		let max_fm = Fixed64::from_natural(i64::max_value());
 
-		// check that for all values it can compute, correctly.
-		vec![
-			0,
-			1,
-			10,
-			1000,
-			kb,
-			10 * kb,
-			100 * kb,
-			mb,
-			10 * mb,
-			Weight::max_value() / 2,
-			Weight::max_value(),
-		].into_iter().for_each(|i| {
-			run_with_system_weight(i, || {
-				let next = TargetedFeeAdjustment::<TargetBlockFullness>::convert(Fixed64::default());
-				let truth = fee_multiplier_update(i, Fixed64::default());
-				assert_eq_error_rate!(truth.into_inner(), next.into_inner(), 5);
+		for params in parameter_sets() {
+			// check that for all values it can compute, correctly.
+			vec![
+				0,
+				1,
+				10,
+				1000,
+				kb,
+				10 * kb,
+				100 * kb,
+				mb,
+				10 * mb,
+				Weight::max_value() / 2,
+				Weight::max_value(),
+			].into_iter().for_each(|i| {
+				run_with_fee_parameters(params, i, || {
+					let next = TargetedFeeAdjustment::convert(Fixed64::default());
+					let truth = fee_multiplier_update(params, i, Fixed64::default());
+					assert_eq_error_rate!(truth.into_inner(), next.into_inner(), 5);
+				});
 			});
+
+			// Some values that are all above the target and will cause an increase.
+			let t = params.target();
+			vec![t + 100, t * 2, t * 4]
+				.into_iter()
+				.for_each(|i| {
+					run_with_fee_parameters(params, i, || {
+						let fm = TargetedFeeAdjustment::convert(max_fm);
+						// won't grow. The convert saturates everything.
+						assert_eq!(fm, max_fm);
+					})
+				});
+		}
+	}
+
+	#[test]
+	fn fee_parameters_are_checked() {
+		run_with_system_weight(0, || {
+			let council = pallet_collective::RawOrigin::<AccountId, CouncilCollective>::Members(2, 3);
+			assert!(Fees::set_fee_adjustment_speed(Origin::from(council), Fixed64::from_rational(1, 10_000)).is_ok());
+			assert_eq!(Fees::fee_adjustment_speed(), Fixed64::from_rational(1, 10_000));
+
+			let who = AccountId::from([1u8; 32]);
+			assert!(Fees::set_target_block_fullness(Origin::signed(who), Perbill::one()).is_err());
+			assert!(Fees::set_fee_adjustment_speed(Origin::ROOT, Fixed64::default()).is_err());
+			assert_eq!(
+				Fees::set_fee_adjustment_speed(Origin::ROOT, Fixed64::from_rational(2, 1)),
+				Err(fees::Error::<Runtime>::SpeedTooHigh.into())
+			);
+			assert!(Fees::set_fee_adjustment_speed(Origin::ROOT, Fixed64::from_rational(19, 10)).is_ok());
+			assert_eq!(
+				Fees::set_target_block_fullness(Origin::ROOT, Perbill::zero()),
+				Err(fees::Error::<Runtime>::InvalidTargetFullness.into())
+			);
+			assert_eq!(
+				Fees::set_target_block_fullness(Origin::ROOT, AvailableBlockRatio::get()),
+				Err(fees::Error::<Runtime>::InvalidTargetFullness.into())
+			);
+			assert!(Fees::set_target_block_fullness(Origin::ROOT, Perbill::from_percent(74)).is_ok());
+			assert!(Fees::set_minimum_multiplier(Origin::ROOT, Fixed64::from_rational(-2, 1)).is_err());
+			assert!(Fees::set_minimum_multiplier(Origin::ROOT, Fixed64::from_rational(1, 2)).is_err());
 		});
+	}
 
-		// Some values that are all above the target and will cause an increase.
-		let t = target();
-		vec![t + 100, t * 2, t * 4]
-			.into_iter()
-			.for_each(|i| {
-				run_with_system_weight(i, || {
-					let fm = TargetedFeeAdjustment::<TargetBlockFullness>::convert(max_fm);
-					// won't grow. The convert saturates everything.
-					assert_eq!(fm, max_fm);
-				})
-			});
+	fn run_with_balances<F>(balances: Vec<(AccountId, Balance)>, assertions: F) where F: Fn() -> () {
+		let mut t = frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap();
+		pallet_balances::GenesisConfig::<Runtime> { balances }.assimilate_storage(&mut t).unwrap();
+		sp_io::TestExternalities::from(t).execute_with(assertions);
+	}
+
+	/// Charge `who` for a remark with `tip` and return the fee without the tip.
+	fn charge(who: &AccountId, tip: Balance) -> Balance {
+		let info = DispatchInfo { weight: 10_000, class: DispatchClass::Normal, pays_fee: true };
+		let call = Call::System(frame_system::Call::remark(vec![]));
+		assert!(ChargeTransactionPayment::<Runtime>::from(tip).pre_dispatch(who, &call, info, 100).is_ok());
+		pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::compute_fee(100, info, 0)
+	}
+
+	#[test]
+	fn fees_are_split_between_author_and_treasury() {
+		let who = AccountId::from([1u8; 32]);
+		run_with_balances(vec![(who.clone(), 10 * RAD)], || {
+			let author = Authorship::author();
+			let fee = charge(&who, 0);
+			let to_author = Perbill::from_percent(20) * fee;
+			assert!(to_author > 0);
+			assert_eq!(Balances::free_balance(&author), to_author);
+			assert_eq!(Balances::free_balance(&Treasury::account_id()), fee - to_author);
+			assert_eq!(Balances::free_balance(&who), 10 * RAD - fee);
+		});
+	}
+
+	#[test]
+	fn tips_go_to_the_author() {
+		let who = AccountId::from([1u8; 32]);
+		run_with_balances(vec![(who.clone(), 10 * RAD)], || {
+			let author = Authorship::author();
+			let tip = 1 * CENTI_RAD;
+			let fee = charge(&who, tip);
+			let to_author = Perbill::from_percent(20) * fee;
+			assert_eq!(Balances::free_balance(&author), to_author + tip);
+			assert_eq!(Balances::free_balance(&Treasury::account_id()), fee - to_author);
+			assert_eq!(Balances::free_balance(&who), 10 * RAD - fee - tip);
+		});
+	}
+
+	#[test]
+	fn council_sets_the_author_fee_share() {
+		let who = AccountId::from([1u8; 32]);
+		run_with_balances(vec![(who.clone(), 10 * RAD)], || {
+			assert!(Fees::set_author_fee_share(Origin::signed(who.clone()), Perbill::one()).is_err());
+			let council = pallet_collective::RawOrigin::<AccountId, CouncilCollective>::Members(2, 3);
+			assert!(Fees::set_author_fee_share(Origin::from(council), Perbill::from_percent(50)).is_ok());
+			assert_eq!(Fees::author_fee_share(), Perbill::from_percent(50));
+
+			let author = Authorship::author();
+			let fee = charge(&who, 0);
+			let to_author = Perbill::from_percent(50) * fee;
+			assert_eq!(Balances::free_balance(&author), to_author);
+			assert_eq!(Balances::free_balance(&Treasury::account_id()), fee - to_author);
+		});
+	}
+
+	#[test]
+	fn scheduler_origins() {
+		let council = |ayes, total| Origin::from(
+			pallet_collective::RawOrigin::<AccountId, CouncilCollective>::Members(ayes, total)
+		);
+		assert_eq!(EnsureRootOrHalfCouncil::try_origin(Origin::ROOT).ok(), Some(TaskOrigin::Root));
+		assert_eq!(EnsureRootOrHalfCouncil::try_origin(council(3, 5)).ok(), Some(TaskOrigin::Council(3, 5)));
+		assert!(EnsureRootOrHalfCouncil::try_origin(council(2, 4)).is_err());
+		assert!(EnsureRootOrHalfCouncil::try_origin(Origin::signed(AccountId::from([1u8; 32]))).is_err());
+
+		assert!(TaskOrigin::Root > TaskOrigin::Council(5, 5));
+		assert!(TaskOrigin::Council(3, 4) > TaskOrigin::Council(3, 5));
+		assert!(TaskOrigin::Council(4, 6) > TaskOrigin::Council(2, 3));
+		assert!(TaskOrigin::Council(2, 3) < TaskOrigin::Council(4, 6));
+	}
+
+	#[test]
+	fn proxy_types_filter_calls() {
+		let transfer = Call::Balances(pallet_balances::Call::transfer(AccountId::from([1u8; 32]).into(), 1));
+		let vote = Call::Elections(pallet_elections_phragmen::Call::remove_voter());
+		let chill = Call::Staking(pallet_staking::Call::chill());
+		// an index holds a deposit and can be handed to another account.
+		let claim_index = Call::Indices(pallet_indices::Call::claim(0));
+
+		assert!(ProxyType::Any.filter(&transfer));
+		assert!(!ProxyType::NonTransfer.filter(&transfer));
+		assert!(ProxyType::NonTransfer.filter(&vote));
+		assert!(!ProxyType::NonTransfer.filter(&claim_index));
+		assert!(ProxyType::Governance.filter(&vote));
+		assert!(!ProxyType::Governance.filter(&chill));
+		assert!(ProxyType::Staking.filter(&chill));
+		assert!(!ProxyType::Shielded.filter(&chill));
+		assert!(ProxyType::Shielded.is_high_risk() && !ProxyType::Staking.is_high_risk());
 	}
 }
@@ -8,20 +8,20 @@ use sp_std::prelude::*;
 use frame_support::{
 	construct_runtime, parameter_types, debug,
 	weights::Weight,
-	traits::{Currency, Randomness},
+	traits::{Currency, Get, Randomness},
 };
-use sp_core::u32_trait::{_1, _2, _3, _4};
+use sp_core::u32_trait::{_1, _2, _3, _4, _5};
 pub use node_primitives::{AccountId, Signature};
-use node_primitives::{Balance, BlockNumber, Hash, Index, Moment};
+use node_primitives::{AccountIndex, Balance, BlockNumber, Hash, Index, Moment};
 use sp_api::{decl_runtime_apis, impl_runtime_apis};
 use sp_runtime::{
-	Perbill, ApplyExtrinsicResult,
+	Perbill, Percent, Permill, ApplyExtrinsicResult,
 	impl_opaque_keys, generic, create_runtime_str,
 };
 use sp_runtime::curve::PiecewiseLinear;
 use sp_runtime::transaction_validity::TransactionValidity;
 use sp_runtime::traits::{
-	self, BlakeTwo256, Block as BlockT, IdentityLookup, SaturatedConversion,
+	self, BlakeTwo256, Block as BlockT, SaturatedConversion, StaticLookup,
 	OpaqueKeys,
 };
 use sp_version::RuntimeVersion;
@@ -44,10 +44,41 @@ pub use pallet_staking::StakerStatus;
 
 /// Implementations of some helper traits passed into runtime modules as associated types.
 pub mod impls;
-use impls::{CurrencyToVoteHandler, Author, LinearWeightToFee, TargetedFeeAdjustment};
+use impls::{
+	CurrencyToVoteHandler, Author, DealWithFees, EnsureRootOrHalfCouncil, LinearWeightToFee,
+	ProxyType, TargetedFeeAdjustment, TaskOrigin,
+};
+
+/// Governance parameters of transaction fees.
+pub mod fees;
+
+/// Balances locked by vesting schedules.
+pub mod vesting;
+
+/// Delayed and periodic dispatch of calls.
+pub mod scheduler;
+
+/// Proxy accounts with typed permissions.
+pub mod proxy;
+
+/// Migrations run on runtime upgrades.
+mod migrations;
 
 /// nft module
-mod nfts;
+pub mod nfts;
+
+/// Note commitment trees of the shielded pools.
+pub mod commitment_tree;
+
+/// Host functions of the shielded pools.
+pub mod host_functions;
+
+/// Staking rewards paid as shielded notes.
+pub mod shielded_rewards;
+
+/// Document anchoring module.
+pub mod anchor;
+use anchor::AnchorData;
 
 /// Constant values used within the runtime.
 pub mod constants;
@@ -66,8 +97,8 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     // and set impl_version to 0. If only runtime
     // implementation changes and behavior does not, then leave spec_version as
     // is and increment impl_version.
-    spec_version: 226,
-    impl_version: 1,
+    spec_version: 262,
+    impl_version: 0,
     apis: RUNTIME_API_VERSIONS,
 };
 
@@ -105,8 +136,9 @@ impl frame_system::Trait for Runtime {
     type Hashing = BlakeTwo256;
     /// The identifier used to distinguish between accounts.
     type AccountId = AccountId;
-    /// The lookup mechanism to get account ID from whatever is passed in dispatchers.
-    type Lookup = IdentityLookup<AccountId>;
+    /// The lookup mechanism to get account ID from whatever is passed in dispatchers: either
+    /// the account ID itself or its index.
+    type Lookup = Indices;
     /// The header type.
     type Header = generic::Header<BlockNumber, BlakeTwo256>;
     /// The overarching event type.
@@ -134,6 +166,19 @@ impl frame_system::Trait for Runtime {
 	type OnKilledAccount = Balances;
 }
 
+parameter_types! {
+	pub const IndexDeposit: Balance = 1 * RAD;
+}
+
+impl pallet_indices::Trait for Runtime {
+	/// The type for recording indexing into the account enumeration.
+	type AccountIndex = AccountIndex;
+	type Currency = Balances;
+	/// Reserved for as long as an index is claimed.
+	type Deposit = IndexDeposit;
+	type Event = Event;
+}
+
 parameter_types! {
 	// One storage item; value is size 4+4+16+32 bytes = 56 bytes.
 	pub const MultisigDepositBase: Balance = 30 * CENTI_RAD;
@@ -185,17 +230,35 @@ parameter_types! {
     pub const TransactionByteFee: Balance = 1 * MICRO_RAD;
 	// setting this to zero will disable the weight fee.
 	pub const WeightFeeCoefficient: Balance = 100_000_000;
-	// for a sane configuration, this should always be less than `AvailableBlockRatio`.
-	pub const TargetBlockFullness: Perbill = Perbill::from_percent(25);
 }
 
 impl pallet_transaction_payment::Trait for Runtime {
 	type Currency = Balances;
-	type OnTransactionPayment = Author;
+	type OnTransactionPayment = DealWithFees;
 	type TransactionBaseFee = TransactionBaseFee;
 	type TransactionByteFee = TransactionByteFee;
 	type WeightToFee = LinearWeightToFee<WeightFeeCoefficient>;
-	type FeeMultiplierUpdate = TargetedFeeAdjustment<TargetBlockFullness>;
+	/// Its parameters are set in the `Fees` module.
+	type FeeMultiplierUpdate = TargetedFeeAdjustment;
+}
+
+impl fees::Trait for Runtime {
+	type Event = Event;
+	/// Two thirds of the council can change the fee parameters, as can a referendum.
+	type SetOrigin = pallet_collective::EnsureProportionAtLeast<_2, _3, AccountId, CouncilCollective>;
+	type OnTip = Author;
+}
+
+parameter_types! {
+	pub const MinVestedTransfer: Balance = 100 * RAD;
+	pub const MaxVestingSchedules: u32 = 16;
+}
+
+impl vesting::Trait for Runtime {
+	type Event = Event;
+	type Currency = Balances;
+	type MinVestedTransfer = MinVestedTransfer;
+	type MaxVestingSchedules = MaxVestingSchedules;
 }
 
 parameter_types! {
@@ -237,7 +300,7 @@ impl pallet_session::Trait for Runtime {
 	type ValidatorId = <Self as frame_system::Trait>::AccountId;
 	type ValidatorIdOf = pallet_staking::StashOf<Self>;
 	type ShouldEndSession = Babe;
-	type SessionManager = Staking;
+	type SessionManager = shielded_rewards::ShieldRewards<Self, Staking>;
 	type SessionHandler = <SessionKeys as OpaqueKeys>::KeyTypeIdProviders;
 	type Keys = SessionKeys;
 	type DisabledValidatorsThreshold = DisabledValidatorsThreshold;
@@ -248,16 +311,19 @@ impl pallet_session::historical::Trait for Runtime {
 	type FullIdentificationOf = pallet_staking::ExposureOf<Runtime>;
 }
 
-// set to almost three percent to correct for a bug, see https://github.com/paritytech/substrate/issues/4964 for
-// details and https://play.rust-lang.org/?version=stable&mode=debug&edition=2018&gist=e6490da3bfb28f69c7f6e6393ec6bb0c
-// for the calculation
-// TODO: set back to Perbill::from_percent(3) as soon as the bug above is fixed.
-const THREE_PERCENT_INFLATION: Perbill = Perbill::from_parts(29_559_999);
-
-const REWARD_CURVE: PiecewiseLinear<'static> = PiecewiseLinear {
-	points: &[(Perbill::from_percent(0), THREE_PERCENT_INFLATION)],
-	maximum: THREE_PERCENT_INFLATION,
-};
+// Yearly inflation paid to stakers, from 2.5% with nothing staked up to 10% at the ideal staking
+// ratio of 50%, then falling off towards 2.5% again. The part of the 10% that isn't paid goes to
+// the treasury as the reward remainder.
+pallet_staking_reward_curve::build! {
+	const REWARD_CURVE: PiecewiseLinear<'static> = curve!(
+		min_inflation: 0_025_000,
+		max_inflation: 0_100_000,
+		ideal_stake: 0_500_000,
+		falloff: 0_050_000,
+		max_piece_count: 40,
+		test_precision: 0_005_000,
+	);
+}
 
 parameter_types! {
 	pub const SessionsPerEra: sp_staking::SessionIndex = 4; // 1 day
@@ -267,12 +333,13 @@ parameter_types! {
 }
 
 impl pallet_staking::Trait for Runtime {
-	type Currency = Balances;
+	/// Records the rewards paid to stashes with a shielded reward destination.
+	type Currency = shielded_rewards::RecordPayouts<Self, Balances>;
 	type Time = Timestamp;
 	type CurrencyToVote = CurrencyToVoteHandler;
-	type RewardRemainder = ();
+	type RewardRemainder = Treasury;
 	type Event = Event;
-	type Slash = ();
+	type Slash = Treasury;
 	type Reward = (); // rewards are minted from the void
 	type SessionsPerEra = SessionsPerEra;
 	type BondingDuration = BondingDuration;
@@ -345,7 +412,7 @@ impl pallet_democracy::Trait for Runtime {
 	type PreimageByteDeposit = PreimageByteDeposit;
 
 	/// Handler for the unbalanced reduction when slashing a preimage deposit.
-	type Slash = ();
+	type Slash = Treasury;
 }
 
 type CouncilCollective = pallet_collective::Instance1;
@@ -391,6 +458,121 @@ impl pallet_elections_phragmen::Trait for Runtime {
 	type TermDuration = TermDuration;
 }
 
+parameter_types! {
+	pub const ProposalBond: Permill = Permill::from_percent(5);
+	pub const ProposalBondMinimum: Balance = 10 * RAD;
+	pub const SpendPeriod: BlockNumber = 1 * DAYS;
+	pub const Burn: Permill = Permill::from_percent(1);
+	pub const TipCountdown: BlockNumber = 1 * DAYS;
+	pub const TipFindersFee: Percent = Percent::from_percent(20);
+	pub const TipReportDepositBase: Balance = 1 * RAD;
+	pub const TipReportDepositPerByte: Balance = 1 * CENTI_RAD;
+}
+
+impl pallet_treasury::Trait for Runtime {
+	type Currency = Balances;
+	/// Three fifths of the council must approve a spending proposal.
+	type ApproveOrigin = pallet_collective::EnsureProportionAtLeast<_3, _5, AccountId, CouncilCollective>;
+	/// Half of the council can reject a spending proposal.
+	type RejectOrigin = pallet_collective::EnsureProportionMoreThan<_1, _2, AccountId, CouncilCollective>;
+	type Tippers = Elections;
+	type TipCountdown = TipCountdown;
+	type TipFindersFee = TipFindersFee;
+	type TipReportDepositBase = TipReportDepositBase;
+	type TipReportDepositPerByte = TipReportDepositPerByte;
+	type Event = Event;
+	/// The bonds of rejected proposals stay in the treasury.
+	type ProposalRejection = Treasury;
+	type ProposalBond = ProposalBond;
+	type ProposalBondMinimum = ProposalBondMinimum;
+	type SpendPeriod = SpendPeriod;
+	/// Share of the funds left after a spend period that is burnt.
+	type Burn = Burn;
+}
+
+parameter_types! {
+	pub const BasicDeposit: Balance = 10 * RAD;
+	pub const FieldDeposit: Balance = 25 * CENTI_RAD;
+	pub const SubAccountDeposit: Balance = 2 * RAD;
+	pub const MaxSubAccounts: u32 = 100;
+	pub const MaxAdditionalFields: u32 = 100;
+}
+
+impl pallet_identity::Trait for Runtime {
+	type Event = Event;
+	type Currency = Balances;
+	/// Deposits of identities removed by `kill_identity` go to the treasury.
+	type Slashed = Treasury;
+	type BasicDeposit = BasicDeposit;
+	type FieldDeposit = FieldDeposit;
+	type SubAccountDeposit = SubAccountDeposit;
+	type MaxSubAccounts = MaxSubAccounts;
+	type MaxAdditionalFields = MaxAdditionalFields;
+	/// Half of the council can appoint registrars.
+	type RegistrarOrigin = pallet_collective::EnsureProportionMoreThan<_1, _2, AccountId, CouncilCollective>;
+	/// Half of the council can set or remove an identity.
+	type ForceOrigin = pallet_collective::EnsureProportionMoreThan<_1, _2, AccountId, CouncilCollective>;
+}
+
+parameter_types! {
+	pub const MaxTasksPerBlock: u32 = 10;
+	// A tenth of a block.
+	pub const MaxTaskWeightPerBlock: Weight = 100_000_000;
+}
+
+impl scheduler::Trait for Runtime {
+	type Event = Event;
+	type Call = Call;
+	type TaskOrigin = TaskOrigin;
+	/// Root and half of the council can schedule calls, which run with their origin.
+	type ScheduleOrigin = EnsureRootOrHalfCouncil;
+	type MaxTasksPerBlock = MaxTasksPerBlock;
+	type MaxTaskWeightPerBlock = MaxTaskWeightPerBlock;
+}
+
+parameter_types! {
+	pub const ProxyDepositBase: Balance = 1 * RAD;
+	pub const ProxyDepositFactor: Balance = 10 * CENTI_RAD;
+	pub const MaxProxies: u32 = 32;
+	pub const AnnouncementDepositBase: Balance = 1 * RAD;
+	pub const AnnouncementDepositFactor: Balance = 10 * CENTI_RAD;
+	pub const MaxPending: u32 = 32;
+	pub const HighRiskDelay: BlockNumber = 1 * DAYS;
+}
+
+impl proxy::Trait for Runtime {
+	type Event = Event;
+	type Call = Call;
+	type Currency = Balances;
+	type ProxyType = ProxyType;
+	type ProxyDepositBase = ProxyDepositBase;
+	type ProxyDepositFactor = ProxyDepositFactor;
+	type MaxProxies = MaxProxies;
+	type AnnouncementDepositBase = AnnouncementDepositBase;
+	type AnnouncementDepositFactor = AnnouncementDepositFactor;
+	type MaxPending = MaxPending;
+	/// Proxies that can move funds announce their calls a day in advance.
+	type HighRiskDelay = HighRiskDelay;
+}
+
+parameter_types! {
+	pub const ConfigDepositBase: Balance = 5 * RAD;
+	pub const FriendDepositFactor: Balance = 50 * CENTI_RAD;
+	pub const MaxFriends: u16 = 9;
+	pub const RecoveryDeposit: Balance = 5 * RAD;
+}
+
+impl pallet_recovery::Trait for Runtime {
+	type Event = Event;
+	type Call = Call;
+	type Currency = Balances;
+	type ConfigDepositBase = ConfigDepositBase;
+	type FriendDepositFactor = FriendDepositFactor;
+	type MaxFriends = MaxFriends;
+	/// Reserved from the rescuer, and given to the lost account if it closes the attempt.
+	type RecoveryDeposit = RecoveryDeposit;
+}
+
 /// A runtime transaction submitter.
 pub type SubmitTransaction = TransactionSubmitter<ImOnlineId, Runtime, UncheckedExtrinsic>;
 
@@ -461,19 +643,80 @@ impl frame_system::offchain::CreateTransaction<Runtime, UncheckedExtrinsic> for
 			frame_system::CheckEra::<Runtime>::from(generic::Era::mortal(period, current_block)),
 			frame_system::CheckNonce::<Runtime>::from(index),
 			frame_system::CheckWeight::<Runtime>::new(),
-			pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(tip),
+			fees::ChargeTransactionPayment::<Runtime>::from(tip),
+			nfts::CheckShielded::<Runtime>::new(),
 		);
 		let raw_payload = SignedPayload::new(call, extra).map_err(|e| {
 			debug::warn!("Unable to create signed payload: {:?}", e);
 		}).ok()?;
 		let signature = TSigner::sign(public, &raw_payload)?;
+		let address = Indices::unlookup(account);
 		let (call, extra, _) = raw_payload.deconstruct();
-		Some((call, (account, signature, extra)))
+		Some((call, (address, signature, extra)))
 	}
 }
 
+parameter_types! {
+	// Anchors and outputs may be stored for at most a year.
+	pub const MaxStorageLifetime: BlockNumber = 365 * DAYS;
+	pub const MaxEvictionsPerBlock: u32 = 100;
+	// Share of a storage deposit that is kept as rent on eviction.
+	pub const StorageRentFraction: Perbill = Perbill::from_percent(10);
+	// Same as a staking era.
+	pub const UnshieldEraLength: BlockNumber = DAYS;
+	// Spends may be anchored to the tree roots of the last day.
+	pub const RootWindow: BlockNumber = DAYS;
+	// Disclosed notes follow ZIP 212, like Zcash notes since Canopy.
+	pub const NotePlaintextVersion: u8 = 2;
+}
+
 impl nfts::Trait for Runtime {
     type Event = Event;
+    type Currency = Balances;
+    /// Stored outputs are priced like democracy preimages.
+    type ByteDeposit = PreimageByteDeposit;
+    type MaxOutputLifetime = MaxStorageLifetime;
+    type MaxEvictionsPerBlock = MaxEvictionsPerBlock;
+    type RentFraction = StorageRentFraction;
+    type Slash = Treasury;
+    type FeeHandler = DealWithFees;
+    type UnshieldEraLength = UnshieldEraLength;
+    type RootWindow = RootWindow;
+    type NotePlaintextVersion = NotePlaintextVersion;
+    type PauseOrigin = pallet_collective::EnsureProportionAtLeast<_2, _3, AccountId, CouncilCollective>;
+}
+
+parameter_types! {
+	// Validators have an hour to prove the output of a reward.
+	pub const RewardProofTimeout: BlockNumber = HOURS;
+	pub const MaxShieldedPayees: u32 = 1_000;
+}
+
+impl shielded_rewards::Trait for Runtime {
+	type Event = Event;
+	type Call = Call;
+	type SubmitTransaction = SubmitTransaction;
+	type Staking = Staking;
+	type RewardProofTimeout = RewardProofTimeout;
+	type MaxShieldedPayees = MaxShieldedPayees;
+}
+
+parameter_types! {
+	pub const PreCommitDeposit: Balance = 10 * MILLI_RAD;
+	pub const PreCommitExpiration: BlockNumber = 80 * MINUTES;
+}
+
+impl anchor::Trait for Runtime {
+	type Event = Event;
+	type Currency = Balances;
+	type PreCommitDeposit = PreCommitDeposit;
+	/// Anchors are priced like democracy preimages.
+	type ByteDeposit = PreimageByteDeposit;
+	type PreCommitExpiration = PreCommitExpiration;
+	type MaxAnchorLifetime = MaxStorageLifetime;
+	type MaxEvictionsPerBlock = MaxEvictionsPerBlock;
+	type RentFraction = StorageRentFraction;
+	type Slash = Treasury;
 }
 
 parameter_types! {
@@ -518,13 +761,23 @@ construct_runtime!(
 		AuthorityDiscovery: pallet_authority_discovery::{Module, Call, Config},
 		Offences: pallet_offences::{Module, Call, Storage, Event},
         RandomnessCollectiveFlip: pallet_randomness_collective_flip::{Module, Call, Storage},
-		Nfts: nfts::{Module, Call, Event<T>},
+		Nfts: nfts::{Module, Call, Storage, Event<T>, Config<T>, ValidateUnsigned},
 		MultiAccount: substrate_pallet_multi_account::{Module, Call, Storage, Event<T>, Config<T>},
+		Anchor: anchor::{Module, Call, Storage, Event<T>},
+		ShieldedRewards: shielded_rewards::{Module, Call, Storage, Event<T>, ValidateUnsigned},
+		Treasury: pallet_treasury::{Module, Call, Storage, Config, Event<T>},
+		Fees: fees::{Module, Call, Storage, Event},
+		Vesting: vesting::{Module, Call, Storage, Event<T>, Config<T>},
+		Identity: pallet_identity::{Module, Call, Storage, Event<T>},
+		Scheduler: scheduler::{Module, Call, Storage, Event<T>},
+		Proxy: proxy::{Module, Call, Storage, Event<T>},
+		Recovery: pallet_recovery::{Module, Call, Storage, Event<T>},
+		Indices: pallet_indices::{Module, Call, Storage, Config<T>, Event<T>},
 	}
 );
 
-/// The address format for describing accounts.
-pub type Address = AccountId;
+/// The address format for describing accounts: an account ID or its index.
+pub type Address = <Indices as StaticLookup>::Source;
 /// Block header type as expected by this runtime.
 pub type Header = generic::Header<BlockNumber, BlakeTwo256>;
 /// Block type as expected by this runtime.
@@ -540,7 +793,8 @@ pub type SignedExtra = (
     frame_system::CheckEra<Runtime>,
     frame_system::CheckNonce<Runtime>,
     frame_system::CheckWeight<Runtime>,
-    pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+    fees::ChargeTransactionPayment<Runtime>,
+    nfts::CheckShielded<Runtime>,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
@@ -549,7 +803,49 @@ pub type SignedPayload = generic::SignedPayload<Call, SignedExtra>;
 /// Extrinsic type that has already been checked.
 pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Call, SignedExtra>;
 /// Executive: handles dispatch to the various modules.
-pub type Executive = frame_executive::Executive<Runtime, Block, frame_system::ChainContext<Runtime>, Runtime, AllModules>;
+pub type Executive = frame_executive::Executive<
+	Runtime,
+	Block,
+	frame_system::ChainContext<Runtime>,
+	Runtime,
+	(
+		migrations::MigrateRewardCurve,
+		migrations::MigrateShieldedState,
+		migrations::MigrateEmergencyAccount,
+		AllModules,
+	),
+>;
+
+decl_runtime_apis! {
+	/// The API to query anchoring info.
+	pub trait AnchorApi {
+		fn get_anchor_by_id(id: Hash) -> Option<AnchorData<Hash, BlockNumber>>;
+	}
+
+	/// The API to check shielded transactions outside of their dispatch.
+	pub trait NftsApi {
+		/// Whether the proofs and signatures of `tx` are valid.
+		fn verify_proofs(tx: nfts::ShieldedTransaction) -> bool;
+
+		/// Key of a spent `nullifier` of `pool` in the child trie of the shielded state.
+		fn nullifier_key(pool: nfts::ShieldedPool, nullifier: [u8; 32]) -> Vec<u8>;
+
+		/// Key of the tree frontier of `pool` in the child trie of the shielded state.
+		fn tree_key(pool: nfts::ShieldedPool) -> Vec<u8>;
+
+		/// The notes of the staking rewards waiting for their output to be proven.
+		fn pending_reward_notes() -> Vec<shielded_rewards::RewardNote>;
+	}
+
+	/// The API to query the identities of accounts.
+	pub trait IdentityApi {
+		/// The identity of `who` and the judgements of registrars on it.
+		fn identity_of(who: AccountId) -> Option<pallet_identity::Registration<Balance>>;
+
+		/// The identity registrars, indexed by their `RegistrarIndex`.
+		fn registrars() -> Vec<Option<pallet_identity::RegistrarInfo<Balance, AccountId>>>;
+	}
+}
 
 impl_runtime_apis! {
 	impl sp_api::Core<Block> for Runtime {
@@ -660,6 +956,40 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl crate::AnchorApi<Block> for Runtime {
+		fn get_anchor_by_id(id: Hash) -> Option<AnchorData<Hash, BlockNumber>> {
+			Anchor::get_anchor_by_id(id)
+		}
+	}
+
+	impl crate::NftsApi<Block> for Runtime {
+		fn verify_proofs(tx: nfts::ShieldedTransaction) -> bool {
+			Nfts::verify_proofs(&tx)
+		}
+
+		fn nullifier_key(pool: nfts::ShieldedPool, nullifier: [u8; 32]) -> Vec<u8> {
+			nfts::nullifier_key(pool, &nullifier)
+		}
+
+		fn tree_key(pool: nfts::ShieldedPool) -> Vec<u8> {
+			nfts::tree_key(pool)
+		}
+
+		fn pending_reward_notes() -> Vec<shielded_rewards::RewardNote> {
+			ShieldedRewards::pending_notes()
+		}
+	}
+
+	impl crate::IdentityApi<Block> for Runtime {
+		fn identity_of(who: AccountId) -> Option<pallet_identity::Registration<Balance>> {
+			Identity::identity(who)
+		}
+
+		fn registrars() -> Vec<Option<pallet_identity::RegistrarInfo<Balance, AccountId>>> {
+			Identity::registrars()
+		}
+	}
+
 	impl sp_session::SessionKeys<Block> for Runtime {
 		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
 			SessionKeys::generate(seed)
@@ -701,6 +1031,32 @@ mod tests {
 		is_sign_and_submit_transaction::<SubmitTransaction>();
 	}
 
+	#[test]
+	fn era_payouts_follow_the_reward_curve() {
+		use sp_runtime::assert_eq_error_rate;
+
+		const MILLISECONDS_PER_YEAR: u64 = 1000 * 3600 * 24 * 36525 / 100;
+		let era_duration = SessionsPerEra::get() as u64 * EPOCH_DURATION_IN_SLOTS * MILLISECS_PER_BLOCK;
+		let total: Balance = 1_000_000_000 * RAD;
+		// payout of an era over a year, in per mill of the total issuance.
+		let yearly_payout = |staked_percent: u32| {
+			let staked = Perbill::from_percent(staked_percent) * total;
+			let era_payout = Perbill::from_rational_approximation(era_duration, MILLISECONDS_PER_YEAR) *
+				REWARD_CURVE.calculate_for_fraction_times_denominator(staked, total);
+			(era_payout * (MILLISECONDS_PER_YEAR / era_duration) as Balance * 1000 / total) as u64
+		};
+
+		// one day eras.
+		assert_eq!(era_duration, 24 * 3600 * 1000);
+		assert_eq_error_rate!(yearly_payout(0), 25, 1);
+		assert_eq_error_rate!(yearly_payout(25), 62, 1);
+		assert_eq_error_rate!(yearly_payout(50), 100, 1);
+		assert_eq_error_rate!(yearly_payout(60), 43, 1);
+		assert_eq_error_rate!(yearly_payout(75), 27, 1);
+		assert_eq_error_rate!(yearly_payout(100), 25, 1);
+		assert_eq!(REWARD_CURVE.maximum, Perbill::from_percent(10));
+	}
+
 	#[test]
 	fn block_hooks_weight_should_not_exceed_limits() {
 		use frame_support::weights::WeighBlock;
@@ -711,7 +1067,10 @@ mod tests {
 
 			assert_eq!(
 				block_hooks_weight,
-				0,
+				(anchor::EVICTION_WEIGHT + nfts::EVICTION_WEIGHT) * MaxEvictionsPerBlock::get() as Weight +
+					nfts::ROOT_PRUNING_WEIGHT +
+					shielded_rewards::MINT_WEIGHT * shielded_rewards::MAX_MINTS_PER_BLOCK as Weight +
+					MaxTaskWeightPerBlock::get(),
 				"This test might fail simply because the value being compared to has increased to a \
 				module declaring a new weight for a hook or call. In this case update the test and \
 				happily move on.",
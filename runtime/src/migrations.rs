@@ -0,0 +1,239 @@
+//! Runtime migrations that don't belong to a single module. They run at the start of the first
+//! block of the runtime that introduces them, before any module.
+
+use codec::Decode;
+use frame_support::{storage::{child, unhashed}, weights::WeighBlock};
+use sp_io::hashing::twox_128;
+use sp_runtime::traits::{OffchainWorker, OnFinalize, OnInitialize};
+use sp_std::prelude::*;
+use node_primitives::BlockNumber;
+use crate::commitment_tree::Frontier;
+use crate::nfts::{self, ShieldedPool};
+use crate::{MultiAccount, Nfts, Staking};
+
+/// Key of the flag set once `MigrateRewardCurve` has run.
+const REWARD_CURVE_MIGRATED: &[u8] = b":migrations:reward_curve";
+/// Key of the flag set once `MigrateShieldedState` has run.
+const SHIELDED_STATE_MIGRATED: &[u8] = b":migrations:shielded_state";
+/// Key of the flag set once `MigrateEmergencyAccount` has run.
+const EMERGENCY_ACCOUNT_MIGRATED: &[u8] = b":migrations:emergency_account";
+
+/// Move staking from the flat inflation workaround to the reward curve.
+///
+/// Era payouts are computed from the curve when the era ends, so the era running at the upgrade
+/// would be paid entirely with the new curve. It is ended at the next session instead, so the
+/// curve only pays for eras that started under it.
+pub struct MigrateRewardCurve;
+
+impl OnInitialize<BlockNumber> for MigrateRewardCurve {
+	fn on_initialize(_n: BlockNumber) {
+		if unhashed::exists(REWARD_CURVE_MIGRATED) {
+			return;
+		}
+		unhashed::put(REWARD_CURVE_MIGRATED, &true);
+
+		// a new chain starts under the curve.
+		if Staking::current_era() > 0 {
+			let _ = Staking::force_new_era(frame_system::RawOrigin::Root.into());
+		}
+	}
+}
+
+impl OnFinalize<BlockNumber> for MigrateRewardCurve {}
+
+impl OffchainWorker<BlockNumber> for MigrateRewardCurve {}
+
+impl WeighBlock<BlockNumber> for MigrateRewardCurve {}
+
+/// Move the nullifier sets and the tree frontiers of `Nfts` from the main trie, where they were
+/// kept as the `Nullifiers` and `Trees` storage items, to the child trie of the shielded state.
+///
+/// A spend of a nullifier left in the main trie would be accepted again, so everything is moved
+/// at once.
+pub struct MigrateShieldedState;
+
+/// Prefix of the keys of the `Nfts` storage item `name` in the main trie.
+fn nfts_prefix(name: &[u8]) -> Vec<u8> {
+	let mut prefix = twox_128(b"Nfts").to_vec();
+	prefix.extend_from_slice(&twox_128(name));
+	prefix
+}
+
+/// Keys of the main trie starting with `prefix`.
+fn keys_with_prefix(prefix: &[u8]) -> Vec<Vec<u8>> {
+	let mut keys = Vec::new();
+	let mut key = prefix.to_vec();
+	while let Some(next) = sp_io::storage::next_key(&key) {
+		if !next.starts_with(prefix) {
+			break;
+		}
+		keys.push(next.clone());
+		key = next;
+	}
+	keys
+}
+
+impl OnInitialize<BlockNumber> for MigrateShieldedState {
+	fn on_initialize(_n: BlockNumber) {
+		if unhashed::exists(SHIELDED_STATE_MIGRATED) {
+			return;
+		}
+		unhashed::put(SHIELDED_STATE_MIGRATED, &true);
+
+		// `Nullifiers` keys end with `twox_64_concat(pool) ++ blake2_128_concat(nullifier)`.
+		let prefix = nfts_prefix(b"Nullifiers");
+		for key in keys_with_prefix(&prefix) {
+			let suffix = &key[prefix.len()..];
+			if suffix.len() == 8 + 1 + 16 + 32 {
+				let mut nullifier = [0u8; 32];
+				nullifier.copy_from_slice(&suffix[8 + 1 + 16..]);
+				if let Ok(pool) = ShieldedPool::decode(&mut &suffix[8..9]) {
+					let key = nfts::nullifier_key(pool, &nullifier);
+					child::put(nfts::CHILD_STORAGE_KEY, nfts::child_info(), &key, &true);
+				}
+			}
+			unhashed::kill(&key);
+		}
+
+		// `Trees` keys end with `twox_64_concat(pool)`.
+		let prefix = nfts_prefix(b"Trees");
+		for key in keys_with_prefix(&prefix) {
+			let pool = key.get(prefix.len() + 8..).map(|mut encoded| ShieldedPool::decode(&mut encoded));
+			if let (Some(Ok(pool)), Some(tree)) = (pool, unhashed::get::<Frontier>(&key)) {
+				child::put(nfts::CHILD_STORAGE_KEY, nfts::child_info(), &nfts::tree_key(pool), &tree);
+			}
+			unhashed::kill(&key);
+		}
+	}
+}
+
+impl OnFinalize<BlockNumber> for MigrateShieldedState {}
+
+impl OffchainWorker<BlockNumber> for MigrateShieldedState {}
+
+impl WeighBlock<BlockNumber> for MigrateShieldedState {}
+
+/// Store the emergency account of `Nfts`, which used to be the second multi account created in the
+/// genesis, on chains whose genesis did not set it.
+pub struct MigrateEmergencyAccount;
+
+impl OnInitialize<BlockNumber> for MigrateEmergencyAccount {
+	fn on_initialize(_n: BlockNumber) {
+		if unhashed::exists(EMERGENCY_ACCOUNT_MIGRATED) {
+			return;
+		}
+		unhashed::put(EMERGENCY_ACCOUNT_MIGRATED, &true);
+
+		if Nfts::emergency_account() == Default::default() {
+			let _ = Nfts::set_emergency_account(
+				frame_system::RawOrigin::Root.into(),
+				MultiAccount::multi_account_id(2),
+			);
+		}
+	}
+}
+
+impl OnFinalize<BlockNumber> for MigrateEmergencyAccount {}
+
+impl OffchainWorker<BlockNumber> for MigrateEmergencyAccount {}
+
+impl WeighBlock<BlockNumber> for MigrateEmergencyAccount {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use pallet_staking::Forcing;
+	use crate::Runtime;
+
+	#[test]
+	fn migration_forces_a_new_era_once() {
+		let mut t: sp_io::TestExternalities =
+			frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap().into();
+		t.execute_with(|| {
+			MigrateRewardCurve::on_initialize(1);
+			assert_eq!(Staking::force_era(), Forcing::NotForcing);
+			assert!(unhashed::exists(REWARD_CURVE_MIGRATED));
+		});
+
+		let mut t = pallet_staking::GenesisConfig::<Runtime> {
+			current_era: 3,
+			.. Default::default()
+		}.build_storage().unwrap();
+		frame_system::GenesisConfig::default().assimilate_storage::<Runtime>(&mut t).unwrap();
+		sp_io::TestExternalities::from(t).execute_with(|| {
+			MigrateRewardCurve::on_initialize(1);
+			assert_eq!(Staking::force_era(), Forcing::ForceNew);
+
+			let _ = Staking::force_no_eras(frame_system::RawOrigin::Root.into());
+			MigrateRewardCurve::on_initialize(2);
+			assert_eq!(Staking::force_era(), Forcing::ForceNone);
+		});
+	}
+
+	fn twox_64_concat(data: &[u8]) -> Vec<u8> {
+		let mut key = sp_io::hashing::twox_64(data).to_vec();
+		key.extend_from_slice(data);
+		key
+	}
+
+	fn blake2_128_concat(data: &[u8]) -> Vec<u8> {
+		let mut key = sp_io::hashing::blake2_128(data).to_vec();
+		key.extend_from_slice(data);
+		key
+	}
+
+	#[test]
+	fn migration_keeps_the_emergency_account() {
+		let mut t: sp_io::TestExternalities =
+			frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap().into();
+		t.execute_with(|| {
+			MigrateEmergencyAccount::on_initialize(1);
+			assert_eq!(Nfts::emergency_account(), MultiAccount::multi_account_id(2));
+			assert!(unhashed::exists(EMERGENCY_ACCOUNT_MIGRATED));
+		});
+
+		let mut t = nfts::GenesisConfig::<Runtime> {
+			emergency_account: MultiAccount::multi_account_id(3),
+		}.build_storage().unwrap();
+		frame_system::GenesisConfig::default().assimilate_storage::<Runtime>(&mut t).unwrap();
+		sp_io::TestExternalities::from(t).execute_with(|| {
+			MigrateEmergencyAccount::on_initialize(1);
+			assert_eq!(Nfts::emergency_account(), MultiAccount::multi_account_id(3));
+		});
+	}
+
+	#[test]
+	fn migration_moves_shielded_state_to_child_trie() {
+		use codec::Encode;
+		use crate::commitment_tree::SaplingHasher;
+
+		let mut t: sp_io::TestExternalities =
+			frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap().into();
+		t.execute_with(|| {
+			let mut tree = Frontier::default();
+			tree.append::<SaplingHasher>([1u8; 32]).unwrap();
+
+			let mut nullifier_key = nfts_prefix(b"Nullifiers");
+			nullifier_key.extend(twox_64_concat(&ShieldedPool::Orchard.encode()));
+			nullifier_key.extend(blake2_128_concat(&[2u8; 32]));
+			unhashed::put(&nullifier_key, &true);
+			let mut tree_key = nfts_prefix(b"Trees");
+			tree_key.extend(twox_64_concat(&ShieldedPool::Sapling.encode()));
+			unhashed::put(&tree_key, &tree);
+
+			MigrateShieldedState::on_initialize(1);
+			assert!(Nfts::is_nullifier_spent(ShieldedPool::Orchard, [2u8; 32]));
+			assert!(!Nfts::is_nullifier_spent(ShieldedPool::Sapling, [2u8; 32]));
+			assert_eq!(Nfts::tree(ShieldedPool::Sapling), tree);
+			assert_eq!(Nfts::tree(ShieldedPool::Orchard), Frontier::default());
+			assert!(!unhashed::exists(&nullifier_key));
+			assert!(!unhashed::exists(&tree_key));
+			assert!(unhashed::exists(SHIELDED_STATE_MIGRATED));
+
+			// the migration runs once.
+			unhashed::put(&nullifier_key, &true);
+			MigrateShieldedState::on_initialize(2);
+			assert!(unhashed::exists(&nullifier_key));
+		});
+	}
+}
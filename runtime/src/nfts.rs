@@ -1,10 +1,75 @@
 use frame_support::{
-    decl_event, decl_module, dispatch::{DispatchResult, DispatchError}, weights::SimpleDispatchInfo,
+    decl_error, decl_event, decl_module, decl_storage, ensure,
+    dispatch::{DispatchResult, IsSubType},
+    traits::{
+        Currency, EnsureOrigin, ExistenceRequirement, Get, OnUnbalanced, ReservableCurrency,
+        WithdrawReason,
+    },
+    weights::{DispatchClass, DispatchInfo, FunctionOf, GetDispatchInfo, SimpleDispatchInfo, Weight},
 };
-use frame_system::{self as system, ensure_signed};
-use sp_std::vec::Vec;
-use sapling::{self, SaplingOutputDescription, zcash, accept_spend, accept_output, Point};
-use codec::{self, Input};
+use frame_system::{self as system, ensure_none, ensure_root, ensure_signed};
+use frame_support::storage::child;
+use sp_core::storage::ChildInfo;
+use sp_runtime::{
+    KeyTypeId, Perbill,
+    traits::{BadOrigin, One, SaturatedConversion, Saturating, SignedExtension, Zero},
+    transaction_validity::{
+        InvalidTransaction, TransactionValidity, TransactionValidityError, ValidTransaction,
+    },
+};
+use sp_std::{cmp::Ordering, collections::btree_set::BTreeSet, convert::TryFrom, marker::PhantomData, vec::Vec};
+use sapling::{self, zcash, accept_spend, accept_output, final_check, Point};
+use codec::{self, Decode, Encode, Input};
+use crate::commitment_tree::{self, Frontier, OrchardHasher, SaplingHasher, TreeError};
+use crate::host_functions::{shielded_crypto, OrchardError};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+    <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::NegativeImbalance;
+
+/// Weight of evicting a single expired output.
+pub const EVICTION_WEIGHT: Weight = 100_000;
+
+/// Number of blocks from its expiry searched for room to queue the eviction of an output.
+pub const MAX_EVICTION_SEARCH: u32 = 16;
+
+/// Weight of pruning the tree roots recorded at a block.
+pub const ROOT_PRUNING_WEIGHT: Weight = 50_000;
+
+/// Length of the encrypted note plaintext of an output.
+const ENC_CIPHER_TEXT_LEN: usize = 580;
+/// Length of the encrypted outgoing plaintext of an output.
+const OUT_CIPHER_TEXT_LEN: usize = 80;
+/// Length of a Groth16 proof.
+const ZKPROOF_LEN: usize = 192;
+/// Length of a raw Sapling payment address.
+const PAYMENT_ADDRESS_LEN: usize = 43;
+
+/// Key type of Sapling spending keys in the keystore. A key is stored under the sr25519 public key
+/// of its secret URI, from which the ZIP-32 seed is derived.
+pub const SAPLING_KEY_TYPE: KeyTypeId = KeyTypeId(*b"sapl");
+
+/// Custom `InvalidTransaction` code of calls rejected because their pool operation is paused.
+pub const PAUSED_TRANSACTION: u8 = 1;
+/// Custom `InvalidTransaction` code of shielded transactions spending a nullifier twice.
+pub const DUPLICATE_NULLIFIER: u8 = 2;
+/// Custom `InvalidTransaction` code of shielded transactions sent by another account than the one
+/// they commit to.
+pub const ACCOUNT_MISMATCH: u8 = 3;
+
+/// Weight of verifying a Sapling spend.
+const SAPLING_SPEND_WEIGHT: Weight = 1_500_000;
+/// Weight of verifying a Sapling output.
+const SAPLING_OUTPUT_WEIGHT: Weight = 1_500_000;
+/// Weight of verifying an Orchard bundle, excluding its actions, with the verifying key of the
+/// circuit cached by the host.
+const ORCHARD_BUNDLE_WEIGHT: Weight = 8_000_000;
+/// Weight of verifying an Orchard action.
+const ORCHARD_ACTION_WEIGHT: Weight = 3_000_000;
+/// Weight of checking the path of a Sapling note commitment to a root of the tree.
+const SAPLING_PATH_WEIGHT: Weight = 1_500_000;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SaplingSpendDescription {
@@ -64,20 +129,504 @@ impl codec::Encode for SaplingSpendDescription {
     }
 }
 
+impl SaplingSpendDescription {
+    /// Append the fields covered by a sighash to `data`, leaving out the proof and signature.
+    fn sighash_data(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(&self.inner.value_commitment);
+        data.extend_from_slice(&self.inner.anchor);
+        data.extend_from_slice(&self.inner.nullifier);
+        data.extend_from_slice(&self.inner.randomized_key);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaplingOutputDescription {
+    pub(crate) inner: sapling::SaplingOutputDescription,
+}
+
+impl From<sapling::SaplingOutputDescription> for SaplingOutputDescription {
+    fn from(output: sapling::SaplingOutputDescription) -> Self {
+        Self {
+            inner: output,
+        }
+    }
+}
+
+impl SaplingOutputDescription {
+    pub fn note_commitment(&self) -> [u8; 32] {
+        self.inner.note_commitment
+    }
+}
+
+impl codec::Decode for SaplingOutputDescription {
+    fn decode<I: codec::Input>(value: &mut I) -> Result<Self, codec::Error> {
+        let mut value_commitment = [0u8; 32];
+        let mut note_commitment = [0u8; 32];
+        let mut ephemeral_key = [0u8; 32];
+        let mut enc_cipher_text = [0u8; ENC_CIPHER_TEXT_LEN];
+        let mut out_cipher_text = [0u8; OUT_CIPHER_TEXT_LEN];
+        let mut zkproof = [0u8; ZKPROOF_LEN];
+
+        value.read(&mut value_commitment)?;
+        value.read(&mut note_commitment)?;
+        value.read(&mut ephemeral_key)?;
+        value.read(&mut enc_cipher_text)?;
+        value.read(&mut out_cipher_text)?;
+        value.read(&mut zkproof)?;
+
+        let inner = sapling::SaplingOutputDescription {
+            value_commitment,
+            note_commitment,
+            ephemeral_key,
+            enc_cipher_text,
+            out_cipher_text,
+            zkproof,
+        };
+
+        Ok(SaplingOutputDescription {
+            inner
+        })
+    }
+}
+
+impl codec::Encode for SaplingOutputDescription {
+    fn using_encoded<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        let inner = &self.inner;
+        let mut output = [0u8; 3 * 32 + ENC_CIPHER_TEXT_LEN + OUT_CIPHER_TEXT_LEN + ZKPROOF_LEN];
+        output[..32].copy_from_slice(&inner.value_commitment);
+        output[32..64].copy_from_slice(&inner.note_commitment);
+        output[64..96].copy_from_slice(&inner.ephemeral_key);
+        output[96..676].copy_from_slice(&inner.enc_cipher_text);
+        output[676..756].copy_from_slice(&inner.out_cipher_text);
+        output[756..].copy_from_slice(&inner.zkproof);
+        f(&output)
+    }
+}
+
+/// An Orchard action: a spend and an output of the Orchard pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrchardAction {
+    pub value_commitment: [u8; 32],
+    pub nullifier: [u8; 32],
+    pub randomized_key: [u8; 32],
+    /// Extracted note commitment of the output.
+    pub note_commitment: [u8; 32],
+    pub ephemeral_key: [u8; 32],
+    pub enc_cipher_text: [u8; ENC_CIPHER_TEXT_LEN],
+    pub out_cipher_text: [u8; OUT_CIPHER_TEXT_LEN],
+    pub spend_auth_sig: [u8; 64],
+}
+
+impl codec::Decode for OrchardAction {
+    fn decode<I: codec::Input>(value: &mut I) -> Result<Self, codec::Error> {
+        let mut action = OrchardAction {
+            value_commitment: [0u8; 32],
+            nullifier: [0u8; 32],
+            randomized_key: [0u8; 32],
+            note_commitment: [0u8; 32],
+            ephemeral_key: [0u8; 32],
+            enc_cipher_text: [0u8; ENC_CIPHER_TEXT_LEN],
+            out_cipher_text: [0u8; OUT_CIPHER_TEXT_LEN],
+            spend_auth_sig: [0u8; 64],
+        };
+
+        value.read(&mut action.value_commitment)?;
+        value.read(&mut action.nullifier)?;
+        value.read(&mut action.randomized_key)?;
+        value.read(&mut action.note_commitment)?;
+        value.read(&mut action.ephemeral_key)?;
+        value.read(&mut action.enc_cipher_text)?;
+        value.read(&mut action.out_cipher_text)?;
+        value.read(&mut action.spend_auth_sig)?;
+
+        Ok(action)
+    }
+}
+
+impl codec::Encode for OrchardAction {
+    fn using_encoded<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        let mut output = [0u8; 5 * 32 + ENC_CIPHER_TEXT_LEN + OUT_CIPHER_TEXT_LEN + 64];
+        output[..32].copy_from_slice(&self.value_commitment);
+        output[32..64].copy_from_slice(&self.nullifier);
+        output[64..96].copy_from_slice(&self.randomized_key);
+        output[96..128].copy_from_slice(&self.note_commitment);
+        output[128..160].copy_from_slice(&self.ephemeral_key);
+        output[160..740].copy_from_slice(&self.enc_cipher_text);
+        output[740..820].copy_from_slice(&self.out_cipher_text);
+        output[820..].copy_from_slice(&self.spend_auth_sig);
+        f(&output)
+    }
+}
+
+/// The Orchard part of a shielded transaction.
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct OrchardBundle {
+    pub actions: Vec<OrchardAction>,
+    /// Whether spends and outputs are enabled, see `orchard::bundle::Flags`.
+    pub flags: u8,
+    /// Net value leaving the Orchard pool.
+    pub value_balance: i64,
+    /// Root of the Orchard tree all actions spend from.
+    pub anchor: [u8; 32],
+    /// Halo 2 proof for all actions.
+    pub zkproof: Vec<u8>,
+    pub binding_sig: [u8; 64],
+}
+
+/// A transaction moving value within and between the Sapling and Orchard pools.
+///
+/// Each pool part is bound to its value balance by its own binding signature. The combined value
+/// balance is paid to the sender if positive and taken from the sender if negative. The sender is
+/// committed to by the sighash, so nobody else can take over a pending unshield.
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct ShieldedTransaction {
+    pub sapling_spends: Vec<SaplingSpendDescription>,
+    pub sapling_outputs: Vec<SaplingOutputDescription>,
+    /// Net value leaving the Sapling pool.
+    pub sapling_value_balance: i64,
+    pub sapling_binding_sig: [u8; 64],
+    pub orchard: Option<OrchardBundle>,
+    /// Encoded account sending the transaction, `None` for an unsigned transaction.
+    pub transparent_account: Option<Vec<u8>>,
+}
+
+impl ShieldedTransaction {
+    /// Combined value balance of the Sapling and Orchard parts.
+    pub fn value_balance(&self) -> Option<i64> {
+        let orchard = self.orchard.as_ref().map_or(0, |bundle| bundle.value_balance);
+        self.sapling_value_balance.checked_add(orchard)
+    }
+
+    /// Hash signed by the spend authorization and binding signatures. It commits to everything
+    /// but the proofs and signatures, the transparent account included.
+    pub fn sighash(&self) -> [u8; 32] {
+        let mut data = b"nfts/sighash".to_vec();
+
+        (self.sapling_spends.len() as u32).encode_to(&mut data);
+        for spend in &self.sapling_spends {
+            spend.sighash_data(&mut data);
+        }
+
+        (self.sapling_outputs.len() as u32).encode_to(&mut data);
+        for output in &self.sapling_outputs {
+            let output = &output.inner;
+            data.extend_from_slice(&output.value_commitment);
+            data.extend_from_slice(&output.note_commitment);
+            data.extend_from_slice(&output.ephemeral_key);
+            data.extend_from_slice(&output.enc_cipher_text);
+            data.extend_from_slice(&output.out_cipher_text);
+        }
+        self.sapling_value_balance.encode_to(&mut data);
+
+        if let Some(bundle) = &self.orchard {
+            (bundle.actions.len() as u32).encode_to(&mut data);
+            for action in &bundle.actions {
+                data.extend_from_slice(&action.value_commitment);
+                data.extend_from_slice(&action.nullifier);
+                data.extend_from_slice(&action.randomized_key);
+                data.extend_from_slice(&action.note_commitment);
+                data.extend_from_slice(&action.ephemeral_key);
+                data.extend_from_slice(&action.enc_cipher_text);
+                data.extend_from_slice(&action.out_cipher_text);
+            }
+            data.push(bundle.flags);
+            bundle.value_balance.encode_to(&mut data);
+            data.extend_from_slice(&bundle.anchor);
+        }
+        self.transparent_account.encode_to(&mut data);
+
+        sp_io::hashing::blake2_256(&data)
+    }
+
+    /// Weight of verifying the transaction.
+    pub fn weight(&self) -> Weight {
+        let spends = SAPLING_SPEND_WEIGHT.saturating_mul(self.sapling_spends.len() as Weight);
+        let outputs = SAPLING_OUTPUT_WEIGHT.saturating_mul(self.sapling_outputs.len() as Weight);
+        let orchard = self.orchard.as_ref().map_or(0, |bundle| {
+            ORCHARD_ACTION_WEIGHT
+                .saturating_mul(bundle.actions.len() as Weight)
+                .saturating_add(ORCHARD_BUNDLE_WEIGHT)
+        });
+        spends.saturating_add(outputs).saturating_add(orchard)
+    }
+}
+
+/// A shielded pool. Every pool has its own note commitment tree and nullifier set.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum ShieldedPool {
+    Sapling,
+    Orchard,
+}
+
+impl ShieldedPool {
+    /// Root of `tree`, hashed the way the tree of this pool is.
+    pub fn root_of(self, tree: &Frontier) -> [u8; 32] {
+        match self {
+            ShieldedPool::Sapling => tree.root::<SaplingHasher>(),
+            ShieldedPool::Orchard => tree.root::<OrchardHasher>(),
+        }
+    }
+}
+
+/// Unique id of the child trie holding the nullifier sets and the tree frontiers.
+///
+/// Both grow with every transaction. Keeping them apart from the main trie keeps its proofs
+/// small, and lets light clients prove the shielded state with proofs of the child trie alone.
+pub const CHILD_TRIE_ID: &[u8] = b"nfts";
+/// Storage key of the child trie of the shielded state.
+pub const CHILD_STORAGE_KEY: &[u8] = b":child_storage:default:nfts";
+
+/// Child info of the child trie of the shielded state.
+pub fn child_info() -> ChildInfo<'static> {
+    ChildInfo::new_default(CHILD_TRIE_ID)
+}
+
+/// Key of a spent `nullifier` of `pool` in the child trie.
+pub fn nullifier_key(pool: ShieldedPool, nullifier: &[u8; 32]) -> Vec<u8> {
+    (b"nullifier", pool, nullifier).encode()
+}
+
+/// Key of the tree frontier of `pool` in the child trie.
+pub fn tree_key(pool: ShieldedPool) -> Vec<u8> {
+    (b"tree", pool).encode()
+}
+
+/// A kind of pool operation that can be paused on its own.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum PauseKind {
+    /// Transactions moving value into the shielded pools.
+    Shield,
+    /// Transactions moving value within the shielded pools, and stand-alone verification of
+    /// spends and outputs.
+    Transfer,
+    /// Transactions moving value out of the shielded pools.
+    Unshield,
+}
+
+impl PauseKind {
+    /// Kind of a transaction with the combined `value_balance`.
+    pub fn of(value_balance: i64) -> Self {
+        match value_balance.cmp(&0) {
+            Ordering::Less => PauseKind::Shield,
+            Ordering::Equal => PauseKind::Transfer,
+            Ordering::Greater => PauseKind::Unshield,
+        }
+    }
+}
+
+/// Encrypted note data of a verified output, kept so that wallets can scan for their notes.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct OutputRecord<AccountId, Balance, BlockNumber> {
+    pub value_commitment: [u8; 32],
+    pub ephemeral_key: [u8; 32],
+    pub enc_cipher_text: Vec<u8>,
+    pub out_cipher_text: Vec<u8>,
+    /// Account that stored the output and pays its deposit.
+    pub owner: AccountId,
+    pub deposit: Balance,
+    /// Block after which the record may be removed from storage.
+    pub expires_at: BlockNumber,
+}
+
+/// A payment disclosed to an auditor and verified against a stored output.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Attestation<AccountId, Balance, BlockNumber> {
+    /// Account that disclosed the payment and pays the deposit.
+    pub discloser: AccountId,
+    /// Raw Sapling payment address the note was sent to.
+    pub recipient: Vec<u8>,
+    /// Value of the note.
+    pub value: u64,
+    pub deposit: Balance,
+    /// Block at which the disclosure was verified.
+    pub disclosed_at: BlockNumber,
+}
+
 pub trait Trait: frame_system::Trait {
     type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+
+    /// Currency used for output deposits.
+    type Currency: ReservableCurrency<Self::AccountId>;
+
+    /// Deposit reserved per byte of a stored output.
+    type ByteDeposit: Get<BalanceOf<Self>>;
+
+    /// Maximum number of blocks an output may be stored for.
+    type MaxOutputLifetime: Get<Self::BlockNumber>;
+
+    /// Maximum number of expired outputs evicted in a single block. This is also the number of
+    /// outputs that may expire at the same block.
+    type MaxEvictionsPerBlock: Get<u32>;
+
+    /// Share of an output deposit that is kept as storage rent when the output is evicted.
+    type RentFraction: Get<Perbill>;
+
+    /// Handler for the storage rent taken from output deposits.
+    type Slash: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+    /// Handler for the fees of unsigned shielded transactions, paid from their value balance.
+    type FeeHandler: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+    /// Number of blocks in an era of the unshielding limit.
+    type UnshieldEraLength: Get<Self::BlockNumber>;
+
+    /// Number of blocks the root of a tree remains a valid anchor after it has been replaced.
+    type RootWindow: Get<Self::BlockNumber>;
+
+    /// Version of the note plaintexts of disclosed outputs: 1 before ZIP 212 and 2 after.
+    type NotePlaintextVersion: Get<u8>;
+
+    /// Origin allowed to pause and resume pool operations, and to set the emergency account.
+    type PauseOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Nfts {
+        /// Verified outputs by note commitment.
+        Outputs get(fn output):
+            map hasher(blake2_256) [u8; 32] => Option<OutputRecord<T::AccountId, BalanceOf<T>, T::BlockNumber>>;
+
+        /// Note commitments of the outputs expiring at a block.
+        Expiries get(fn expiries): map hasher(twox_64_concat) T::BlockNumber => Vec<[u8; 32]>;
+
+        /// First block whose expiries have not been fully evicted yet.
+        EvictionCursor get(fn eviction_cursor): T::BlockNumber;
+
+        /// Roots the tree of a pool had at the start of the blocks of the last `RootWindow`
+        /// blocks. Spends must use one of them, or the current root, as anchor.
+        Roots get(fn is_root):
+            double_map hasher(twox_64_concat) ShieldedPool, hasher(blake2_128_concat) [u8; 32] => bool;
+
+        /// Roots recorded at a block, removed from `Roots` once the block leaves the window.
+        RecordedRoots get(fn recorded_roots):
+            map hasher(twox_64_concat) T::BlockNumber => Vec<(ShieldedPool, [u8; 32])>;
+
+        /// Value held by each pool. A pool never releases more value than it holds.
+        PoolBalances get(fn pool_balance): map hasher(twox_64_concat) ShieldedPool => u64;
+
+        /// Net value unshielded in a block, along with that block.
+        BlockUnshielded: (T::BlockNumber, i64);
+
+        /// Net value unshielded in an era, along with the index of that era.
+        EraUnshielded: (T::BlockNumber, i64);
+
+        /// Maximum net value unshielded in a block, unlimited if `None`.
+        BlockUnshieldLimit get(fn block_unshield_limit): Option<u64>;
+
+        /// Maximum net value unshielded in an era, unlimited if `None`.
+        EraUnshieldLimit get(fn era_unshield_limit): Option<u64>;
+
+        /// Pool operations that are paused.
+        Paused get(fn is_paused): map hasher(twox_64_concat) PauseKind => bool;
+
+        /// Account allowed to pause and resume pool operations in an emergency, besides
+        /// `PauseOrigin`.
+        EmergencyAccount get(fn emergency_account) config(): T::AccountId;
+
+        /// Verified payment disclosures by auditor and note commitment.
+        Attestations get(fn attestation):
+            double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) [u8; 32]
+            => Option<Attestation<T::AccountId, BalanceOf<T>, T::BlockNumber>>;
+    }
 }
 
 decl_event!(
-    pub enum Event<T> where <T as frame_system::Trait>::Hash {
+    pub enum Event<T> where
+        <T as frame_system::Trait>::Hash,
+        <T as frame_system::Trait>::AccountId,
+    {
         DepositAsset(Hash),
+        /// A verified output has been stored by the account.
+        OutputStored(AccountId, [u8; 32]),
+        /// An expired output has been removed from storage.
+        OutputEvicted([u8; 32]),
+        /// A shielded transaction with the given sighash has been applied.
+        Transacted([u8; 32]),
+        /// A payment of the given value by the output has been disclosed to the auditor:
+        /// (discloser, auditor, note commitment, value).
+        PaymentDisclosed(AccountId, AccountId, [u8; 32], u64),
+        /// The auditor has cleared the attestation of the output.
+        AttestationCleared(AccountId, [u8; 32]),
+        /// A verified transaction would have pushed the balance of the pool below zero.
+        /// Unshielding has been paused.
+        TurnstileViolated(ShieldedPool),
+        /// The unshielding limits per block and per era have been set.
+        UnshieldLimitsSet(Option<u64>, Option<u64>),
+        /// A pool operation has been paused (`true`) or resumed (`false`).
+        PauseSet(PauseKind, bool),
+        /// The emergency account has been set.
+        EmergencyAccountSet(AccountId),
     }
 );
 
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// The zero-knowledge proof could not be verified.
+        VerificationFailed,
+        /// A cipher text or proof has the wrong length.
+        InvalidLength,
+        /// An output with this note commitment is already stored.
+        OutputExists,
+        /// The expiry of the output is not in the future or too far in the future.
+        InvalidExpiry,
+        /// The evictions of the blocks around the expiry are full.
+        ExpiriesFull,
+        /// A nullifier has already been spent.
+        NullifierSpent,
+        /// A nullifier is spent twice in the same transaction.
+        DuplicateNullifier,
+        /// An anchor is not a root of the tree of its pool.
+        UnknownAnchor,
+        /// The value balances of the transaction do not add up to zero.
+        ValueBalanceMismatch,
+        /// The Orchard bundle is empty or has malformed fields.
+        InvalidOrchardBundle,
+        /// The note commitment tree of a pool is full.
+        TreeFull,
+        /// A note commitment is not a valid leaf of the tree of its pool.
+        InvalidCommitment,
+        /// No output with this note commitment is stored.
+        UnknownOutput,
+        /// The output could not be decrypted, or does not pay the disclosed value to the
+        /// disclosed address.
+        DisclosureFailed,
+        /// The payment has already been disclosed to this auditor.
+        AttestationExists,
+        /// There is no attestation of this output for the auditor.
+        UnknownAttestation,
+        /// This kind of pool operation is paused.
+        Paused,
+        /// The transaction would exceed the unshielding limit of the block or the era.
+        UnshieldLimitExceeded,
+        /// The transaction would push the balance of a pool below zero.
+        TurnstileViolation,
+        /// An unsigned transaction has no positive value balance to pay its fee from.
+        NoFee,
+        /// The transaction commits to another transparent account than its sender.
+        AccountMismatch,
+    }
+}
+
 decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin  {
+        type Error = Error<T>;
+
         fn deposit_event() = default;
 
+        #[weight = SimpleDispatchInfo::FixedOperational(
+            EVICTION_WEIGHT
+                .saturating_mul(T::MaxEvictionsPerBlock::get() as Weight)
+                .saturating_add(ROOT_PRUNING_WEIGHT)
+        )]
+        fn on_initialize(now: T::BlockNumber) {
+            Self::evict_expired(now);
+            Self::prune_roots(now);
+        }
+
         /// TODO:
         #[weight = SimpleDispatchInfo::FixedNormal(1_500_000)]
         fn validate_spend(
@@ -86,15 +635,19 @@ decl_module! {
             spend_desc: SaplingSpendDescription,
         ) -> DispatchResult {
             ensure_signed(origin)?;
+            ensure!(!Self::is_paused(PauseKind::Transfer), Error::<T>::Paused);
 
             let spend_vk = zcash::spend_vk();
             let mut point = Point::default();
 
             accept_spend(&spend_vk.into(), &sighash, &mut point, &spend_desc.inner)
-                .map_err(|_| DispatchError::Other("verification failed"))
+                .map_err(|_| Error::<T>::VerificationFailed.into())
         }
 
-        /// TODO:
+        /// Verify an output and store its encrypted note data until `expires_at`.
+        ///
+        /// The sender reserves `ByteDeposit` for every stored byte. `expires_at` may be at most
+        /// `MaxOutputLifetime` blocks in the future.
         #[weight = SimpleDispatchInfo::FixedNormal(1_500_000)]
         fn validate_output(
             origin, 
@@ -107,34 +660,746 @@ decl_module! {
             out_cipher_text: Vec<u8>,
             // 192 elements
             zkproof: Vec<u8>,
+            expires_at: T::BlockNumber,
         ) -> DispatchResult {
-            ensure_signed(origin)?;
+            let who = ensure_signed(origin)?;
+            ensure!(!Self::is_paused(PauseKind::Transfer), Error::<T>::Paused);
+            let now = <system::Module<T>>::block_number();
+            ensure!(
+                expires_at > now && expires_at <= now.saturating_add(T::MaxOutputLifetime::get()),
+                Error::<T>::InvalidExpiry
+            );
+            ensure!(
+                enc_cipher_text.len() == ENC_CIPHER_TEXT_LEN &&
+                    out_cipher_text.len() == OUT_CIPHER_TEXT_LEN &&
+                    zkproof.len() == ZKPROOF_LEN,
+                Error::<T>::InvalidLength
+            );
+            ensure!(!<Outputs<T>>::contains_key(note_commitment), Error::<T>::OutputExists);
+            let eviction_block = Self::eviction_block(expires_at)?;
 
             let output_vk = zcash::output_vk();
             let mut point = Point::default();
-            let output_desc = SaplingOutputDescription {
+            let output_desc = sapling::SaplingOutputDescription {
                 value_commitment,
                 note_commitment,
                 ephemeral_key,
                 enc_cipher_text: {
-                    let mut data = [0u8; 580];
-                    data.copy_from_slice(&zkproof);
+                    let mut data = [0u8; ENC_CIPHER_TEXT_LEN];
+                    data.copy_from_slice(&enc_cipher_text);
                     data
                 },
                 out_cipher_text: {
-                    let mut data = [0u8; 80];
-                    data.copy_from_slice(&zkproof);
+                    let mut data = [0u8; OUT_CIPHER_TEXT_LEN];
+                    data.copy_from_slice(&out_cipher_text);
                     data
                 },
                 zkproof: {
-                    let mut data = [0u8; 192];
+                    let mut data = [0u8; ZKPROOF_LEN];
                     data.copy_from_slice(&zkproof);
                     data
                 },
             };
 
             accept_output(&output_vk.into(), &mut point, &output_desc)
-                .map_err(|_| DispatchError::Other("verification failed"))
+                .map_err(|_| Error::<T>::VerificationFailed)?;
+
+            let mut record = OutputRecord {
+                value_commitment,
+                ephemeral_key,
+                enc_cipher_text,
+                out_cipher_text,
+                owner: who.clone(),
+                deposit: Default::default(),
+                expires_at,
+            };
+            record.deposit = T::ByteDeposit::get()
+                .saturating_mul(((record.encode().len() + note_commitment.len()) as u32).into());
+            T::Currency::reserve(&who, record.deposit)?;
+
+            <Outputs<T>>::insert(note_commitment, record);
+            <Expiries<T>>::append_or_insert(eviction_block, &[note_commitment][..]);
+
+            Self::deposit_event(RawEvent::OutputStored(who, note_commitment));
+            Ok(())
+        }
+
+        /// Apply a shielded transaction mixing Sapling and Orchard parts.
+        ///
+        /// Spent nullifiers are added to the nullifier set of their pool and output note
+        /// commitments to the tree of their pool. A positive value balance is unshielded to the
+        /// sender, a negative one is shielded from the sender.
+        ///
+        /// A verified transaction that would push the balance of a pool below zero can only come
+        /// from a soundness bug. It pauses unshielding until governance resumes it.
+        #[weight = FunctionOf(
+            |args: (&ShieldedTransaction,)| args.0.weight(),
+            |_: (&ShieldedTransaction,)| DispatchClass::Normal,
+            true
+        )]
+        fn transact(origin, tx: ShieldedTransaction) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::apply_transaction(Some(&who), tx)
+        }
+
+        /// Apply a shielded transaction without a transparent sender, as relayed shielded
+        /// transactions are.
+        ///
+        /// The whole value balance, which must be positive, is the fee of the transaction and goes
+        /// to `FeeHandler`. Otherwise it is applied like `transact`.
+        #[weight = FunctionOf(
+            |args: (&ShieldedTransaction,)| args.0.weight(),
+            |_: (&ShieldedTransaction,)| DispatchClass::Normal,
+            true
+        )]
+        fn transact_unsigned(origin, tx: ShieldedTransaction) -> DispatchResult {
+            ensure_none(origin)?;
+            ensure!(tx.value_balance().map_or(false, |value_balance| value_balance > 0), Error::<T>::NoFee);
+            Self::apply_transaction(None, tx)
+        }
+
+        /// Set the maximum net value unshielded per block and per era. `None` lifts a limit.
+        #[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+        fn set_unshield_limits(origin, per_block: Option<u64>, per_era: Option<u64>) {
+            ensure_root(origin)?;
+            BlockUnshieldLimit::mutate(|limit| *limit = per_block);
+            EraUnshieldLimit::mutate(|limit| *limit = per_era);
+            Self::deposit_event(RawEvent::UnshieldLimitsSet(per_block, per_era));
+        }
+
+        /// Pause or resume a kind of pool operation.
+        ///
+        /// Can be called by `PauseOrigin` or by the emergency account.
+        #[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+        fn set_paused(origin, kind: PauseKind, paused: bool) {
+            if let Err(origin) = T::PauseOrigin::try_origin(origin) {
+                let who = ensure_signed(origin)?;
+                ensure!(who == Self::emergency_account(), BadOrigin);
+            }
+            Paused::insert(kind, paused);
+            Self::deposit_event(RawEvent::PauseSet(kind, paused));
+        }
+
+        /// Set the account allowed to pause and resume pool operations in an emergency.
+        ///
+        /// Can be called by `PauseOrigin` or by root.
+        #[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+        fn set_emergency_account(origin, account: T::AccountId) {
+            if let Err(origin) = T::PauseOrigin::try_origin(origin) {
+                ensure_root(origin)?;
+            }
+            <EmergencyAccount<T>>::put(&account);
+            Self::deposit_event(RawEvent::EmergencyAccountSet(account));
+        }
+
+        /// Prove to `auditor` that the stored output `note_commitment` pays `value` to the raw
+        /// payment address `recipient`.
+        ///
+        /// `ock` is the outgoing cipher key of the output, derived from the outgoing viewing key
+        /// of its sender. It decrypts this output only, so no viewing key is revealed. The output
+        /// must not have been evicted yet. The attestation is kept until the auditor clears it,
+        /// and the sender reserves `ByteDeposit` for every stored byte.
+        #[weight = SimpleDispatchInfo::FixedNormal(1_000_000)]
+        fn disclose_payment(
+            origin,
+            auditor: T::AccountId,
+            note_commitment: [u8; 32],
+            ock: [u8; 32],
+            // 43
+            recipient: Vec<u8>,
+            value: u64,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(recipient.len() == PAYMENT_ADDRESS_LEN, Error::<T>::InvalidLength);
+            ensure!(
+                !<Attestations<T>>::contains_key(&auditor, note_commitment),
+                Error::<T>::AttestationExists
+            );
+
+            let record = Self::output(note_commitment).ok_or(Error::<T>::UnknownOutput)?;
+            let output = sapling::SaplingOutputDescription {
+                value_commitment: record.value_commitment,
+                note_commitment,
+                ephemeral_key: record.ephemeral_key,
+                enc_cipher_text: {
+                    let mut data = [0u8; ENC_CIPHER_TEXT_LEN];
+                    data.copy_from_slice(&record.enc_cipher_text);
+                    data
+                },
+                out_cipher_text: {
+                    let mut data = [0u8; OUT_CIPHER_TEXT_LEN];
+                    data.copy_from_slice(&record.out_cipher_text);
+                    data
+                },
+                // not needed for decryption, the proof was checked when the output was stored.
+                zkproof: [0u8; ZKPROOF_LEN],
+            };
+            Self::attest_payment(who, auditor, &output, ock, recipient, value)
+        }
+
+        /// Prove to `auditor` that the output of a shielded transaction pays `value` to the raw
+        /// payment address `recipient`.
+        ///
+        /// `path` holds the siblings of the note commitment of `output`, from the leaves up, and
+        /// must lead from the leaf at `position` to the current root of the Sapling tree or one
+        /// of the recent roots that are valid anchors. The proof of `output` is not checked, the
+        /// decrypted note must open its note commitment. Otherwise the same as
+        /// `disclose_payment`.
+        #[weight = SimpleDispatchInfo::FixedNormal(1_000_000 + SAPLING_PATH_WEIGHT)]
+        fn disclose_transacted_payment(
+            origin,
+            auditor: T::AccountId,
+            output: SaplingOutputDescription,
+            position: u64,
+            path: Vec<[u8; 32]>,
+            ock: [u8; 32],
+            // 43
+            recipient: Vec<u8>,
+            value: u64,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            ensure!(
+                recipient.len() == PAYMENT_ADDRESS_LEN && path.len() == commitment_tree::TREE_DEPTH,
+                Error::<T>::InvalidLength
+            );
+            ensure!(
+                !<Attestations<T>>::contains_key(&auditor, output.note_commitment()),
+                Error::<T>::AttestationExists
+            );
+
+            let root = commitment_tree::root_of_path::<SaplingHasher>(output.note_commitment(), position, &path);
+            ensure!(
+                root.map_or(false, |root| Self::is_known_root(ShieldedPool::Sapling, &root)),
+                Error::<T>::UnknownOutput
+            );
+            Self::attest_payment(who, auditor, &output.inner, ock, recipient, value)
+        }
+
+        /// Remove an attestation addressed to the sender and return its deposit to the
+        /// discloser.
+        #[weight = SimpleDispatchInfo::FixedNormal(10_000)]
+        fn clear_attestation(origin, note_commitment: [u8; 32]) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            let attestation = <Attestations<T>>::take(&who, note_commitment)
+                .ok_or(Error::<T>::UnknownAttestation)?;
+            T::Currency::unreserve(&attestation.discloser, attestation.deposit);
+
+            Self::deposit_event(RawEvent::AttestationCleared(who, note_commitment));
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Whether `nullifier` has been spent in `pool`.
+    pub fn is_nullifier_spent(pool: ShieldedPool, nullifier: [u8; 32]) -> bool {
+        child::exists(CHILD_STORAGE_KEY, child_info(), &nullifier_key(pool, &nullifier))
+    }
+
+    /// Add `nullifier` to the nullifier set of `pool`.
+    fn spend_nullifier(pool: ShieldedPool, nullifier: [u8; 32]) {
+        child::put(CHILD_STORAGE_KEY, child_info(), &nullifier_key(pool, &nullifier), &true);
+    }
+
+    /// Frontier of the note commitment tree of `pool`.
+    pub fn tree(pool: ShieldedPool) -> Frontier {
+        child::get(CHILD_STORAGE_KEY, child_info(), &tree_key(pool)).unwrap_or_default()
+    }
+
+    /// Current root of the tree of `pool`.
+    pub fn tree_root(pool: ShieldedPool) -> [u8; 32] {
+        pool.root_of(&Self::tree(pool))
+    }
+
+    /// Whether `root` is the root of the tree of `pool`, or has been within `RootWindow`.
+    pub fn is_known_root(pool: ShieldedPool, root: &[u8; 32]) -> bool {
+        Roots::get(pool, root) || *root == Self::tree_root(pool)
+    }
+
+    /// Verify the proofs and signatures of `tx`, and that its anchors are known roots.
+    ///
+    /// Only the anchor check depends on the state, and dispatch checks the anchors again. The
+    /// transaction pool caches a passing result instead of verifying the proofs again on every
+    /// revalidation.
+    pub fn verify_proofs(tx: &ShieldedTransaction) -> bool {
+        let sighash = tx.sighash();
+        Self::verify_sapling(tx, &sighash).is_ok() &&
+            tx.orchard.as_ref().map_or(true, |bundle| Self::verify_orchard(bundle, &sighash).is_ok())
+    }
+
+    /// Verify the proof of a Sapling output on its own.
+    pub fn verify_output(output: &SaplingOutputDescription) -> bool {
+        let mut point = Point::default();
+        accept_output(&zcash::output_vk().into(), &mut point, &output.inner).is_ok()
+    }
+
+    /// Append a Sapling note of `value` minted by the runtime to the Sapling tree. The value is
+    /// added to the Sapling pool, its transparent counterpart must have been taken out of
+    /// circulation by the caller.
+    ///
+    /// A mint shields `value`: it fails while shielding is paused, and counts towards the net
+    /// unshielded value like a shielding transaction.
+    pub fn mint_sapling_note(note_commitment: [u8; 32], value: u64) -> DispatchResult {
+        ensure!(!Self::is_paused(PauseKind::Shield), Error::<T>::Paused);
+        let value_balance = i64::try_from(value).map_err(|_| Error::<T>::ValueBalanceMismatch)?;
+        let (block_unshielded, era_unshielded) = Self::unshielded_after(-value_balance)?;
+        let tree = Self::extend_tree(ShieldedPool::Sapling, sp_std::iter::once(note_commitment))?;
+        let balance = PoolBalances::get(ShieldedPool::Sapling)
+            .checked_add(value)
+            .ok_or(Error::<T>::ValueBalanceMismatch)?;
+        Self::store_tree(ShieldedPool::Sapling, tree);
+        PoolBalances::insert(ShieldedPool::Sapling, balance);
+        <BlockUnshielded<T>>::put(block_unshielded);
+        <EraUnshielded<T>>::put(era_unshielded);
+        Ok(())
+    }
+
+    /// Check that `nullifiers` are distinct and unspent in `pool`.
+    fn check_nullifiers(pool: ShieldedPool, nullifiers: &[[u8; 32]]) -> DispatchResult {
+        let mut seen = BTreeSet::new();
+        for nullifier in nullifiers {
+            ensure!(seen.insert(*nullifier), Error::<T>::DuplicateNullifier);
+            ensure!(!Self::is_nullifier_spent(pool, *nullifier), Error::<T>::NullifierSpent);
+        }
+        Ok(())
+    }
+
+    /// Verify the Sapling spends and outputs of `tx` and its Sapling binding signature.
+    fn verify_sapling(tx: &ShieldedTransaction, sighash: &[u8; 32]) -> DispatchResult {
+        if tx.sapling_spends.is_empty() && tx.sapling_outputs.is_empty() {
+            ensure!(tx.sapling_value_balance == 0, Error::<T>::ValueBalanceMismatch);
+            return Ok(());
+        }
+
+        let mut point = Point::default();
+        for spend in &tx.sapling_spends {
+            ensure!(
+                Self::is_known_root(ShieldedPool::Sapling, &spend.inner.anchor),
+                Error::<T>::UnknownAnchor
+            );
+            accept_spend(&zcash::spend_vk().into(), sighash, &mut point, &spend.inner)
+                .map_err(|_| Error::<T>::VerificationFailed)?;
+        }
+        for output in &tx.sapling_outputs {
+            accept_output(&zcash::output_vk().into(), &mut point, &output.inner)
+                .map_err(|_| Error::<T>::VerificationFailed)?;
+        }
+
+        final_check(tx.sapling_value_balance, &tx.sapling_binding_sig, sighash, &point)
+            .map_err(|_| Error::<T>::VerificationFailed.into())
+    }
+
+    /// Verify the Halo 2 proof, spend authorization signatures and binding signature of an
+    /// Orchard bundle.
+    fn verify_orchard(bundle: &OrchardBundle, sighash: &[u8; 32]) -> DispatchResult {
+        ensure!(
+            Self::is_known_root(ShieldedPool::Orchard, &bundle.anchor),
+            Error::<T>::UnknownAnchor
+        );
+        shielded_crypto::verify_orchard_bundle(&bundle.encode(), sighash).map_err(|error| match error {
+            OrchardError::InvalidBundle => Error::<T>::InvalidOrchardBundle.into(),
+            OrchardError::VerificationFailed => Error::<T>::VerificationFailed.into(),
+        })
+    }
+
+    /// Net value unshielded in the current block and era with `value_balance` added, along with
+    /// the block and the era index. Fails if a limit would be exceeded.
+    fn unshielded_after(
+        value_balance: i64,
+    ) -> Result<((T::BlockNumber, i64), (T::BlockNumber, i64)), Error<T>> {
+        let now = <system::Module<T>>::block_number();
+        let era = now / T::UnshieldEraLength::get();
+        let add = |(at, net): (T::BlockNumber, i64), current: T::BlockNumber| {
+            let net = if at == current { net } else { 0 };
+            (current, net.saturating_add(value_balance))
+        };
+        let block_unshielded = add(<BlockUnshielded<T>>::get(), now);
+        let era_unshielded = add(<EraUnshielded<T>>::get(), era);
+
+        if value_balance > 0 {
+            let within = |net: i64, limit: Option<u64>| limit.map_or(true, |limit| net <= 0 || net as u64 <= limit);
+            ensure!(
+                within(block_unshielded.1, Self::block_unshield_limit()) &&
+                    within(era_unshielded.1, Self::era_unshield_limit()),
+                Error::<T>::UnshieldLimitExceeded
+            );
+        }
+        Ok((block_unshielded, era_unshielded))
+    }
+
+    /// Balances of the Sapling and Orchard pools after applying `tx`, or the first pool whose
+    /// balance would drop below zero.
+    fn pool_balances_after(tx: &ShieldedTransaction) -> Result<(u64, u64), ShieldedPool> {
+        let apply = |pool: ShieldedPool, value_balance: i64| {
+            let balance = i128::from(PoolBalances::get(pool)) - i128::from(value_balance);
+            u64::try_from(balance).map_err(|_| pool)
+        };
+        let orchard_value_balance = tx.orchard.as_ref().map_or(0, |bundle| bundle.value_balance);
+        Ok((
+            apply(ShieldedPool::Sapling, tx.sapling_value_balance)?,
+            apply(ShieldedPool::Orchard, orchard_value_balance)?,
+        ))
+    }
+
+    /// Pause unshielding and raise the alarm for `pool`.
+    fn trip_turnstile(pool: ShieldedPool) {
+        Paused::insert(PauseKind::Unshield, true);
+        Self::deposit_event(RawEvent::TurnstileViolated(pool));
+    }
+
+    /// Whether `call` performs a pool operation that is paused.
+    pub fn is_call_paused(call: &Call<T>) -> bool {
+        let kind = match call {
+            Call::transact(tx) | Call::transact_unsigned(tx) => tx.value_balance().map(PauseKind::of),
+            Call::validate_spend(..) | Call::validate_output(..) => Some(PauseKind::Transfer),
+            _ => None,
+        };
+        kind.map_or(false, Self::is_paused)
+    }
+
+    /// Apply `tx`, moving its value balance from or to `payer`, or paying it as fee without a
+    /// payer. All checks run before storage is touched.
+    fn apply_transaction(payer: Option<&T::AccountId>, tx: ShieldedTransaction) -> DispatchResult {
+        ensure!(tx.transparent_account == payer.map(Encode::encode), Error::<T>::AccountMismatch);
+        let value_balance = tx.value_balance().ok_or(Error::<T>::ValueBalanceMismatch)?;
+        ensure!(!Self::is_paused(PauseKind::of(value_balance)), Error::<T>::Paused);
+        let (block_unshielded, era_unshielded) = Self::unshielded_after(value_balance)?;
+
+        let sapling_nullifiers = tx.sapling_spends.iter()
+            .map(|spend| spend.inner.nullifier)
+            .collect::<Vec<_>>();
+        let orchard_nullifiers = tx.orchard.iter()
+            .flat_map(|bundle| bundle.actions.iter().map(|action| action.nullifier))
+            .collect::<Vec<_>>();
+        Self::check_nullifiers(ShieldedPool::Sapling, &sapling_nullifiers)?;
+        Self::check_nullifiers(ShieldedPool::Orchard, &orchard_nullifiers)?;
+
+        let sighash = tx.sighash();
+        Self::verify_sapling(&tx, &sighash)?;
+        if let Some(bundle) = &tx.orchard {
+            Self::verify_orchard(bundle, &sighash)?;
+        }
+
+        let (sapling_balance, orchard_balance) = match Self::pool_balances_after(&tx) {
+            Ok(balances) => balances,
+            Err(pool) => {
+                // dispatch is not transactional, the pause and the alarm persist although
+                // the call fails.
+                Self::trip_turnstile(pool);
+                return Err(Error::<T>::TurnstileViolation.into());
+            }
+        };
+
+        let sapling_tree = Self::extend_tree(
+            ShieldedPool::Sapling,
+            tx.sapling_outputs.iter().map(|output| output.inner.note_commitment),
+        )?;
+        let orchard_tree = Self::extend_tree(
+            ShieldedPool::Orchard,
+            tx.orchard.iter().flat_map(|bundle| bundle.actions.iter().map(|action| action.note_commitment)),
+        )?;
+
+        match payer {
+            Some(who) => Self::move_transparent(who, value_balance)?,
+            // the value balance of an unsigned transaction is positive, see `transact_unsigned`.
+            None => T::FeeHandler::on_unbalanced(T::Currency::issue((value_balance as u64).saturated_into())),
+        }
+        for nullifier in sapling_nullifiers {
+            Self::spend_nullifier(ShieldedPool::Sapling, nullifier);
+        }
+        for nullifier in orchard_nullifiers {
+            Self::spend_nullifier(ShieldedPool::Orchard, nullifier);
+        }
+        Self::store_tree(ShieldedPool::Sapling, sapling_tree);
+        Self::store_tree(ShieldedPool::Orchard, orchard_tree);
+        PoolBalances::insert(ShieldedPool::Sapling, sapling_balance);
+        PoolBalances::insert(ShieldedPool::Orchard, orchard_balance);
+        <BlockUnshielded<T>>::put(block_unshielded);
+        <EraUnshielded<T>>::put(era_unshielded);
+
+        Self::deposit_event(RawEvent::Transacted(sighash));
+        Ok(())
+    }
+
+    /// Pay a positive `value_balance` to `who`, or take a negative one from `who`. Shielded value
+    /// is not part of the transparent issuance.
+    fn move_transparent(who: &T::AccountId, value_balance: i64) -> DispatchResult {
+        let amount = i128::from(value_balance).abs() as u64;
+        if value_balance < 0 {
+            let _ = T::Currency::withdraw(
+                who,
+                amount.saturated_into(),
+                WithdrawReason::Transfer.into(),
+                ExistenceRequirement::AllowDeath,
+            )?;
+        } else if value_balance > 0 {
+            let _ = T::Currency::deposit_creating(who, amount.saturated_into());
+        }
+        Ok(())
+    }
+
+    /// The tree of `pool` with `commitments` appended. Storage is left untouched.
+    fn extend_tree(
+        pool: ShieldedPool,
+        commitments: impl Iterator<Item = [u8; 32]>,
+    ) -> Result<Frontier, Error<T>> {
+        let mut tree = Self::tree(pool);
+        for commitment in commitments {
+            match pool {
+                ShieldedPool::Sapling => tree.append::<SaplingHasher>(commitment),
+                ShieldedPool::Orchard => tree.append::<OrchardHasher>(commitment),
+            }.map_err(|e| match e {
+                TreeError::Full => Error::<T>::TreeFull,
+                TreeError::InvalidNode => Error::<T>::InvalidCommitment,
+            })?;
+        }
+        Ok(tree)
+    }
+
+    /// Store the tree of `pool`. On the first change of the block, the root the tree had at the
+    /// start of the block is recorded as an anchor for the next `RootWindow` blocks.
+    fn store_tree(pool: ShieldedPool, tree: Frontier) {
+        let now = <system::Module<T>>::block_number();
+        if !Self::recorded_roots(now).iter().any(|(recorded, _)| *recorded == pool) {
+            let root = Self::tree_root(pool);
+            Roots::insert(pool, root, true);
+            <RecordedRoots<T>>::append_or_insert(now, &[(pool, root)][..]);
+        }
+        child::put(CHILD_STORAGE_KEY, child_info(), &tree_key(pool), &tree);
+    }
+
+    /// Remove the roots recorded at the block leaving the window at `now`.
+    fn prune_roots(now: T::BlockNumber) {
+        let window = T::RootWindow::get();
+        if now > window {
+            for (pool, root) in <RecordedRoots<T>>::take(now - window) {
+                Roots::remove(pool, root);
+            }
+        }
+    }
+
+    /// Check that `output` pays `value` to `recipient` and record the attestation of the
+    /// payment for `auditor`, reserving its deposit from `who`.
+    fn attest_payment(
+        who: T::AccountId,
+        auditor: T::AccountId,
+        output: &sapling::SaplingOutputDescription,
+        ock: [u8; 32],
+        recipient: Vec<u8>,
+        value: u64,
+    ) -> DispatchResult {
+        let note_commitment = output.note_commitment;
+        let (note_value, address) = Self::recover_payment(output, ock)
+            .ok_or(Error::<T>::DisclosureFailed)?;
+        ensure!(note_value == value && address[..] == recipient[..], Error::<T>::DisclosureFailed);
+
+        let mut attestation = Attestation {
+            discloser: who.clone(),
+            recipient,
+            value,
+            deposit: Default::default(),
+            disclosed_at: <system::Module<T>>::block_number(),
+        };
+        attestation.deposit = T::ByteDeposit::get().saturating_mul(
+            ((attestation.encode().len() + auditor.encode().len() + note_commitment.len()) as u32).into()
+        );
+        T::Currency::reserve(&who, attestation.deposit)?;
+
+        <Attestations<T>>::insert(&auditor, note_commitment, attestation);
+        Self::deposit_event(RawEvent::PaymentDisclosed(who, auditor, note_commitment, value));
+        Ok(())
+    }
+
+    /// Decrypt an output with its outgoing cipher key and return the value and raw recipient
+    /// address of its note. Fails if the note does not open the note commitment of the output.
+    ///
+    /// Notes are decrypted under the plaintext version set by `NotePlaintextVersion`.
+    fn recover_payment(output: &sapling::SaplingOutputDescription, ock: [u8; 32]) -> Option<(u64, Vec<u8>)> {
+        shielded_crypto::recover_sapling_payment(
+            &SaplingOutputDescription::from(output.clone()).encode(),
+            &ock,
+            T::NotePlaintextVersion::get(),
+        )
+    }
+
+    /// Block to queue the eviction of an output expiring at `at`: the first block from `at`, and
+    /// among the next `MAX_EVICTION_SEARCH`, whose evictions are not full.
+    fn eviction_block(mut at: T::BlockNumber) -> Result<T::BlockNumber, Error<T>> {
+        let max = T::MaxEvictionsPerBlock::get() as usize;
+        for _ in 0..MAX_EVICTION_SEARCH {
+            if <Expiries<T>>::decode_len(at).unwrap_or(0) < max {
+                return Ok(at);
+            }
+            at += One::one();
+        }
+        Err(Error::<T>::ExpiriesFull)
+    }
+
+    /// Evict up to `MaxEvictionsPerBlock` outputs that expired before `now`.
+    fn evict_expired(now: T::BlockNumber) {
+        let mut remaining = T::MaxEvictionsPerBlock::get();
+        let mut block = Self::eviction_cursor();
+        while block < now && remaining > 0 {
+            let mut expired = <Expiries<T>>::take(block);
+            let count = remaining.min(expired.len() as u32);
+            for note_commitment in expired.drain(..count as usize) {
+                Self::evict(note_commitment);
+            }
+            remaining = remaining.saturating_sub(count.max(1));
+            if !expired.is_empty() {
+                <Expiries<T>>::insert(block, expired);
+                break;
+            }
+            block += One::one();
+        }
+        <EvictionCursor<T>>::put(block);
+    }
+
+    /// Remove an output and return its deposit minus the `RentFraction` share, which is slashed.
+    fn evict(note_commitment: [u8; 32]) {
+        if let Some(record) = <Outputs<T>>::take(note_commitment) {
+            let rent = T::RentFraction::get() * record.deposit;
+            let (imbalance, _) = T::Currency::slash_reserved(&record.owner, rent);
+            T::Slash::on_unbalanced(imbalance);
+            T::Currency::unreserve(&record.owner, record.deposit.saturating_sub(rent));
+            Self::deposit_event(RawEvent::OutputEvicted(note_commitment));
+        }
+    }
+}
+
+/// Checks calls of the module before they enter the transaction pool.
+///
+/// Calls whose pool operation is paused are rejected. A shielded transaction is rejected if it
+/// spends a nullifier that is already spent, otherwise it provides a tag per nullifier. The pool
+/// keeps a single transaction per tag, so conflicting spends of a note are resolved by priority:
+/// a spend replaces a pending one only by paying more.
+///
+/// Shielded transactions are prioritised by fee per weight on top of the fee based priority of
+/// `ChargeTransactionPayment`, which also accounts for the tip. An unsigned shielded transaction
+/// pays its value balance as fee instead, which must cover the fee of a signed one. Proofs are not
+/// verified here, see `Module::verify_proofs`.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct CheckShielded<T: Trait + Send + Sync>(PhantomData<T>);
+
+impl<T: Trait + Send + Sync> CheckShielded<T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Trait + pallet_transaction_payment::Trait + Send + Sync> CheckShielded<T> {
+    /// Validity of `call`, sent by `who` or unsigned, dispatched with `info` in an extrinsic of
+    /// `len` bytes.
+    pub fn validate_call(
+        who: Option<&T::AccountId>,
+        call: &Call<T>,
+        info: DispatchInfo,
+        len: usize,
+    ) -> TransactionValidity {
+        if Module::<T>::is_call_paused(call) {
+            return InvalidTransaction::Custom(PAUSED_TRANSACTION).into();
+        }
+        let (tx, paid) = match call {
+            Call::transact(tx) => (tx, None),
+            Call::transact_unsigned(tx) => (tx, Some(tx.value_balance().unwrap_or(0))),
+            _ => return Ok(ValidTransaction::default()),
+        };
+        if tx.transparent_account != who.map(Encode::encode) {
+            return InvalidTransaction::Custom(ACCOUNT_MISMATCH).into();
+        }
+
+        let nullifiers = tx.sapling_spends.iter()
+            .map(|spend| (ShieldedPool::Sapling, spend.inner.nullifier))
+            .chain(tx.orchard.iter().flat_map(|bundle| {
+                bundle.actions.iter().map(|action| (ShieldedPool::Orchard, action.nullifier))
+            }))
+            .collect::<Vec<_>>();
+        let mut seen = BTreeSet::new();
+        for (pool, nullifier) in &nullifiers {
+            if !seen.insert((*pool, *nullifier)) {
+                return InvalidTransaction::Custom(DUPLICATE_NULLIFIER).into();
+            }
+            if Module::<T>::is_nullifier_spent(*pool, *nullifier) {
+                return InvalidTransaction::Stale.into();
+            }
+        }
+
+        let fee: u128 = pallet_transaction_payment::ChargeTransactionPayment::<T>::compute_fee(
+            len as u32,
+            info,
+            Zero::zero(),
+        ).saturated_into();
+        let fee = match paid {
+            None => fee,
+            Some(paid) if paid > 0 && paid as u128 >= fee => paid as u128,
+            Some(_) => return InvalidTransaction::Payment.into(),
+        };
+        let max_weight = u128::from(<T as frame_system::Trait>::MaximumBlockWeight::get());
+        let priority = fee.saturating_mul(max_weight) / u128::from(info.weight.max(1));
+
+        Ok(ValidTransaction {
+            priority: priority.saturated_into(),
+            provides: nullifiers.into_iter()
+                .map(|(pool, nullifier)| (b"nfts/nullifier", pool, nullifier).encode())
+                .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+impl<T: Trait + pallet_transaction_payment::Trait + Send + Sync> frame_support::unsigned::ValidateUnsigned
+    for Module<T>
+{
+    type Call = Call<T>;
+
+    fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+        if let Call::transact_unsigned(_) = call {
+            // an unsigned extrinsic adds a version byte and a length prefix of at most five
+            // bytes to its call.
+            let len = call.encode().len() + 6;
+            CheckShielded::<T>::validate_call(None, call, call.get_dispatch_info(), len)
+        } else {
+            InvalidTransaction::Call.into()
+        }
+    }
+}
+
+impl<T: Trait + Send + Sync> sp_std::fmt::Debug for CheckShielded<T> {
+    #[cfg(feature = "std")]
+    fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+        write!(f, "CheckShielded")
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+        Ok(())
+    }
+}
+
+impl<T: Trait + pallet_transaction_payment::Trait + Send + Sync> SignedExtension for CheckShielded<T> where
+    <T as frame_system::Trait>::Call: IsSubType<Module<T>, T>,
+{
+    const IDENTIFIER: &'static str = "CheckShielded";
+    type AccountId = T::AccountId;
+    type Call = <T as frame_system::Trait>::Call;
+    type AdditionalSigned = ();
+    type DispatchInfo = DispatchInfo;
+    type Pre = ();
+
+    fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+        Ok(())
+    }
+
+    fn validate(
+        &self,
+        who: &Self::AccountId,
+        call: &Self::Call,
+        info: Self::DispatchInfo,
+        len: usize,
+    ) -> TransactionValidity {
+        match call.is_sub_type() {
+            Some(call) => Self::validate_call(Some(who), call, info, len),
+            None => Ok(ValidTransaction::default()),
         }
     }
 }
@@ -142,13 +1407,14 @@ decl_module! {
 #[cfg(test)]
 mod tests {
     use super::*;
-	use frame_support::{impl_outer_origin, parameter_types, weights::Weight};
+	use frame_support::{assert_noop, impl_outer_origin, parameter_types, traits::ReservableCurrency, weights::Weight};
+    use frame_system::EnsureRoot;
 	use sp_core::H256;
     use frame_system;
     use hex_literal::hex;
     use sp_runtime::{
         testing::Header,
-        traits::{BadOrigin, BlakeTwo256, Hash, IdentityLookup},
+        traits::{BlakeTwo256, ConvertInto, IdentityLookup, OnInitialize},
         Perbill,
     };
     use sapling;
@@ -164,11 +1430,23 @@ mod tests {
 
     type System = frame_system::Module<Test>;
 
+    type Balances = pallet_balances::Module<Test>;
+
     parameter_types! {
         pub const BlockHashCount: u64 = 250;
         pub const MaximumBlockWeight: Weight = 1024;
         pub const MaximumBlockLength: u32 = 2 * 1024;
         pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+        pub const ExistentialDeposit: u64 = 1;
+        pub const ByteDeposit: u64 = 1;
+        pub const MaxOutputLifetime: u64 = 100;
+        pub const MaxEvictionsPerBlock: u32 = 2;
+        pub const RentFraction: Perbill = Perbill::from_percent(50);
+        pub const UnshieldEraLength: u64 = 10;
+        pub const RootWindow: u64 = 5;
+        pub const NotePlaintextVersion: u8 = 2;
+        pub const TransactionBaseFee: u64 = 10;
+        pub const TransactionByteFee: u64 = 1;
     }
 
     impl frame_system::Trait for Test {
@@ -188,13 +1466,68 @@ mod tests {
         type AvailableBlockRatio = AvailableBlockRatio;
         type Version = ();
         type ModuleToIndex = ();
-        type AccountData = ();
+        type AccountData = pallet_balances::AccountData<u64>;
         type OnNewAccount = ();
         type OnKilledAccount = ();
     }
 
+    impl pallet_balances::Trait for Test {
+        type Balance = u64;
+        type DustRemoval = ();
+        type Event = ();
+        type ExistentialDeposit = ExistentialDeposit;
+        type AccountStore = System;
+    }
+
+    impl pallet_transaction_payment::Trait for Test {
+        type Currency = Balances;
+        type OnTransactionPayment = ();
+        type TransactionBaseFee = TransactionBaseFee;
+        type TransactionByteFee = TransactionByteFee;
+        type WeightToFee = ConvertInto;
+        type FeeMultiplierUpdate = ();
+    }
+
     impl Trait for Test {
         type Event = ();
+        type Currency = Balances;
+        type ByteDeposit = ByteDeposit;
+        type MaxOutputLifetime = MaxOutputLifetime;
+        type MaxEvictionsPerBlock = MaxEvictionsPerBlock;
+        type RentFraction = RentFraction;
+        type Slash = ();
+        type FeeHandler = ();
+        type UnshieldEraLength = UnshieldEraLength;
+        type RootWindow = RootWindow;
+        type NotePlaintextVersion = NotePlaintextVersion;
+        type PauseOrigin = EnsureRoot<u64>;
+    }
+
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        pallet_balances::GenesisConfig::<Test> {
+            balances: vec![(1, 10_000)],
+        }.assimilate_storage(&mut t).unwrap();
+        GenesisConfig::<Test> {
+            emergency_account: 3,
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    fn store_output(note_commitment: [u8; 32], expires_at: u64) {
+        let record = OutputRecord {
+            value_commitment: [0u8; 32],
+            ephemeral_key: [0u8; 32],
+            enc_cipher_text: vec![0u8; ENC_CIPHER_TEXT_LEN],
+            out_cipher_text: vec![0u8; OUT_CIPHER_TEXT_LEN],
+            owner: 1,
+            deposit: 1000,
+            expires_at,
+        };
+        assert!(Balances::reserve(&1, record.deposit).is_ok());
+        <Outputs<Test>>::insert(note_commitment, record);
+        let eviction_block = Nfts::eviction_block(expires_at).ok().unwrap();
+        <Expiries<Test>>::append_or_insert(eviction_block, &[note_commitment][..]);
     }
 
     #[test]
@@ -217,5 +1550,455 @@ mod tests {
             spend.into(),
         ).unwrap();
     }
+
+    fn spend(anchor: [u8; 32], nullifier: [u8; 32]) -> SaplingSpendDescription {
+        sapling::SaplingSpendDescription {
+            value_commitment: [0u8; 32],
+            anchor,
+            nullifier,
+            randomized_key: [0u8; 32],
+            zkproof: [0u8; 192],
+            spend_auth_sig: [0u8; 64],
+        }.into()
+    }
+
+    fn transaction(spends: Vec<SaplingSpendDescription>, value_balance: i64) -> ShieldedTransaction {
+        ShieldedTransaction {
+            sapling_spends: spends,
+            sapling_outputs: vec![],
+            sapling_value_balance: value_balance,
+            sapling_binding_sig: [0u8; 64],
+            orchard: None,
+            transparent_account: Some(1u64.encode()),
+        }
+    }
+
+    fn unsigned_transaction(spends: Vec<SaplingSpendDescription>, value_balance: i64) -> ShieldedTransaction {
+        ShieldedTransaction {
+            transparent_account: None,
+            ..transaction(spends, value_balance)
+        }
+    }
+
+    #[test]
+    fn test_transact_checks_value_balance() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![], 5)),
+                Error::<Test>::ValueBalanceMismatch
+            );
+        });
+    }
+
+    #[test]
+    fn test_transact_checks_nullifiers() {
+        new_test_ext().execute_with(|| {
+            let anchor = Nfts::tree_root(ShieldedPool::Sapling);
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![spend(anchor, [1u8; 32]), spend(anchor, [1u8; 32])], 0)),
+                Error::<Test>::DuplicateNullifier
+            );
+
+            Nfts::spend_nullifier(ShieldedPool::Sapling, [2u8; 32]);
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![spend(anchor, [2u8; 32])], 0)),
+                Error::<Test>::NullifierSpent
+            );
+
+            // nullifier sets of the pools are separate.
+            Nfts::spend_nullifier(ShieldedPool::Orchard, [3u8; 32]);
+            assert!(!Nfts::is_nullifier_spent(ShieldedPool::Sapling, [3u8; 32]));
+        });
+    }
+
+    #[test]
+    fn test_shielded_state_lives_in_child_trie() {
+        new_test_ext().execute_with(|| {
+            Nfts::spend_nullifier(ShieldedPool::Sapling, [1u8; 32]);
+            let tree = Nfts::extend_tree(ShieldedPool::Sapling, vec![[1u8; 32]].into_iter()).ok().unwrap();
+            Nfts::store_tree(ShieldedPool::Sapling, tree.clone());
+
+            let key = nullifier_key(ShieldedPool::Sapling, &[1u8; 32]);
+            assert!(child::exists(CHILD_STORAGE_KEY, child_info(), &key));
+            assert!(sp_io::storage::get(&key).is_none());
+            assert_eq!(Nfts::tree(ShieldedPool::Sapling), tree);
+            assert!(Nfts::is_known_root(ShieldedPool::Sapling, &tree.root.unwrap()));
+        });
+    }
+
+    #[test]
+    fn test_roots_leave_the_window() {
+        new_test_ext().execute_with(|| {
+            System::set_block_number(1);
+            let empty_root = Nfts::tree_root(ShieldedPool::Sapling);
+            for leaf in 1..=2u8 {
+                let tree = Nfts::extend_tree(ShieldedPool::Sapling, vec![[leaf; 32]].into_iter()).ok().unwrap();
+                Nfts::store_tree(ShieldedPool::Sapling, tree);
+            }
+            let block_root = Nfts::tree_root(ShieldedPool::Sapling);
+
+            // only the root at the start of the block is recorded.
+            assert_eq!(Nfts::recorded_roots(1), vec![(ShieldedPool::Sapling, empty_root)]);
+
+            System::set_block_number(2);
+            let tree = Nfts::extend_tree(ShieldedPool::Sapling, vec![[3u8; 32]].into_iter()).ok().unwrap();
+            Nfts::store_tree(ShieldedPool::Sapling, tree);
+            assert!(Nfts::is_known_root(ShieldedPool::Sapling, &block_root));
+
+            Nfts::on_initialize(6);
+            assert!(!Nfts::is_known_root(ShieldedPool::Sapling, &empty_root));
+            assert!(Nfts::is_known_root(ShieldedPool::Sapling, &block_root));
+            Nfts::on_initialize(7);
+            assert!(!Nfts::is_known_root(ShieldedPool::Sapling, &block_root));
+        });
+    }
+
+    #[test]
+    fn test_transact_checks_anchor() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![spend([9u8; 32], [1u8; 32])], 0)),
+                Error::<Test>::UnknownAnchor
+            );
+        });
+    }
+
+    #[test]
+    fn test_transact_checks_unshield_limits() {
+        new_test_ext().execute_with(|| {
+            assert!(Nfts::set_unshield_limits(frame_system::RawOrigin::Root.into(), Some(10), Some(15)).is_ok());
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![], 11)),
+                Error::<Test>::UnshieldLimitExceeded
+            );
+
+            <BlockUnshielded<Test>>::put((0, 8));
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![], 3)),
+                Error::<Test>::UnshieldLimitExceeded
+            );
+
+            // the block limit restarts in the next block, the era limit does not.
+            System::set_block_number(1);
+            <EraUnshielded<Test>>::put((0, 8));
+            assert_eq!(Nfts::unshielded_after(7).ok(), Some(((1, 7), (0, 15))));
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![], 8)),
+                Error::<Test>::UnshieldLimitExceeded
+            );
+
+            // shielding is never limited.
+            assert_eq!(Nfts::unshielded_after(-20).ok(), Some(((1, -20), (0, -12))));
+        });
+    }
+
+    #[test]
+    fn test_turnstile_pauses_unshielding() {
+        new_test_ext().execute_with(|| {
+            PoolBalances::insert(ShieldedPool::Sapling, 10);
+            assert_eq!(Nfts::pool_balances_after(&transaction(vec![], 4)), Ok((6, 0)));
+            assert_eq!(Nfts::pool_balances_after(&transaction(vec![], -4)), Ok((14, 0)));
+            assert_eq!(Nfts::pool_balances_after(&transaction(vec![], 11)), Err(ShieldedPool::Sapling));
+
+            Nfts::trip_turnstile(ShieldedPool::Sapling);
+            assert!(Nfts::is_paused(PauseKind::Unshield));
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![], 1)),
+                Error::<Test>::Paused
+            );
+
+            assert!(Nfts::set_paused(frame_system::RawOrigin::Root.into(), PauseKind::Unshield, false).is_ok());
+            assert!(!Nfts::is_paused(PauseKind::Unshield));
+        });
+    }
+
+    #[test]
+    fn test_set_emergency_account() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(Nfts::set_emergency_account(Origin::signed(3), 4), BadOrigin);
+            assert!(Nfts::set_emergency_account(frame_system::RawOrigin::Root.into(), 4).is_ok());
+            assert_eq!(Nfts::emergency_account(), 4);
+
+            assert_noop!(Nfts::set_paused(Origin::signed(3), PauseKind::Shield, true), BadOrigin);
+            assert!(Nfts::set_paused(Origin::signed(4), PauseKind::Shield, true).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_pause_blocks_pool_operations_separately() {
+        new_test_ext().execute_with(|| {
+            assert!(Nfts::set_paused(Origin::signed(1), PauseKind::Shield, true).is_err());
+            assert!(Nfts::set_paused(Origin::signed(3), PauseKind::Shield, true).is_ok());
+            assert!(Nfts::set_paused(frame_system::RawOrigin::Root.into(), PauseKind::Transfer, true).is_ok());
+
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![], -1)),
+                Error::<Test>::Paused
+            );
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![], 0)),
+                Error::<Test>::Paused
+            );
+            assert_noop!(
+                Nfts::validate_spend(Origin::signed(1), [0u8; 32], spend([0u8; 32], [0u8; 32])),
+                Error::<Test>::Paused
+            );
+            // unshielding is still open.
+            assert_noop!(
+                Nfts::transact(Origin::signed(1), transaction(vec![], 1)),
+                Error::<Test>::ValueBalanceMismatch
+            );
+
+            assert!(Nfts::is_call_paused(&Call::transact(transaction(vec![], 0))));
+            assert!(!Nfts::is_call_paused(&Call::transact(transaction(vec![], 1))));
+            assert!(!Nfts::is_call_paused(&Call::clear_attestation([0u8; 32])));
+        });
+    }
+
+    #[test]
+    fn test_mint_sapling_note_shields() {
+        new_test_ext().execute_with(|| {
+            System::set_block_number(1);
+            Paused::insert(PauseKind::Shield, true);
+            assert_noop!(Nfts::mint_sapling_note([1u8; 32], 10), Error::<Test>::Paused);
+
+            Paused::insert(PauseKind::Shield, false);
+            assert!(Nfts::mint_sapling_note([1u8; 32], 10).is_ok());
+            assert_eq!(Nfts::pool_balance(ShieldedPool::Sapling), 10);
+            assert_eq!(<BlockUnshielded<Test>>::get(), (1, -10));
+            assert_eq!(<EraUnshielded<Test>>::get(), (0, -10));
+        });
+    }
+
+    #[test]
+    fn test_pool_validity_of_shielded_transactions() {
+        new_test_ext().execute_with(|| {
+            let info = |weight| DispatchInfo { weight, ..Default::default() };
+            let validity = |tx, weight| CheckShielded::<Test>::validate_call(Some(&1), &Call::transact(tx), info(weight), 100);
+
+            let valid = validity(transaction(vec![spend([0u8; 32], [1u8; 32]), spend([0u8; 32], [2u8; 32])], 0), 100)
+                .unwrap();
+            assert_eq!(valid.provides, vec![
+                (b"nfts/nullifier", ShieldedPool::Sapling, [1u8; 32]).encode(),
+                (b"nfts/nullifier", ShieldedPool::Sapling, [2u8; 32]).encode(),
+            ]);
+            // fee per weight: the base and length fees weigh more on a lighter transaction.
+            let light = validity(transaction(vec![spend([0u8; 32], [1u8; 32])], 0), 10).unwrap();
+            assert!(light.priority > valid.priority);
+
+            assert_eq!(
+                validity(transaction(vec![spend([0u8; 32], [1u8; 32]), spend([0u8; 32], [1u8; 32])], 0), 100).err(),
+                Some(InvalidTransaction::Custom(DUPLICATE_NULLIFIER).into())
+            );
+            Nfts::spend_nullifier(ShieldedPool::Sapling, [2u8; 32]);
+            assert_eq!(
+                validity(transaction(vec![spend([0u8; 32], [1u8; 32]), spend([0u8; 32], [2u8; 32])], 0), 100).err(),
+                Some(InvalidTransaction::Stale.into())
+            );
+            Paused::insert(PauseKind::Transfer, true);
+            assert_eq!(
+                validity(transaction(vec![spend([0u8; 32], [1u8; 32])], 0), 100).err(),
+                Some(InvalidTransaction::Custom(PAUSED_TRANSACTION).into())
+            );
+        });
+    }
+
+    #[test]
+    fn test_unsigned_transactions_pay_fee_from_value_balance() {
+        new_test_ext().execute_with(|| {
+            use frame_support::unsigned::ValidateUnsigned;
+
+            let anchor = Nfts::tree_root(ShieldedPool::Sapling);
+            assert_noop!(
+                Nfts::transact_unsigned(Origin::signed(1), unsigned_transaction(vec![spend(anchor, [1u8; 32])], 1)),
+                BadOrigin
+            );
+            assert_noop!(
+                Nfts::transact_unsigned(Origin::NONE, unsigned_transaction(vec![spend(anchor, [1u8; 32])], 0)),
+                Error::<Test>::NoFee
+            );
+
+            let fee = 1 << 40;
+            let valid = Nfts::validate_unsigned(&Call::transact_unsigned(unsigned_transaction(vec![spend(anchor, [1u8; 32])], fee)))
+                .unwrap();
+            assert_eq!(valid.provides, vec![(b"nfts/nullifier", ShieldedPool::Sapling, [1u8; 32]).encode()]);
+            // paying more raises the priority.
+            let richer = Nfts::validate_unsigned(&Call::transact_unsigned(unsigned_transaction(vec![spend(anchor, [1u8; 32])], 2 * fee)))
+                .unwrap();
+            assert!(richer.priority > valid.priority);
+
+            assert_eq!(
+                Nfts::validate_unsigned(&Call::transact_unsigned(unsigned_transaction(vec![spend(anchor, [1u8; 32])], 1))).err(),
+                Some(InvalidTransaction::Payment.into())
+            );
+            assert_eq!(
+                Nfts::validate_unsigned(&Call::transact(transaction(vec![spend(anchor, [1u8; 32])], fee))).err(),
+                Some(InvalidTransaction::Call.into())
+            );
+            Paused::insert(PauseKind::Unshield, true);
+            assert_eq!(
+                Nfts::validate_unsigned(&Call::transact_unsigned(unsigned_transaction(vec![spend(anchor, [1u8; 32])], fee))).err(),
+                Some(InvalidTransaction::Custom(PAUSED_TRANSACTION).into())
+            );
+        });
+    }
+
+    #[test]
+    fn test_transactions_are_bound_to_their_account() {
+        new_test_ext().execute_with(|| {
+            let tx = transaction(vec![], 0);
+            let taken_over = ShieldedTransaction { transparent_account: Some(2u64.encode()), ..tx.clone() };
+            // the signatures of the transaction do not cover a copy naming another sender.
+            assert_ne!(tx.sighash(), taken_over.sighash());
+
+            let info = DispatchInfo { weight: 100, ..Default::default() };
+            assert_eq!(
+                CheckShielded::<Test>::validate_call(Some(&2), &Call::transact(tx.clone()), info, 100).err(),
+                Some(InvalidTransaction::Custom(ACCOUNT_MISMATCH).into())
+            );
+            assert_noop!(Nfts::transact(Origin::signed(2), tx), Error::<Test>::AccountMismatch);
+            assert_noop!(Nfts::transact_unsigned(Origin::NONE, transaction(vec![], 1)), Error::<Test>::AccountMismatch);
+        });
+    }
+
+    #[test]
+    fn test_validate_output_checks_lengths() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                Nfts::validate_output(
+                    Origin::signed(1),
+                    [0u8; 32],
+                    [0u8; 32],
+                    [0u8; 32],
+                    vec![0u8; ENC_CIPHER_TEXT_LEN],
+                    vec![0u8; OUT_CIPHER_TEXT_LEN],
+                    vec![0u8; ZKPROOF_LEN - 1],
+                    10,
+                ),
+                Error::<Test>::InvalidLength
+            );
+        });
+    }
+
+    #[test]
+    fn test_eviction_search_is_bounded() {
+        new_test_ext().execute_with(|| {
+            System::set_block_number(1);
+            for block in 2..2 + MAX_EVICTION_SEARCH as u64 {
+                <Expiries<Test>>::insert(block, vec![[0u8; 32]; 2]);
+            }
+            assert_noop!(
+                Nfts::validate_output(
+                    Origin::signed(1),
+                    [0u8; 32],
+                    [1u8; 32],
+                    [0u8; 32],
+                    vec![0u8; ENC_CIPHER_TEXT_LEN],
+                    vec![0u8; OUT_CIPHER_TEXT_LEN],
+                    vec![0u8; ZKPROOF_LEN],
+                    2,
+                ),
+                Error::<Test>::ExpiriesFull
+            );
+        });
+    }
+
+    #[test]
+    fn test_expired_outputs_are_evicted() {
+        new_test_ext().execute_with(|| {
+            store_output([1u8; 32], 1);
+            store_output([2u8; 32], 1);
+            store_output([3u8; 32], 1);
+            assert_eq!(Nfts::expiries(1).len(), 2);
+            assert_eq!(Nfts::expiries(2).len(), 1);
+
+            for n in 1..=2 {
+                Nfts::on_initialize(n);
+            }
+            assert!(Nfts::output([1u8; 32]).is_none());
+            assert!(Nfts::output([2u8; 32]).is_none());
+            assert!(Nfts::output([3u8; 32]).is_some());
+            assert_eq!(Balances::reserved_balance(1), 1000);
+            assert_eq!(Balances::free_balance(1), 10_000 - 3000 + 2 * 500);
+
+            Nfts::on_initialize(3);
+            assert!(Nfts::output([3u8; 32]).is_none());
+            assert_eq!(Balances::reserved_balance(1), 0);
+        });
+    }
+
+    #[test]
+    fn test_disclose_payment_checks_output() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                Nfts::disclose_payment(Origin::signed(1), 2, [1u8; 32], [0u8; 32], vec![0u8; PAYMENT_ADDRESS_LEN - 1], 5),
+                Error::<Test>::InvalidLength
+            );
+            assert_noop!(
+                Nfts::disclose_payment(Origin::signed(1), 2, [1u8; 32], [0u8; 32], vec![0u8; PAYMENT_ADDRESS_LEN], 5),
+                Error::<Test>::UnknownOutput
+            );
+
+            store_output([1u8; 32], 10);
+            assert_noop!(
+                Nfts::disclose_payment(Origin::signed(1), 2, [1u8; 32], [0u8; 32], vec![0u8; PAYMENT_ADDRESS_LEN], 5),
+                Error::<Test>::DisclosureFailed
+            );
+        });
+    }
+
+    #[test]
+    fn test_disclose_transacted_payment_checks_path() {
+        new_test_ext().execute_with(|| {
+            let output: SaplingOutputDescription = sapling::SaplingOutputDescription {
+                value_commitment: [0u8; 32],
+                note_commitment: [1u8; 32],
+                ephemeral_key: [0u8; 32],
+                enc_cipher_text: [0u8; ENC_CIPHER_TEXT_LEN],
+                out_cipher_text: [0u8; OUT_CIPHER_TEXT_LEN],
+                zkproof: [0u8; ZKPROOF_LEN],
+            }.into();
+            let disclose = |position: u64, path: Vec<[u8; 32]>| Nfts::disclose_transacted_payment(
+                Origin::signed(1), 2, output.clone(), position, path, [0u8; 32], vec![0u8; PAYMENT_ADDRESS_LEN], 5,
+            );
+            let empty_path: Vec<[u8; 32]> = (0..commitment_tree::TREE_DEPTH).map(SaplingHasher::empty_root).collect();
+
+            assert_noop!(disclose(0, empty_path[1..].to_vec()), Error::<Test>::InvalidLength);
+            assert_noop!(disclose(0, empty_path.clone()), Error::<Test>::UnknownOutput);
+
+            // the first leaf of the tree has empty siblings only.
+            let tree = Nfts::extend_tree(ShieldedPool::Sapling, vec![[1u8; 32]].into_iter()).ok().unwrap();
+            Nfts::store_tree(ShieldedPool::Sapling, tree);
+            assert_noop!(disclose(1, empty_path.clone()), Error::<Test>::UnknownOutput);
+            assert_noop!(disclose(0, empty_path), Error::<Test>::DisclosureFailed);
+        });
+    }
+
+    #[test]
+    fn test_clear_attestation_returns_deposit() {
+        new_test_ext().execute_with(|| {
+            assert!(Balances::reserve(&1, 100).is_ok());
+            <Attestations<Test>>::insert(2, [1u8; 32], Attestation {
+                discloser: 1,
+                recipient: vec![0u8; PAYMENT_ADDRESS_LEN],
+                value: 5,
+                deposit: 100,
+                disclosed_at: 1,
+            });
+            assert_noop!(
+                Nfts::disclose_payment(Origin::signed(1), 2, [1u8; 32], [0u8; 32], vec![0u8; PAYMENT_ADDRESS_LEN], 5),
+                Error::<Test>::AttestationExists
+            );
+
+            // only the auditor can clear it.
+            assert_noop!(
+                Nfts::clear_attestation(Origin::signed(1), [1u8; 32]),
+                Error::<Test>::UnknownAttestation
+            );
+            assert!(Nfts::clear_attestation(Origin::signed(2), [1u8; 32]).is_ok());
+            assert!(Nfts::attestation(2, [1u8; 32]).is_none());
+            assert_eq!(Balances::reserved_balance(1), 0);
+        });
+    }
 }
 
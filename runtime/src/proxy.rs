@@ -0,0 +1,543 @@
+//! Proxy accounts.
+//!
+//! An account can allow another one, its proxy, to dispatch calls on its behalf, so that keys
+//! holding funds, such as validator stashes, can stay offline. Each proxy has a type, which
+//! restricts the calls it may dispatch. Adding a proxy reserves a deposit from the account.
+//!
+//! A proxy may be given a delay. It must then announce the hash of a call, and may only
+//! dispatch the call once the delay has passed, leaving the account time to reject it.
+//! Announcing reserves a deposit from the proxy. Proxies of the types that can move funds must
+//! have a delay of at least `HighRiskDelay`.
+
+use codec::{Decode, Encode};
+use frame_support::{
+    decl_error, decl_event, decl_module, decl_storage,
+    dispatch::{DispatchError, DispatchResult},
+    ensure,
+    traits::{Currency, Get, ReservableCurrency},
+    weights::{DispatchClass, FunctionOf, GetDispatchInfo, SimpleDispatchInfo},
+    Parameter,
+};
+use frame_system::{self as system, ensure_signed};
+use sp_runtime::traits::{Dispatchable, Hash, Saturating, Zero};
+use sp_std::{boxed::Box, vec::Vec};
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+
+/// The calls a type of proxy may dispatch.
+pub trait ProxyFilter<Call> {
+    /// Whether a proxy of this type may dispatch `call`.
+    fn filter(&self, call: &Call) -> bool;
+
+    /// Whether proxies of this type can move funds, and must have a delay.
+    fn is_high_risk(&self) -> bool;
+}
+
+/// A proxy of an account.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ProxyDefinition<AccountId, ProxyType, BlockNumber> {
+    pub delegate: AccountId,
+    pub proxy_type: ProxyType,
+    /// Number of blocks between the announcement of a call and its dispatch.
+    pub delay: BlockNumber,
+}
+
+/// A call announced by a proxy.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Announcement<AccountId, Hash, BlockNumber> {
+    /// Account the call is dispatched on behalf of.
+    pub real: AccountId,
+    pub call_hash: Hash,
+    /// Block of the announcement.
+    pub height: BlockNumber,
+}
+
+pub trait Trait: frame_system::Trait {
+    type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+
+    /// A call proxies can dispatch.
+    type Call: Parameter + GetDispatchInfo + Dispatchable<Origin = <Self as frame_system::Trait>::Origin>;
+
+    /// Currency of the deposits.
+    type Currency: ReservableCurrency<Self::AccountId>;
+
+    /// The types of proxies.
+    type ProxyType: Parameter + ProxyFilter<<Self as Trait>::Call>;
+
+    /// Deposit reserved for the proxies of an account.
+    type ProxyDepositBase: Get<BalanceOf<Self>>;
+
+    /// Deposit reserved per proxy.
+    type ProxyDepositFactor: Get<BalanceOf<Self>>;
+
+    /// Maximum number of proxies of an account.
+    type MaxProxies: Get<u32>;
+
+    /// Deposit reserved for the announcements of a proxy.
+    type AnnouncementDepositBase: Get<BalanceOf<Self>>;
+
+    /// Deposit reserved per announcement.
+    type AnnouncementDepositFactor: Get<BalanceOf<Self>>;
+
+    /// Maximum number of pending announcements of a proxy.
+    type MaxPending: Get<u32>;
+
+    /// Minimum delay of high risk proxies.
+    type HighRiskDelay: Get<Self::BlockNumber>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Proxy {
+        /// Proxies of an account, and the deposit reserved for them.
+        pub Proxies get(fn proxies): map hasher(blake2_128_concat) T::AccountId
+            => (Vec<ProxyDefinition<T::AccountId, T::ProxyType, T::BlockNumber>>, BalanceOf<T>);
+
+        /// Calls announced by a proxy, and the deposit reserved for them.
+        pub Announcements get(fn announcements): map hasher(blake2_128_concat) T::AccountId
+            => (Vec<Announcement<T::AccountId, T::Hash, T::BlockNumber>>, BalanceOf<T>);
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        AccountId = <T as frame_system::Trait>::AccountId,
+        Hash = <T as frame_system::Trait>::Hash,
+        ProxyType = <T as Trait>::ProxyType,
+    {
+        /// A proxy has been added to an account: (account, proxy, type).
+        ProxyAdded(AccountId, AccountId, ProxyType),
+        /// A proxy has been removed from an account: (account, proxy, type).
+        ProxyRemoved(AccountId, AccountId, ProxyType),
+        /// A proxy has announced a call: (account, proxy, call hash).
+        Announced(AccountId, AccountId, Hash),
+        /// A proxy has dispatched a call, successfully (`true`) or not.
+        ProxyExecuted(bool),
+    }
+);
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// The sender is not a proxy of the account, or not of the given type.
+        NotProxy,
+        /// The proxy has already been added.
+        Duplicate,
+        /// The account already has `MaxProxies` proxies, or the proxy `MaxPending`
+        /// announcements.
+        TooMany,
+        /// The proxy may not dispatch this call.
+        Unproxyable,
+        /// High risk proxies must have a delay of at least `HighRiskDelay`.
+        DelayTooShort,
+        /// The proxy has a delay and must announce its calls.
+        Unannounced,
+        /// The call has not been announced, or its delay has not passed yet.
+        NotAnnounced,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        /// Dispatch `call` on behalf of `real`, of which the sender is a proxy without delay.
+        /// `force_proxy_type` picks the proxy of the sender if it has several.
+        #[weight = FunctionOf(
+            |args: (&T::AccountId, &Option<T::ProxyType>, &Box<<T as Trait>::Call>)|
+                args.2.get_dispatch_info().weight.saturating_add(50_000),
+            |args: (&T::AccountId, &Option<T::ProxyType>, &Box<<T as Trait>::Call>)|
+                args.2.get_dispatch_info().class,
+            true
+        )]
+        fn proxy(
+            origin,
+            real: T::AccountId,
+            force_proxy_type: Option<T::ProxyType>,
+            call: Box<<T as Trait>::Call>,
+        ) {
+            let who = ensure_signed(origin)?;
+            let definition = Self::find_proxy(&real, &who, force_proxy_type)?;
+            ensure!(definition.delay.is_zero(), Error::<T>::Unannounced);
+            ensure!(definition.proxy_type.filter(&call), Error::<T>::Unproxyable);
+            Self::do_proxy(real, *call);
+        }
+
+        /// Allow `delegate` to dispatch calls of `proxy_type` on behalf of the sender, after
+        /// announcing them `delay` blocks in advance.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn add_proxy(origin, delegate: T::AccountId, proxy_type: T::ProxyType, delay: T::BlockNumber) {
+            let who = ensure_signed(origin)?;
+            ensure!(
+                !proxy_type.is_high_risk() || delay >= T::HighRiskDelay::get(),
+                Error::<T>::DelayTooShort
+            );
+            let definition = ProxyDefinition { delegate: delegate.clone(), proxy_type: proxy_type.clone(), delay };
+
+            let (mut proxies, deposit) = Self::proxies(&who);
+            ensure!((proxies.len() as u32) < T::MaxProxies::get(), Error::<T>::TooMany);
+            ensure!(!proxies.contains(&definition), Error::<T>::Duplicate);
+            proxies.push(definition);
+
+            let new_deposit = Self::proxy_deposit(proxies.len());
+            T::Currency::reserve(&who, new_deposit.saturating_sub(deposit))?;
+            <Proxies<T>>::insert(&who, (proxies, new_deposit));
+            Self::deposit_event(RawEvent::ProxyAdded(who, delegate, proxy_type));
+        }
+
+        /// Remove a proxy of the sender.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn remove_proxy(origin, delegate: T::AccountId, proxy_type: T::ProxyType, delay: T::BlockNumber) {
+            let who = ensure_signed(origin)?;
+            let definition = ProxyDefinition { delegate: delegate.clone(), proxy_type: proxy_type.clone(), delay };
+
+            let (mut proxies, deposit) = Self::proxies(&who);
+            let position = proxies.iter().position(|proxy| proxy == &definition).ok_or(Error::<T>::NotProxy)?;
+            proxies.remove(position);
+
+            if proxies.is_empty() {
+                T::Currency::unreserve(&who, deposit);
+                <Proxies<T>>::remove(&who);
+            } else {
+                let new_deposit = Self::proxy_deposit(proxies.len());
+                T::Currency::unreserve(&who, deposit.saturating_sub(new_deposit));
+                <Proxies<T>>::insert(&who, (proxies, new_deposit));
+            }
+            Self::deposit_event(RawEvent::ProxyRemoved(who, delegate, proxy_type));
+        }
+
+        /// Remove all proxies of the sender.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn remove_proxies(origin) {
+            let who = ensure_signed(origin)?;
+            let (proxies, deposit) = <Proxies<T>>::take(&who);
+            T::Currency::unreserve(&who, deposit);
+            for proxy in proxies {
+                Self::deposit_event(RawEvent::ProxyRemoved(who.clone(), proxy.delegate, proxy.proxy_type));
+            }
+        }
+
+        /// Announce the call with hash `call_hash`, to be dispatched on behalf of `real` once the
+        /// delay of the sender has passed.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn announce(origin, real: T::AccountId, call_hash: T::Hash) {
+            let who = ensure_signed(origin)?;
+            ensure!(Self::proxies(&real).0.iter().any(|proxy| proxy.delegate == who), Error::<T>::NotProxy);
+
+            let (mut announcements, deposit) = Self::announcements(&who);
+            ensure!((announcements.len() as u32) < T::MaxPending::get(), Error::<T>::TooMany);
+            announcements.push(Announcement {
+                real: real.clone(),
+                call_hash,
+                height: <system::Module<T>>::block_number(),
+            });
+
+            let new_deposit = Self::announcement_deposit(announcements.len());
+            T::Currency::reserve(&who, new_deposit.saturating_sub(deposit))?;
+            <Announcements<T>>::insert(&who, (announcements, new_deposit));
+            Self::deposit_event(RawEvent::Announced(real, who, call_hash));
+        }
+
+        /// Withdraw an announcement of the sender.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn remove_announcement(origin, real: T::AccountId, call_hash: T::Hash) {
+            let who = ensure_signed(origin)?;
+            Self::remove_announcements(&who, |announcement| announcement.real == real && announcement.call_hash == call_hash)?;
+        }
+
+        /// Reject an announcement of a proxy of the sender.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn reject_announcement(origin, delegate: T::AccountId, call_hash: T::Hash) {
+            let who = ensure_signed(origin)?;
+            Self::remove_announcements(&delegate, |announcement| announcement.real == who && announcement.call_hash == call_hash)?;
+        }
+
+        /// Dispatch an announced `call` on behalf of `real`, once the delay of the sender has
+        /// passed.
+        #[weight = FunctionOf(
+            |args: (&T::AccountId, &Option<T::ProxyType>, &Box<<T as Trait>::Call>)|
+                args.2.get_dispatch_info().weight.saturating_add(100_000),
+            |args: (&T::AccountId, &Option<T::ProxyType>, &Box<<T as Trait>::Call>)|
+                args.2.get_dispatch_info().class,
+            true
+        )]
+        fn proxy_announced(
+            origin,
+            real: T::AccountId,
+            force_proxy_type: Option<T::ProxyType>,
+            call: Box<<T as Trait>::Call>,
+        ) {
+            let who = ensure_signed(origin)?;
+            let definition = Self::find_proxy(&real, &who, force_proxy_type)?;
+            ensure!(definition.proxy_type.filter(&call), Error::<T>::Unproxyable);
+
+            let call_hash = T::Hashing::hash_of(&call);
+            let now = <system::Module<T>>::block_number();
+            Self::remove_announcements(&who, |announcement| {
+                announcement.real == real
+                    && announcement.call_hash == call_hash
+                    && now.saturating_sub(announcement.height) >= definition.delay
+            })?;
+
+            Self::do_proxy(real, *call);
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Deposit reserved for `proxies` proxies.
+    fn proxy_deposit(proxies: usize) -> BalanceOf<T> {
+        T::ProxyDepositBase::get()
+            .saturating_add(T::ProxyDepositFactor::get().saturating_mul((proxies as u32).into()))
+    }
+
+    /// Deposit reserved for `announcements` announcements.
+    fn announcement_deposit(announcements: usize) -> BalanceOf<T> {
+        T::AnnouncementDepositBase::get()
+            .saturating_add(T::AnnouncementDepositFactor::get().saturating_mul((announcements as u32).into()))
+    }
+
+    /// The proxy `delegate` of `real`, of type `proxy_type` if given.
+    fn find_proxy(
+        real: &T::AccountId,
+        delegate: &T::AccountId,
+        proxy_type: Option<T::ProxyType>,
+    ) -> Result<ProxyDefinition<T::AccountId, T::ProxyType, T::BlockNumber>, DispatchError> {
+        Self::proxies(real).0.into_iter()
+            .find(|proxy| &proxy.delegate == delegate && proxy_type.as_ref().map_or(true, |t| t == &proxy.proxy_type))
+            .ok_or_else(|| Error::<T>::NotProxy.into())
+    }
+
+    /// Remove the announcements of `delegate` matching `matches`, and unreserve their deposit.
+    fn remove_announcements(
+        delegate: &T::AccountId,
+        matches: impl Fn(&Announcement<T::AccountId, T::Hash, T::BlockNumber>) -> bool,
+    ) -> DispatchResult {
+        let (mut announcements, deposit) = Self::announcements(delegate);
+        let count = announcements.len();
+        announcements.retain(|announcement| !matches(announcement));
+        ensure!(announcements.len() < count, Error::<T>::NotAnnounced);
+
+        if announcements.is_empty() {
+            T::Currency::unreserve(delegate, deposit);
+            <Announcements<T>>::remove(delegate);
+        } else {
+            let new_deposit = Self::announcement_deposit(announcements.len());
+            T::Currency::unreserve(delegate, deposit.saturating_sub(new_deposit));
+            <Announcements<T>>::insert(delegate, (announcements, new_deposit));
+        }
+        Ok(())
+    }
+
+    /// Dispatch `call` on behalf of `real`.
+    fn do_proxy(real: T::AccountId, call: <T as Trait>::Call) {
+        let ok = call.dispatch(frame_system::RawOrigin::Signed(real).into()).is_ok();
+        Self::deposit_event(RawEvent::ProxyExecuted(ok));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_support::{assert_noop, impl_outer_dispatch, impl_outer_origin, parameter_types, weights::Weight};
+    use sp_core::H256;
+    use sp_runtime::{
+        testing::Header,
+        traits::{BlakeTwo256, IdentityLookup},
+        Perbill,
+    };
+
+    mod proxy {
+        pub use super::super::*;
+    }
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    impl_outer_dispatch! {
+        pub enum Call for Test where origin: Origin {
+            pallet_balances::Balances,
+            proxy::Proxy,
+        }
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: Weight = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+        pub const ExistentialDeposit: u64 = 1;
+        pub const ProxyDepositBase: u64 = 2;
+        pub const ProxyDepositFactor: u64 = 1;
+        pub const MaxProxies: u32 = 2;
+        pub const AnnouncementDepositBase: u64 = 2;
+        pub const AnnouncementDepositFactor: u64 = 1;
+        pub const MaxPending: u32 = 2;
+        pub const HighRiskDelay: u64 = 2;
+    }
+
+    impl frame_system::Trait for Test {
+        type AccountId = u64;
+        type Call = ();
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Header = Header;
+        type Event = ();
+        type Origin = Origin;
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type MaximumBlockLength = MaximumBlockLength;
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type Version = ();
+        type ModuleToIndex = ();
+        type AccountData = pallet_balances::AccountData<u64>;
+        type OnNewAccount = ();
+        type OnKilledAccount = ();
+    }
+
+    impl pallet_balances::Trait for Test {
+        type Balance = u64;
+        type DustRemoval = ();
+        type Event = ();
+        type ExistentialDeposit = ExistentialDeposit;
+        type AccountStore = System;
+    }
+
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub enum ProxyType {
+        Any,
+        NonTransfer,
+    }
+
+    impl ProxyFilter<Call> for ProxyType {
+        fn filter(&self, call: &Call) -> bool {
+            match self {
+                ProxyType::Any => true,
+                ProxyType::NonTransfer => match call {
+                    Call::Balances(..) => false,
+                    _ => true,
+                },
+            }
+        }
+
+        fn is_high_risk(&self) -> bool {
+            *self == ProxyType::Any
+        }
+    }
+
+    impl Trait for Test {
+        type Event = ();
+        type Call = Call;
+        type Currency = Balances;
+        type ProxyType = ProxyType;
+        type ProxyDepositBase = ProxyDepositBase;
+        type ProxyDepositFactor = ProxyDepositFactor;
+        type MaxProxies = MaxProxies;
+        type AnnouncementDepositBase = AnnouncementDepositBase;
+        type AnnouncementDepositFactor = AnnouncementDepositFactor;
+        type MaxPending = MaxPending;
+        type HighRiskDelay = HighRiskDelay;
+    }
+
+    type System = frame_system::Module<Test>;
+    type Balances = pallet_balances::Module<Test>;
+    type Proxy = Module<Test>;
+
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        pallet_balances::GenesisConfig::<Test> {
+            balances: vec![(1, 100), (2, 100), (3, 100)],
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    fn transfer(to: u64, value: u64) -> Box<Call> {
+        Box::new(Call::Balances(pallet_balances::Call::transfer(to, value)))
+    }
+
+    fn remove_proxies() -> Box<Call> {
+        Box::new(Call::Proxy(super::Call::remove_proxies()))
+    }
+
+    #[test]
+    fn test_proxies_and_deposits() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                Proxy::add_proxy(Origin::signed(1), 2, ProxyType::Any, 1),
+                Error::<Test>::DelayTooShort
+            );
+            assert!(Proxy::add_proxy(Origin::signed(1), 2, ProxyType::NonTransfer, 0).is_ok());
+            assert_noop!(
+                Proxy::add_proxy(Origin::signed(1), 2, ProxyType::NonTransfer, 0),
+                Error::<Test>::Duplicate
+            );
+            assert!(Proxy::add_proxy(Origin::signed(1), 3, ProxyType::Any, 2).is_ok());
+            assert_noop!(
+                Proxy::add_proxy(Origin::signed(1), 4, ProxyType::NonTransfer, 0),
+                Error::<Test>::TooMany
+            );
+            assert_eq!(Balances::reserved_balance(&1), 4);
+
+            assert!(Proxy::remove_proxy(Origin::signed(1), 3, ProxyType::Any, 2).is_ok());
+            assert_eq!(Balances::reserved_balance(&1), 3);
+            assert!(Proxy::remove_proxies(Origin::signed(1)).is_ok());
+            assert_eq!(Balances::reserved_balance(&1), 0);
+            assert!(Proxy::proxies(&1).0.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_proxies_are_filtered() {
+        new_test_ext().execute_with(|| {
+            assert!(Proxy::add_proxy(Origin::signed(1), 2, ProxyType::NonTransfer, 0).is_ok());
+            assert_noop!(Proxy::proxy(Origin::signed(3), 1, None, transfer(3, 10)), Error::<Test>::NotProxy);
+            assert_noop!(Proxy::proxy(Origin::signed(2), 1, None, transfer(2, 10)), Error::<Test>::Unproxyable);
+
+            assert!(Proxy::proxy(Origin::signed(2), 1, None, remove_proxies()).is_ok());
+            assert!(Proxy::proxies(&1).0.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_announced_calls() {
+        new_test_ext().execute_with(|| {
+            System::set_block_number(1);
+            assert!(Proxy::add_proxy(Origin::signed(1), 2, ProxyType::Any, 2).is_ok());
+            assert_noop!(Proxy::proxy(Origin::signed(2), 1, None, transfer(2, 10)), Error::<Test>::Unannounced);
+
+            let call_hash = BlakeTwo256::hash_of(&transfer(2, 10));
+            assert_noop!(Proxy::announce(Origin::signed(3), 1, call_hash), Error::<Test>::NotProxy);
+            assert!(Proxy::announce(Origin::signed(2), 1, call_hash).is_ok());
+            assert_eq!(Balances::reserved_balance(&2), 3);
+
+            System::set_block_number(2);
+            assert_noop!(
+                Proxy::proxy_announced(Origin::signed(2), 1, None, transfer(2, 10)),
+                Error::<Test>::NotAnnounced
+            );
+            System::set_block_number(3);
+            assert!(Proxy::proxy_announced(Origin::signed(2), 1, None, transfer(2, 10)).is_ok());
+            assert_eq!(Balances::free_balance(&2), 110);
+            assert_eq!(Balances::reserved_balance(&2), 0);
+
+            // the account rejects the next one.
+            assert!(Proxy::announce(Origin::signed(2), 1, call_hash).is_ok());
+            assert!(Proxy::reject_announcement(Origin::signed(1), 2, call_hash).is_ok());
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            System::set_block_number(5);
+            assert_noop!(
+                Proxy::proxy_announced(Origin::signed(2), 1, None, transfer(2, 10)),
+                Error::<Test>::NotAnnounced
+            );
+        });
+    }
+}
@@ -0,0 +1,513 @@
+//! Delayed and periodic dispatch of calls.
+//!
+//! Root and the council can schedule a call to be dispatched at a future block, optionally
+//! repeated every period afterwards, for instance pausing a pool operation of the nfts module at a
+//! given block, or resuming it periodically. Tasks are named, so they can be cancelled until they
+//! have run for the last time.
+//!
+//! The calls dispatched at a block weigh at most `MaxTaskWeightPerBlock`, which is checked when a
+//! task is scheduled, so the weight of the hook is known in advance. A periodic task whose next
+//! run finds no room within `MAX_TASK_SEARCH` blocks is dropped.
+//!
+//! A task is dispatched with the origin it was scheduled with, so scheduling never grants more
+//! than dispatching the call right away would. It can only be cancelled by an origin at least as
+//! powerful as the one that scheduled it.
+
+use codec::{Decode, Encode};
+use frame_support::{
+    decl_error, decl_event, decl_module, decl_storage, ensure,
+    traits::{EnsureOrigin, Get},
+    weights::{GetDispatchInfo, SimpleDispatchInfo, Weight},
+    Parameter,
+};
+use frame_system as system;
+use sp_runtime::traits::{BadOrigin, Dispatchable, One, Zero};
+use sp_std::{boxed::Box, vec::Vec};
+
+/// Weight of dispatching a single task, besides the weight of its call.
+pub const TASK_WEIGHT: Weight = 100_000;
+
+/// Maximum length of the name of a task.
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// Number of blocks from its next run searched for room to reschedule a periodic task.
+pub const MAX_TASK_SEARCH: u32 = 16;
+
+/// A task's call is repeated `count` more times, every `period` blocks.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Period<BlockNumber> {
+    pub period: BlockNumber,
+    pub count: u32,
+}
+
+/// A scheduled call.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Task<Call, TaskOrigin, BlockNumber> {
+    pub call: Call,
+    /// Origin the call is dispatched with.
+    pub origin: TaskOrigin,
+    /// Next block the call is dispatched at.
+    pub when: BlockNumber,
+    pub period: Option<Period<BlockNumber>>,
+}
+
+pub trait Trait: frame_system::Trait {
+    type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+
+    /// A call that can be scheduled.
+    type Call: Parameter + Dispatchable<Origin = <Self as frame_system::Trait>::Origin> + GetDispatchInfo;
+
+    /// Origin a task is scheduled with, and dispatched with. Origins compare by the power they
+    /// hold.
+    type TaskOrigin: Parameter + PartialOrd + Into<<Self as frame_system::Trait>::Origin>;
+
+    /// Origin allowed to schedule and cancel tasks.
+    type ScheduleOrigin: EnsureOrigin<Self::Origin, Success = Self::TaskOrigin>;
+
+    /// Maximum number of tasks dispatched at the same block.
+    type MaxTasksPerBlock: Get<u32>;
+
+    /// Maximum weight of the tasks dispatched at the same block, each weighing its call and
+    /// `TASK_WEIGHT`.
+    type MaxTaskWeightPerBlock: Get<Weight>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Scheduler {
+        /// Scheduled tasks by name.
+        Tasks get(fn task):
+            map hasher(blake2_128_concat) Vec<u8> => Option<Task<<T as Trait>::Call, T::TaskOrigin, T::BlockNumber>>;
+
+        /// Names of the tasks dispatched at a block.
+        Agenda get(fn agenda): map hasher(twox_64_concat) T::BlockNumber => Vec<Vec<u8>>;
+
+        /// Weight of the tasks dispatched at a block.
+        AgendaWeight get(fn agenda_weight): map hasher(twox_64_concat) T::BlockNumber => Weight;
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where <T as frame_system::Trait>::BlockNumber {
+        /// A task has been scheduled at the given block.
+        Scheduled(Vec<u8>, BlockNumber),
+        /// A task has been cancelled.
+        Cancelled(Vec<u8>),
+        /// The call of a task has been dispatched, successfully (`true`) or not.
+        Dispatched(Vec<u8>, bool),
+        /// A periodic task has been dropped, no block searched had room for its next run.
+        Dropped(Vec<u8>),
+    }
+);
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// A task with this name is already scheduled.
+        TaskExists,
+        /// No task with this name is scheduled.
+        UnknownTask,
+        /// The name of the task is longer than `MAX_NAME_LENGTH`.
+        NameTooLong,
+        /// The task is not scheduled in the future.
+        InvalidTime,
+        /// The period of the task is zero.
+        InvalidPeriod,
+        /// The block already has `MaxTasksPerBlock` tasks, or not enough weight left for the task.
+        AgendaFull,
+        /// The task weighs more than `MaxTaskWeightPerBlock`.
+        TaskTooHeavy,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        #[weight = SimpleDispatchInfo::FixedOperational(T::MaxTaskWeightPerBlock::get())]
+        fn on_initialize(now: T::BlockNumber) {
+            <AgendaWeight<T>>::remove(now);
+            for name in <Agenda<T>>::take(now) {
+                if let Some(task) = <Tasks<T>>::take(&name) {
+                    Self::run(now, name, task);
+                }
+            }
+        }
+
+        /// Schedule `call` to be dispatched at block `when` with the origin of the caller, and
+        /// then repeated as given by `period`.
+        #[weight = SimpleDispatchInfo::FixedOperational(50_000)]
+        fn schedule(
+            origin,
+            name: Vec<u8>,
+            when: T::BlockNumber,
+            period: Option<Period<T::BlockNumber>>,
+            call: Box<<T as Trait>::Call>,
+        ) {
+            let origin = T::ScheduleOrigin::ensure_origin(origin)?;
+            ensure!(name.len() <= MAX_NAME_LENGTH, Error::<T>::NameTooLong);
+            ensure!(!<Tasks<T>>::contains_key(&name), Error::<T>::TaskExists);
+            ensure!(when > <system::Module<T>>::block_number(), Error::<T>::InvalidTime);
+            ensure!(period.map_or(true, |period| !period.period.is_zero()), Error::<T>::InvalidPeriod);
+            let weight = Self::task_weight(&call);
+            ensure!(weight <= T::MaxTaskWeightPerBlock::get(), Error::<T>::TaskTooHeavy);
+            ensure!(Self::has_room(when, weight), Error::<T>::AgendaFull);
+
+            Self::add_to_agenda(when, name.clone(), weight);
+            <Tasks<T>>::insert(&name, Task { call: *call, origin, when, period });
+            Self::deposit_event(RawEvent::Scheduled(name, when));
+        }
+
+        /// Cancel the task `name`.
+        #[weight = SimpleDispatchInfo::FixedOperational(50_000)]
+        fn cancel(origin, name: Vec<u8>) {
+            let origin = T::ScheduleOrigin::ensure_origin(origin)?;
+            let task = Self::task(&name).ok_or(Error::<T>::UnknownTask)?;
+            ensure!(origin >= task.origin, BadOrigin);
+
+            <Agenda<T>>::mutate(task.when, |agenda| agenda.retain(|scheduled| scheduled != &name));
+            <AgendaWeight<T>>::mutate(task.when, |weight| {
+                *weight = weight.saturating_sub(Self::task_weight(&task.call))
+            });
+            <Tasks<T>>::remove(&name);
+            Self::deposit_event(RawEvent::Cancelled(name));
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Dispatch the call of `task`, and schedule its next run if it has one left.
+    fn run(now: T::BlockNumber, name: Vec<u8>, mut task: Task<<T as Trait>::Call, T::TaskOrigin, T::BlockNumber>) {
+        let ok = task.call.clone().dispatch(task.origin.clone().into()).is_ok();
+        Self::deposit_event(RawEvent::Dispatched(name.clone(), ok));
+
+        if let Some(Period { period, count }) = task.period.filter(|period| period.count > 0) {
+            task.period = Some(Period { period, count: count - 1 });
+            let weight = Self::task_weight(&task.call);
+            match Self::next_free_block(now + period, weight) {
+                Some(when) => {
+                    task.when = when;
+                    Self::add_to_agenda(when, name.clone(), weight);
+                    <Tasks<T>>::insert(name, task);
+                },
+                None => Self::deposit_event(RawEvent::Dropped(name)),
+            }
+        }
+    }
+
+    /// Weight of dispatching a task with `call`.
+    fn task_weight(call: &<T as Trait>::Call) -> Weight {
+        TASK_WEIGHT.saturating_add(call.get_dispatch_info().weight)
+    }
+
+    /// Whether the agenda of block `at` has room for a task of `weight`.
+    fn has_room(at: T::BlockNumber, weight: Weight) -> bool {
+        (<Agenda<T>>::decode_len(at).unwrap_or(0) as u32) < T::MaxTasksPerBlock::get() &&
+            Self::agenda_weight(at).saturating_add(weight) <= T::MaxTaskWeightPerBlock::get()
+    }
+
+    /// Add the task `name` of `weight` to the agenda of block `at`.
+    fn add_to_agenda(at: T::BlockNumber, name: Vec<u8>, weight: Weight) {
+        <Agenda<T>>::mutate(at, |agenda| agenda.push(name));
+        <AgendaWeight<T>>::mutate(at, |agenda_weight| *agenda_weight = agenda_weight.saturating_add(weight));
+    }
+
+    /// The first block from `at`, and among the next `MAX_TASK_SEARCH`, whose agenda has room for
+    /// a task of `weight`.
+    fn next_free_block(mut at: T::BlockNumber, weight: Weight) -> Option<T::BlockNumber> {
+        for _ in 0..MAX_TASK_SEARCH {
+            if Self::has_room(at, weight) {
+                return Some(at);
+            }
+            at += One::one();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_support::{
+        assert_noop, impl_outer_dispatch, impl_outer_origin, parameter_types,
+    };
+    use frame_system::RawOrigin;
+    use sp_core::H256;
+    use std::cell::RefCell;
+    use sp_runtime::{
+        testing::Header,
+        traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+        Perbill,
+    };
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    impl_outer_dispatch! {
+        pub enum Call for Test where origin: Origin {
+            pallet_balances::Balances,
+        }
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: Weight = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+        pub const ExistentialDeposit: u64 = 1;
+        pub const MaxTasksPerBlock: u32 = 2;
+    }
+
+    thread_local! {
+        static MAX_TASK_WEIGHT_PER_BLOCK: RefCell<Weight> = RefCell::new(1_000_000);
+    }
+
+    pub struct MaxTaskWeightPerBlock;
+
+    impl Get<Weight> for MaxTaskWeightPerBlock {
+        fn get() -> Weight {
+            MAX_TASK_WEIGHT_PER_BLOCK.with(|weight| *weight.borrow())
+        }
+    }
+
+    impl frame_system::Trait for Test {
+        type AccountId = u64;
+        type Call = ();
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Header = Header;
+        type Event = ();
+        type Origin = Origin;
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type MaximumBlockLength = MaximumBlockLength;
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type Version = ();
+        type ModuleToIndex = ();
+        type AccountData = pallet_balances::AccountData<u64>;
+        type OnNewAccount = ();
+        type OnKilledAccount = ();
+    }
+
+    impl pallet_balances::Trait for Test {
+        type Balance = u64;
+        type DustRemoval = ();
+        type Event = ();
+        type ExistentialDeposit = ExistentialDeposit;
+        type AccountStore = System;
+    }
+
+    /// The council is played by account 100.
+    const COUNCIL: u64 = 100;
+
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, PartialOrd, Debug)]
+    pub enum TestOrigin {
+        Council,
+        Root,
+    }
+
+    impl From<TestOrigin> for Origin {
+        fn from(origin: TestOrigin) -> Origin {
+            match origin {
+                TestOrigin::Council => RawOrigin::Signed(COUNCIL).into(),
+                TestOrigin::Root => RawOrigin::Root.into(),
+            }
+        }
+    }
+
+    pub struct EnsureRootOrCouncil;
+
+    impl EnsureOrigin<Origin> for EnsureRootOrCouncil {
+        type Success = TestOrigin;
+
+        fn try_origin(o: Origin) -> Result<TestOrigin, Origin> {
+            o.into().and_then(|o| match o {
+                RawOrigin::Root => Ok(TestOrigin::Root),
+                RawOrigin::Signed(COUNCIL) => Ok(TestOrigin::Council),
+                o => Err(Origin::from(o)),
+            })
+        }
+    }
+
+    impl Trait for Test {
+        type Event = ();
+        type Call = Call;
+        type TaskOrigin = TestOrigin;
+        type ScheduleOrigin = EnsureRootOrCouncil;
+        type MaxTasksPerBlock = MaxTasksPerBlock;
+        type MaxTaskWeightPerBlock = MaxTaskWeightPerBlock;
+    }
+
+    type System = frame_system::Module<Test>;
+    type Balances = pallet_balances::Module<Test>;
+    type Scheduler = Module<Test>;
+
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        pallet_balances::GenesisConfig::<Test> {
+            balances: vec![(1, 10), (COUNCIL, 10)],
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    /// A call only root can dispatch.
+    fn set_balance(who: u64, free: u64) -> Box<Call> {
+        Box::new(Call::Balances(pallet_balances::Call::set_balance(who, free, 0)))
+    }
+
+    fn run_to_block(n: u64) {
+        while System::block_number() < n {
+            System::set_block_number(System::block_number() + 1);
+            Scheduler::on_initialize(System::block_number());
+        }
+    }
+
+    #[test]
+    fn test_tasks_run_with_their_origin() {
+        new_test_ext().execute_with(|| {
+            let council = Origin::signed(COUNCIL);
+            assert!(Scheduler::schedule(Origin::ROOT, b"root".to_vec(), 2, None, set_balance(1, 20)).is_ok());
+            assert!(Scheduler::schedule(council, b"council".to_vec(), 2, None, set_balance(1, 30)).is_ok());
+            assert_noop!(
+                Scheduler::schedule(Origin::signed(1), b"other".to_vec(), 2, None, set_balance(1, 40)),
+                BadOrigin
+            );
+
+            run_to_block(1);
+            assert_eq!(Balances::free_balance(&1), 10);
+            // the council can't set balances, scheduled or not.
+            run_to_block(2);
+            assert_eq!(Balances::free_balance(&1), 20);
+            assert!(Scheduler::task(b"root".to_vec()).is_none());
+            assert!(Scheduler::agenda(2).is_empty());
+        });
+    }
+
+    #[test]
+    fn test_periodic_tasks() {
+        new_test_ext().execute_with(|| {
+            let period = Some(Period { period: 3, count: 2 });
+            assert!(Scheduler::schedule(Origin::ROOT, b"task".to_vec(), 1, period, set_balance(1, 20)).is_ok());
+            assert!(Scheduler::schedule(Origin::ROOT, b"a".to_vec(), 4, None, set_balance(2, 20)).is_ok());
+            assert!(Scheduler::schedule(Origin::ROOT, b"b".to_vec(), 4, None, set_balance(2, 20)).is_ok());
+
+            run_to_block(1);
+            assert_eq!(Balances::free_balance(&1), 20);
+            assert!(Balances::set_balance(Origin::ROOT, 1, 10, 0).is_ok());
+
+            // the agenda of block 4 is full.
+            run_to_block(4);
+            assert_eq!(Balances::free_balance(&1), 10);
+            run_to_block(5);
+            assert_eq!(Balances::free_balance(&1), 20);
+            assert_eq!(Scheduler::task(b"task".to_vec()).unwrap().when, 8);
+
+            run_to_block(8);
+            assert!(Scheduler::task(b"task".to_vec()).is_none());
+        });
+    }
+
+    #[test]
+    fn test_schedule_checks() {
+        new_test_ext().execute_with(|| {
+            run_to_block(2);
+            assert_noop!(
+                Scheduler::schedule(Origin::ROOT, b"task".to_vec(), 2, None, set_balance(1, 20)),
+                Error::<Test>::InvalidTime
+            );
+            assert_noop!(
+                Scheduler::schedule(Origin::ROOT, vec![0; 33], 3, None, set_balance(1, 20)),
+                Error::<Test>::NameTooLong
+            );
+            assert_noop!(
+                Scheduler::schedule(
+                    Origin::ROOT, b"task".to_vec(), 3, Some(Period { period: 0, count: 1 }), set_balance(1, 20)
+                ),
+                Error::<Test>::InvalidPeriod
+            );
+            assert!(Scheduler::schedule(Origin::ROOT, b"task".to_vec(), 3, None, set_balance(1, 20)).is_ok());
+            assert_noop!(
+                Scheduler::schedule(Origin::ROOT, b"task".to_vec(), 4, None, set_balance(1, 20)),
+                Error::<Test>::TaskExists
+            );
+            assert!(Scheduler::schedule(Origin::ROOT, b"other".to_vec(), 3, None, set_balance(1, 20)).is_ok());
+            assert_noop!(
+                Scheduler::schedule(Origin::ROOT, b"third".to_vec(), 3, None, set_balance(1, 20)),
+                Error::<Test>::AgendaFull
+            );
+        });
+    }
+
+    #[test]
+    fn test_tasks_fit_the_weight_of_a_block() {
+        new_test_ext().execute_with(|| {
+            let weight = Scheduler::task_weight(&set_balance(1, 20));
+            // room for a single task per block.
+            MAX_TASK_WEIGHT_PER_BLOCK.with(|max| *max.borrow_mut() = weight * 3 / 2);
+
+            let period = Some(Period { period: 1, count: 1 });
+            assert!(Scheduler::schedule(Origin::ROOT, b"task".to_vec(), 1, period, set_balance(1, 20)).is_ok());
+            assert!(Scheduler::schedule(Origin::ROOT, b"a".to_vec(), 2, None, set_balance(2, 20)).is_ok());
+            assert_noop!(
+                Scheduler::schedule(Origin::ROOT, b"b".to_vec(), 2, None, set_balance(2, 20)),
+                Error::<Test>::AgendaFull
+            );
+            assert_eq!(Scheduler::agenda_weight(2), weight);
+
+            // the repetition moves on to the next block with room left.
+            run_to_block(1);
+            assert_eq!(Scheduler::task(b"task".to_vec()).unwrap().when, 3);
+            assert!(Scheduler::cancel(Origin::ROOT, b"task".to_vec()).is_ok());
+            assert_eq!(Scheduler::agenda_weight(3), 0);
+
+            MAX_TASK_WEIGHT_PER_BLOCK.with(|max| *max.borrow_mut() = weight - 1);
+            assert_noop!(
+                Scheduler::schedule(Origin::ROOT, b"b".to_vec(), 4, None, set_balance(2, 20)),
+                Error::<Test>::TaskTooHeavy
+            );
+        });
+    }
+
+    #[test]
+    fn test_periodic_tasks_without_room_are_dropped() {
+        new_test_ext().execute_with(|| {
+            let period = Some(Period { period: 1, count: 1 });
+            assert!(Scheduler::schedule(Origin::ROOT, b"task".to_vec(), 1, period, set_balance(1, 20)).is_ok());
+            for at in 2..2 + u64::from(MAX_TASK_SEARCH) {
+                Scheduler::add_to_agenda(at, b"a".to_vec(), 0);
+                Scheduler::add_to_agenda(at, b"b".to_vec(), 0);
+            }
+
+            run_to_block(1);
+            assert_eq!(Balances::free_balance(&1), 20);
+            assert!(Scheduler::task(b"task".to_vec()).is_none());
+        });
+    }
+
+    #[test]
+    fn test_cancel_needs_as_much_power() {
+        new_test_ext().execute_with(|| {
+            let council = || Origin::signed(COUNCIL);
+            assert!(Scheduler::schedule(Origin::ROOT, b"root".to_vec(), 2, None, set_balance(1, 20)).is_ok());
+            assert!(Scheduler::schedule(council(), b"council".to_vec(), 2, None, set_balance(1, 30)).is_ok());
+
+            assert_noop!(Scheduler::cancel(council(), b"root".to_vec()), BadOrigin);
+            assert_noop!(Scheduler::cancel(council(), b"none".to_vec()), Error::<Test>::UnknownTask);
+            assert!(Scheduler::cancel(council(), b"council".to_vec()).is_ok());
+            assert!(Scheduler::cancel(Origin::ROOT, b"root".to_vec()).is_ok());
+            assert!(Scheduler::agenda(2).is_empty());
+
+            run_to_block(2);
+            assert_eq!(Balances::free_balance(&1), 10);
+        });
+    }
+}
@@ -0,0 +1,765 @@
+//! Staking rewards paid as shielded notes.
+//!
+//! A staker registers a Sapling payment address as the destination of its rewards. The staking
+//! pallet still pays rewards to a public balance: at every session change the reward paid to the
+//! payee account of a registered stash is taken out of circulation again and minted as a Sapling
+//! note of the same value. Staking pays through `RecordPayouts`, which records what it deposits
+//! to the payee accounts, so nothing else they receive is shielded. Rewards staked straight into
+//! the bond can't be taken back, so registered stashes should have their rewards paid to the
+//! stash or the controller. While shielding is paused in `nfts`, rewards stay public.
+//!
+//! The note of a reward is deterministic: its `rseed` is derived from the index of the reward, so
+//! the runtime can compute the note commitment without a proof. Validators prove the output and
+//! encrypt the note for the recipient off chain, and their offchain worker submits the output so
+//! wallets find the note by trial decryption like any other. If no valid output is submitted
+//! within `RewardProofTimeout` blocks, the note commitment is minted on its own and the recipient
+//! rebuilds the note from the reward index.
+
+use codec::{Decode, Encode};
+use frame_support::{
+    decl_error, decl_event, decl_module, decl_storage, ensure,
+    storage::IterableStorageMap,
+    traits::{
+        Currency, ExistenceRequirement, Get, Imbalance, LockIdentifier, LockableCurrency,
+        SignedImbalance, WithdrawReason, WithdrawReasons,
+    },
+    weights::{SimpleDispatchInfo, Weight},
+};
+use frame_system::{self as system, ensure_none, ensure_signed, offchain::SubmitUnsignedTransaction};
+use pallet_staking::RewardDestination;
+use sp_core::offchain::StorageKind;
+use sp_runtime::{
+    DispatchError, DispatchResult,
+    traits::{SaturatedConversion, Saturating, Zero},
+    transaction_validity::{
+        InvalidTransaction, TransactionPriority, TransactionValidity, ValidTransaction,
+    },
+};
+use sp_staking::SessionIndex;
+use sp_std::{marker::PhantomData, vec::Vec};
+use crate::host_functions::shielded_crypto;
+use crate::nfts::{self, PauseKind, SaplingOutputDescription};
+
+type BalanceOf<T> =
+    <<T as nfts::Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+
+/// Maximum number of pending rewards the offchain worker submits outputs for per block.
+const MAX_SUBMISSIONS_PER_BLOCK: u64 = 16;
+/// Maximum number of pending rewards looked at for minting without an output per block.
+pub const MAX_MINTS_PER_BLOCK: u64 = 16;
+/// Weight of minting the note of a reward without an output.
+pub const MINT_WEIGHT: Weight = 200_000;
+
+/// The accounts staking rewards are paid to.
+pub trait RewardPayee<AccountId> {
+    /// The stash controlled by `controller`, if it is bonded.
+    fn stash_of(controller: &AccountId) -> Option<AccountId>;
+
+    /// The account whose free balance receives the rewards of `stash`, if any.
+    fn reward_account(stash: &AccountId) -> Option<AccountId>;
+}
+
+impl<T: pallet_staking::Trait> RewardPayee<T::AccountId> for pallet_staking::Module<T> {
+    fn stash_of(controller: &T::AccountId) -> Option<T::AccountId> {
+        Self::ledger(controller).map(|ledger| ledger.stash)
+    }
+
+    fn reward_account(stash: &T::AccountId) -> Option<T::AccountId> {
+        match Self::payee(stash) {
+            RewardDestination::Stash => Some(stash.clone()),
+            RewardDestination::Controller => Self::bonded(stash),
+            RewardDestination::Staked => None,
+        }
+    }
+}
+
+/// A reward waiting for the output of its note.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct PendingReward<AccountId, BlockNumber> {
+    pub stash: AccountId,
+    /// Raw payment address of the note.
+    pub address: Vec<u8>,
+    pub value: u64,
+    pub rseed: [u8; 32],
+    pub note_commitment: [u8; 32],
+    pub created_at: BlockNumber,
+}
+
+/// The note of a pending reward, as needed to prove its output.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct RewardNote {
+    pub index: u64,
+    pub address: Vec<u8>,
+    pub value: u64,
+    pub rseed: [u8; 32],
+}
+
+/// The `rseed` of the note of reward `index`.
+pub fn reward_rseed(index: u64) -> [u8; 32] {
+    sp_io::hashing::blake2_256(&(b"shielded_rewards/rseed", index).encode())
+}
+
+/// The commitment of the note of `value` to the raw payment `address` with `rseed`, under the
+/// ZIP 212 rules.
+pub fn note_commitment(address: &[u8], value: u64, rseed: [u8; 32]) -> Option<[u8; 32]> {
+    shielded_crypto::sapling_note_commitment(address, value, &rseed)
+}
+
+/// Key of the output of reward `index` in the persistent offchain storage, where the node puts
+/// the outputs it has proven.
+pub fn reward_output_key(index: u64) -> Vec<u8> {
+    (b"shielded_rewards/output", index).encode()
+}
+
+pub trait Trait: nfts::Trait {
+    type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+
+    /// A dispatchable call type.
+    type Call: From<Call<Self>>;
+
+    /// Submits the outputs of pending rewards.
+    type SubmitTransaction: SubmitUnsignedTransaction<Self, <Self as Trait>::Call>;
+
+    /// The staking accounts rewards are paid to.
+    type Staking: RewardPayee<Self::AccountId>;
+
+    /// Number of blocks validators have to submit the output of a reward.
+    type RewardProofTimeout: Get<Self::BlockNumber>;
+
+    /// Maximum number of stashes with a shielded reward destination.
+    type MaxShieldedPayees: Get<u32>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as ShieldedRewards {
+        /// Raw payment address the rewards of a stash are paid to.
+        Payees get(fn payee): map hasher(blake2_128_concat) T::AccountId => Option<Vec<u8>>;
+
+        /// Number of registered stashes.
+        PayeeCount get(fn payee_count): u32;
+
+        /// Rewards waiting for the output of their note.
+        PendingRewards get(fn pending_reward):
+            map hasher(twox_64_concat) u64 => Option<PendingReward<T::AccountId, T::BlockNumber>>;
+
+        /// Index of the next reward.
+        NextReward get(fn next_reward): u64;
+
+        /// First reward that may still be pending.
+        PendingCursor get(fn pending_cursor): u64;
+
+        /// Rewards paid by staking to the payee accounts of registered stashes while a session
+        /// starts. Only the accounts watched by `ShieldRewards` have an entry.
+        RecordedPayouts: map hasher(blake2_128_concat) T::AccountId => Option<BalanceOf<T>>;
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where <T as frame_system::Trait>::AccountId {
+        /// The rewards of a stash are paid to a payment address.
+        ShieldedPayeeSet(AccountId),
+        /// The rewards of a stash are paid publicly again.
+        ShieldedPayeeCleared(AccountId),
+        /// A reward of the given value is waiting for the output of its note.
+        RewardPending(u64, AccountId, u64),
+        /// The output of the note of a reward has been minted.
+        RewardShielded(u64, SaplingOutputDescription),
+        /// The note of a reward has been minted without an output, with the given commitment.
+        RewardMinted(u64, [u8; 32]),
+    }
+);
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// The sender is not the controller of a bonded stash.
+        NotController,
+        /// The payment address is not valid.
+        InvalidAddress,
+        /// Too many stashes have a shielded reward destination.
+        TooManyPayees,
+        /// The stash has no shielded reward destination.
+        NoPayee,
+        /// The reward is not pending.
+        UnknownReward,
+        /// The output does not commit to the note of the reward or its proof is invalid.
+        InvalidOutput,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        #[weight = SimpleDispatchInfo::FixedOperational(
+            MINT_WEIGHT.saturating_mul(MAX_MINTS_PER_BLOCK as Weight)
+        )]
+        fn on_initialize(now: T::BlockNumber) {
+            Self::mint_expired(now);
+        }
+
+        fn offchain_worker(_now: T::BlockNumber) {
+            if sp_io::offchain::is_validator() {
+                Self::submit_outputs();
+            }
+        }
+
+        /// Pay the rewards of the stash controlled by the sender to a Sapling payment address.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn set_shielded_payee(origin, address: Vec<u8>) {
+            let controller = ensure_signed(origin)?;
+            let stash = T::Staking::stash_of(&controller).ok_or(Error::<T>::NotController)?;
+            ensure!(note_commitment(&address, 0, [0u8; 32]).is_some(), Error::<T>::InvalidAddress);
+
+            if !<Payees<T>>::contains_key(&stash) {
+                let count = Self::payee_count();
+                ensure!(count < T::MaxShieldedPayees::get(), Error::<T>::TooManyPayees);
+                PayeeCount::put(count + 1);
+            }
+            <Payees<T>>::insert(&stash, address);
+            Self::deposit_event(RawEvent::ShieldedPayeeSet(stash));
+        }
+
+        /// Pay the rewards of the stash controlled by the sender publicly again.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn clear_shielded_payee(origin) {
+            let controller = ensure_signed(origin)?;
+            let stash = T::Staking::stash_of(&controller).ok_or(Error::<T>::NotController)?;
+            ensure!(<Payees<T>>::contains_key(&stash), Error::<T>::NoPayee);
+
+            <Payees<T>>::remove(&stash);
+            PayeeCount::mutate(|count| *count = count.saturating_sub(1));
+            Self::deposit_event(RawEvent::ShieldedPayeeCleared(stash));
+        }
+
+        /// Mint the note of reward `index` with its proven and encrypted `output`.
+        ///
+        /// Submitted unsigned by the offchain workers of validators.
+        #[weight = SimpleDispatchInfo::FixedOperational(2_000_000)]
+        fn submit_reward_output(origin, index: u64, output: SaplingOutputDescription) {
+            ensure_none(origin)?;
+            let reward = Self::checked_reward(index, &output)?;
+
+            <nfts::Module<T>>::mint_sapling_note(reward.note_commitment, reward.value)?;
+            <PendingRewards<T>>::remove(index);
+            Self::deposit_event(RawEvent::RewardShielded(index, output));
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// The notes of the pending rewards, oldest first.
+    pub fn pending_notes() -> Vec<RewardNote> {
+        (Self::pending_cursor()..Self::next_reward())
+            .filter_map(|index| Self::pending_reward(index).map(|reward| RewardNote {
+                index,
+                address: reward.address,
+                value: reward.value,
+                rseed: reward.rseed,
+            }))
+            .collect()
+    }
+
+    /// The pending reward `index`, if `output` is a valid output of its note.
+    fn checked_reward(
+        index: u64,
+        output: &SaplingOutputDescription,
+    ) -> Result<PendingReward<T::AccountId, T::BlockNumber>, Error<T>> {
+        let reward = Self::pending_reward(index).ok_or(Error::<T>::UnknownReward)?;
+        ensure!(
+            output.note_commitment() == reward.note_commitment &&
+                <nfts::Module<T>>::verify_output(output),
+            Error::<T>::InvalidOutput
+        );
+        Ok(reward)
+    }
+
+    /// Take `reward`, just paid to the payee `account` of `stash`, out of circulation and make it
+    /// a pending shielded reward to `address`.
+    fn shield_reward(stash: T::AccountId, account: &T::AccountId, address: Vec<u8>, reward: BalanceOf<T>) {
+        let value = reward.saturated_into::<u64>();
+        let index = Self::next_reward();
+        let rseed = reward_rseed(index);
+        let note_commitment = match note_commitment(&address, value, rseed) {
+            Some(note_commitment) => note_commitment,
+            None => return,
+        };
+        let withdrawn = T::Currency::withdraw(
+            account,
+            value.saturated_into(),
+            WithdrawReason::Transfer.into(),
+            ExistenceRequirement::KeepAlive,
+        );
+        if withdrawn.is_err() {
+            return;
+        }
+
+        NextReward::put(index + 1);
+        <PendingRewards<T>>::insert(index, PendingReward {
+            stash: stash.clone(),
+            address,
+            value,
+            rseed,
+            note_commitment,
+            created_at: <system::Module<T>>::block_number(),
+        });
+        Self::deposit_event(RawEvent::RewardPending(index, stash, value));
+    }
+
+    /// Mint the notes of the rewards pending for `RewardProofTimeout` blocks without an output,
+    /// looking at `MAX_MINTS_PER_BLOCK` rewards at most.
+    fn mint_expired(now: T::BlockNumber) {
+        let timeout = T::RewardProofTimeout::get();
+        let mut cursor = Self::pending_cursor();
+        let end = Self::next_reward().min(cursor.saturating_add(MAX_MINTS_PER_BLOCK));
+        while cursor < end {
+            if let Some(reward) = Self::pending_reward(cursor) {
+                if reward.created_at.saturating_add(timeout) > now {
+                    break;
+                }
+                // a pause, a limit or a full tree fails every later mint as well, keep the reward
+                // pending.
+                if <nfts::Module<T>>::mint_sapling_note(reward.note_commitment, reward.value).is_err() {
+                    break;
+                }
+                <PendingRewards<T>>::remove(cursor);
+                Self::deposit_event(RawEvent::RewardMinted(cursor, reward.note_commitment));
+            }
+            cursor += 1;
+        }
+        PendingCursor::put(cursor);
+    }
+
+    /// Submit the outputs the node has proven for pending rewards.
+    fn submit_outputs() {
+        let next = Self::next_reward();
+        let cursor = Self::pending_cursor();
+        for index in cursor..next.min(cursor.saturating_add(MAX_SUBMISSIONS_PER_BLOCK)) {
+            if !<PendingRewards<T>>::contains_key(index) {
+                continue;
+            }
+            let output = sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &reward_output_key(index))
+                .and_then(|output| SaplingOutputDescription::decode(&mut &output[..]).ok());
+            if let Some(output) = output {
+                let call = Call::submit_reward_output(index, output);
+                if T::SubmitTransaction::submit_unsigned(call).is_err() {
+                    sp_runtime::print("Failed to submit the output of a shielded reward");
+                }
+            }
+        }
+    }
+}
+
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+    type Call = Call<T>;
+
+    fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+        if let Call::submit_reward_output(index, output) = call {
+            if Self::checked_reward(*index, output).is_err() {
+                return InvalidTransaction::BadProof.into();
+            }
+            // the mint would fail.
+            if <nfts::Module<T>>::is_paused(PauseKind::Shield) {
+                return InvalidTransaction::Call.into();
+            }
+            Ok(ValidTransaction {
+                priority: TransactionPriority::max_value(),
+                requires: vec![],
+                provides: vec![(b"shielded_rewards", index).encode()],
+                longevity: T::RewardProofTimeout::get().saturated_into::<u64>(),
+                propagate: true,
+            })
+        } else {
+            InvalidTransaction::Call.into()
+        }
+    }
+}
+
+/// A session manager shielding the rewards `Inner` pays to stashes with a shielded reward
+/// destination. Staking must pay through `RecordPayouts`, or no reward is shielded.
+pub struct ShieldRewards<T, Inner>(PhantomData<(T, Inner)>);
+
+impl<T: Trait, Inner: pallet_session::SessionManager<T::AccountId>> pallet_session::SessionManager<T::AccountId>
+    for ShieldRewards<T, Inner>
+{
+    fn new_session(new_index: SessionIndex) -> Option<Vec<T::AccountId>> {
+        if <nfts::Module<T>>::is_paused(PauseKind::Shield) {
+            return Inner::new_session(new_index);
+        }
+
+        let payees = <Payees<T>>::iter()
+            .filter_map(|(stash, address)| Some((stash.clone(), T::Staking::reward_account(&stash)?, address)))
+            .collect::<Vec<_>>();
+        for (_, account, _) in &payees {
+            <RecordedPayouts<T>>::insert(account, BalanceOf::<T>::zero());
+        }
+
+        let validators = Inner::new_session(new_index);
+
+        for (stash, account, address) in payees {
+            // taken, so that the rewards of an account paid for several stashes are shielded once.
+            let reward = <RecordedPayouts<T>>::take(&account).unwrap_or_else(Zero::zero);
+            if !reward.is_zero() {
+                <Module<T>>::shield_reward(stash, &account, address, reward);
+            }
+        }
+        validators
+    }
+
+    fn end_session(end_index: SessionIndex) {
+        Inner::end_session(end_index)
+    }
+}
+
+/// The currency of staking, `C`, recording the rewards staking deposits to the accounts watched
+/// by `ShieldRewards`.
+pub struct RecordPayouts<T, C>(PhantomData<(T, C)>);
+
+impl<T: Trait, C> RecordPayouts<T, C> {
+    fn record(who: &T::AccountId, amount: BalanceOf<T>) {
+        if <RecordedPayouts<T>>::contains_key(who) {
+            <RecordedPayouts<T>>::mutate(who, |recorded| {
+                *recorded = recorded.map(|recorded| recorded.saturating_add(amount));
+            });
+        }
+    }
+}
+
+impl<T, C> Currency<T::AccountId> for RecordPayouts<T, C> where
+    T: Trait,
+    C: Currency<T::AccountId, Balance = BalanceOf<T>>,
+{
+    type Balance = C::Balance;
+    type PositiveImbalance = C::PositiveImbalance;
+    type NegativeImbalance = C::NegativeImbalance;
+
+    fn total_balance(who: &T::AccountId) -> Self::Balance {
+        C::total_balance(who)
+    }
+
+    fn can_slash(who: &T::AccountId, value: Self::Balance) -> bool {
+        C::can_slash(who, value)
+    }
+
+    fn total_issuance() -> Self::Balance {
+        C::total_issuance()
+    }
+
+    fn minimum_balance() -> Self::Balance {
+        C::minimum_balance()
+    }
+
+    fn burn(amount: Self::Balance) -> Self::PositiveImbalance {
+        C::burn(amount)
+    }
+
+    fn issue(amount: Self::Balance) -> Self::NegativeImbalance {
+        C::issue(amount)
+    }
+
+    fn free_balance(who: &T::AccountId) -> Self::Balance {
+        C::free_balance(who)
+    }
+
+    fn ensure_can_withdraw(
+        who: &T::AccountId,
+        amount: Self::Balance,
+        reasons: WithdrawReasons,
+        new_balance: Self::Balance,
+    ) -> DispatchResult {
+        C::ensure_can_withdraw(who, amount, reasons, new_balance)
+    }
+
+    fn transfer(
+        source: &T::AccountId,
+        dest: &T::AccountId,
+        value: Self::Balance,
+        existence_requirement: ExistenceRequirement,
+    ) -> DispatchResult {
+        C::transfer(source, dest, value, existence_requirement)
+    }
+
+    fn slash(who: &T::AccountId, value: Self::Balance) -> (Self::NegativeImbalance, Self::Balance) {
+        C::slash(who, value)
+    }
+
+    fn deposit_into_existing(
+        who: &T::AccountId,
+        value: Self::Balance,
+    ) -> Result<Self::PositiveImbalance, DispatchError> {
+        let imbalance = C::deposit_into_existing(who, value)?;
+        Self::record(who, imbalance.peek());
+        Ok(imbalance)
+    }
+
+    fn deposit_creating(who: &T::AccountId, value: Self::Balance) -> Self::PositiveImbalance {
+        let imbalance = C::deposit_creating(who, value);
+        Self::record(who, imbalance.peek());
+        imbalance
+    }
+
+    fn withdraw(
+        who: &T::AccountId,
+        value: Self::Balance,
+        reasons: WithdrawReasons,
+        liveness: ExistenceRequirement,
+    ) -> Result<Self::NegativeImbalance, DispatchError> {
+        C::withdraw(who, value, reasons, liveness)
+    }
+
+    fn make_free_balance_be(
+        who: &T::AccountId,
+        balance: Self::Balance,
+    ) -> SignedImbalance<Self::Balance, Self::PositiveImbalance> {
+        C::make_free_balance_be(who, balance)
+    }
+}
+
+impl<T, C> LockableCurrency<T::AccountId> for RecordPayouts<T, C> where
+    T: Trait,
+    C: LockableCurrency<T::AccountId, Balance = BalanceOf<T>>,
+{
+    type Moment = C::Moment;
+
+    fn set_lock(id: LockIdentifier, who: &T::AccountId, amount: Self::Balance, reasons: WithdrawReasons) {
+        C::set_lock(id, who, amount, reasons)
+    }
+
+    fn extend_lock(id: LockIdentifier, who: &T::AccountId, amount: Self::Balance, reasons: WithdrawReasons) {
+        C::extend_lock(id, who, amount, reasons)
+    }
+
+    fn remove_lock(id: LockIdentifier, who: &T::AccountId) {
+        C::remove_lock(id, who)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_support::{assert_noop, impl_outer_origin, parameter_types, weights::Weight};
+    use frame_system::EnsureRoot;
+    use pallet_session::SessionManager;
+    use sp_core::H256;
+    use sp_runtime::{
+        testing::{Header, TestXt},
+        traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+        Perbill,
+    };
+    use zcash_primitives::zip32::{DiversifierIndex, ExtendedFullViewingKey, ExtendedSpendingKey};
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: Weight = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+        pub const ExistentialDeposit: u64 = 1;
+        pub const ByteDeposit: u64 = 1;
+        pub const MaxOutputLifetime: u64 = 100;
+        pub const MaxEvictionsPerBlock: u32 = 2;
+        pub const RentFraction: Perbill = Perbill::from_percent(50);
+        pub const UnshieldEraLength: u64 = 10;
+        pub const RootWindow: u64 = 5;
+        pub const NotePlaintextVersion: u8 = 2;
+        pub const RewardProofTimeout: u64 = 5;
+        pub const MaxShieldedPayees: u32 = 2;
+    }
+
+    impl frame_system::Trait for Test {
+        type AccountId = u64;
+        type Call = ();
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Header = Header;
+        type Event = ();
+        type Origin = Origin;
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type MaximumBlockLength = MaximumBlockLength;
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type Version = ();
+        type ModuleToIndex = ();
+        type AccountData = pallet_balances::AccountData<u64>;
+        type OnNewAccount = ();
+        type OnKilledAccount = ();
+    }
+
+    impl pallet_balances::Trait for Test {
+        type Balance = u64;
+        type DustRemoval = ();
+        type Event = ();
+        type ExistentialDeposit = ExistentialDeposit;
+        type AccountStore = System;
+    }
+
+    impl nfts::Trait for Test {
+        type Event = ();
+        type Currency = Balances;
+        type ByteDeposit = ByteDeposit;
+        type MaxOutputLifetime = MaxOutputLifetime;
+        type MaxEvictionsPerBlock = MaxEvictionsPerBlock;
+        type RentFraction = RentFraction;
+        type Slash = ();
+        type FeeHandler = ();
+        type UnshieldEraLength = UnshieldEraLength;
+        type RootWindow = RootWindow;
+        type NotePlaintextVersion = NotePlaintextVersion;
+        type PauseOrigin = EnsureRoot<u64>;
+    }
+
+    /// Stash `n` is controlled by `n + 100` and paid to itself.
+    pub struct TestStaking;
+
+    impl RewardPayee<u64> for TestStaking {
+        fn stash_of(controller: &u64) -> Option<u64> {
+            controller.checked_sub(100)
+        }
+
+        fn reward_account(stash: &u64) -> Option<u64> {
+            Some(*stash)
+        }
+    }
+
+    /// Pays a reward of 50 to stash 1 and 70 to stash 2 at every session. Stash 1 also receives
+    /// 5 from outside staking.
+    pub struct PayRewards;
+
+    impl pallet_session::SessionManager<u64> for PayRewards {
+        fn new_session(_: SessionIndex) -> Option<Vec<u64>> {
+            let _ = RecordPayouts::<Test, Balances>::deposit_creating(&1, 50);
+            let _ = RecordPayouts::<Test, Balances>::deposit_into_existing(&2, 70);
+            let _ = Balances::deposit_creating(&1, 5);
+            None
+        }
+
+        fn end_session(_: SessionIndex) {}
+    }
+
+    impl Trait for Test {
+        type Event = ();
+        type Call = Call<Test>;
+        type SubmitTransaction = frame_system::offchain::TransactionSubmitter<(), Call<Test>, TestXt<Call<Test>, ()>>;
+        type Staking = TestStaking;
+        type RewardProofTimeout = RewardProofTimeout;
+        type MaxShieldedPayees = MaxShieldedPayees;
+    }
+
+    type System = frame_system::Module<Test>;
+    type Balances = pallet_balances::Module<Test>;
+    type Nfts = nfts::Module<Test>;
+    type ShieldedRewards = Module<Test>;
+
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        pallet_balances::GenesisConfig::<Test> {
+            balances: vec![(1, 1_000), (2, 1_000)],
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    /// A valid raw payment address.
+    fn address() -> Vec<u8> {
+        let key = ExtendedFullViewingKey::from(&ExtendedSpendingKey::master(&[7u8; 32]));
+        let (_, address) = key.address(DiversifierIndex::new()).expect("a diversifier is valid; qed");
+        address.to_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_set_shielded_payee() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                ShieldedRewards::set_shielded_payee(Origin::signed(1), address()),
+                Error::<Test>::NotController
+            );
+            assert_noop!(
+                ShieldedRewards::set_shielded_payee(Origin::signed(101), vec![0u8; 10]),
+                Error::<Test>::InvalidAddress
+            );
+            assert!(ShieldedRewards::set_shielded_payee(Origin::signed(101), address()).is_ok());
+            assert!(ShieldedRewards::set_shielded_payee(Origin::signed(101), address()).is_ok());
+            assert!(ShieldedRewards::set_shielded_payee(Origin::signed(102), address()).is_ok());
+            assert_eq!(ShieldedRewards::payee_count(), 2);
+            assert_noop!(
+                ShieldedRewards::set_shielded_payee(Origin::signed(103), address()),
+                Error::<Test>::TooManyPayees
+            );
+
+            assert!(ShieldedRewards::clear_shielded_payee(Origin::signed(102)).is_ok());
+            assert_eq!(ShieldedRewards::payee(2), None);
+            assert_eq!(ShieldedRewards::payee_count(), 1);
+        });
+    }
+
+    #[test]
+    fn test_rewards_are_shielded_and_minted_after_timeout() {
+        new_test_ext().execute_with(|| {
+            System::set_block_number(1);
+            assert!(ShieldedRewards::set_shielded_payee(Origin::signed(101), address()).is_ok());
+
+            ShieldRewards::<Test, PayRewards>::new_session(1);
+            // the reward of stash 1 is taken out of circulation, stash 2 is paid publicly.
+            assert_eq!(Balances::free_balance(&1), 1_005);
+            assert_eq!(Balances::free_balance(&2), 1_070);
+            assert!(!<RecordedPayouts<Test>>::contains_key(&1));
+            let reward = ShieldedRewards::pending_reward(0).unwrap();
+            assert_eq!(reward.value, 50);
+            assert_eq!(reward.note_commitment, note_commitment(&address(), 50, reward_rseed(0)).unwrap());
+            assert_eq!(ShieldedRewards::pending_notes().len(), 1);
+
+            let root = Nfts::tree_root(nfts::ShieldedPool::Sapling);
+            ShieldedRewards::on_initialize(5);
+            assert!(ShieldedRewards::pending_reward(0).is_some());
+
+            ShieldedRewards::on_initialize(6);
+            assert!(ShieldedRewards::pending_reward(0).is_none());
+            assert_eq!(ShieldedRewards::pending_cursor(), 1);
+            assert_eq!(Nfts::pool_balance(nfts::ShieldedPool::Sapling), 50);
+            assert!(Nfts::tree_root(nfts::ShieldedPool::Sapling) != root);
+        });
+    }
+
+    #[test]
+    fn test_minting_is_bounded_and_paused_with_shielding() {
+        new_test_ext().execute_with(|| {
+            System::set_block_number(1);
+            assert!(ShieldedRewards::set_shielded_payee(Origin::signed(101), address()).is_ok());
+            for session in 0..MAX_MINTS_PER_BLOCK + 1 {
+                ShieldRewards::<Test, PayRewards>::new_session(session as SessionIndex);
+            }
+            assert_eq!(ShieldedRewards::next_reward(), MAX_MINTS_PER_BLOCK + 1);
+
+            assert!(Nfts::set_paused(Origin::ROOT, PauseKind::Shield, true).is_ok());
+            ShieldedRewards::on_initialize(6);
+            assert_eq!(ShieldedRewards::pending_cursor(), 0);
+            // rewards are left public while shielding is paused.
+            let balance = Balances::free_balance(&1);
+            ShieldRewards::<Test, PayRewards>::new_session(100);
+            assert_eq!(Balances::free_balance(&1), balance + 55);
+            assert_eq!(ShieldedRewards::next_reward(), MAX_MINTS_PER_BLOCK + 1);
+
+            assert!(Nfts::set_paused(Origin::ROOT, PauseKind::Shield, false).is_ok());
+            ShieldedRewards::on_initialize(6);
+            assert_eq!(ShieldedRewards::pending_cursor(), MAX_MINTS_PER_BLOCK);
+            assert!(ShieldedRewards::pending_reward(MAX_MINTS_PER_BLOCK).is_some());
+            ShieldedRewards::on_initialize(7);
+            assert_eq!(ShieldedRewards::pending_cursor(), MAX_MINTS_PER_BLOCK + 1);
+            assert_eq!(
+                Nfts::pool_balance(nfts::ShieldedPool::Sapling),
+                50 * (MAX_MINTS_PER_BLOCK + 1)
+            );
+        });
+    }
+}
@@ -0,0 +1,360 @@
+//! Vesting of locked balances.
+//!
+//! A vesting schedule locks part of the balance of an account and unlocks it over time, either
+//! linearly from its start over its duration, or all at once when its duration has passed, in a
+//! cliff. An account may have several schedules, whose locked amounts add up into a single
+//! balance lock. Schedules are created in the genesis or by `vested_transfer`.
+//!
+//! The lock is only updated when `vest` or `vest_other` is called, so funds that have vested
+//! stay locked until then.
+
+use codec::{Decode, Encode};
+use frame_support::{
+    decl_error, decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure,
+    traits::{
+        Currency, ExistenceRequirement, Get, LockIdentifier, LockableCurrency, WithdrawReason,
+        WithdrawReasons,
+    },
+    weights::SimpleDispatchInfo,
+};
+use frame_system::{self as system, ensure_signed};
+use sp_runtime::{
+    helpers_128bit::multiply_by_rational,
+    traits::{SaturatedConversion, Saturating, UniqueSaturatedFrom, UniqueSaturatedInto, Zero},
+};
+use sp_std::vec::Vec;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+
+/// Identifier of the balance lock of vesting schedules.
+const VESTING_ID: LockIdentifier = *b"vesting ";
+
+/// How a vesting schedule unlocks its balance.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum VestingKind {
+    /// Unlocks linearly from the start of the schedule until its end.
+    Linear,
+    /// Unlocks everything at the end of the schedule.
+    Cliff,
+}
+
+/// A vesting schedule.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct VestingSchedule<Balance, BlockNumber> {
+    /// Balance locked at the start of the schedule.
+    pub locked: Balance,
+    /// Block vesting starts at.
+    pub start: BlockNumber,
+    /// Number of blocks until everything is unlocked.
+    pub duration: BlockNumber,
+    /// How the balance unlocks.
+    pub kind: VestingKind,
+}
+
+impl<Balance, BlockNumber> VestingSchedule<Balance, BlockNumber> where
+    Balance: Copy + Zero + UniqueSaturatedInto<u128> + UniqueSaturatedFrom<u128>,
+    BlockNumber: Copy + PartialOrd + Saturating + UniqueSaturatedInto<u128>,
+{
+    /// Balance still locked at block `now`.
+    pub fn locked_at(&self, now: BlockNumber) -> Balance {
+        let end = self.start.saturating_add(self.duration);
+        if now >= end {
+            return Zero::zero();
+        }
+        match self.kind {
+            VestingKind::Cliff => self.locked,
+            VestingKind::Linear if now <= self.start => self.locked,
+            VestingKind::Linear => {
+                let remaining = end.saturating_sub(now).saturated_into::<u128>();
+                let duration = self.duration.saturated_into::<u128>();
+                // rounds down, so the last unit of balance unlocks at the end.
+                multiply_by_rational(self.locked.saturated_into::<u128>(), remaining, duration)
+                    .map(Balance::unique_saturated_from)
+                    .unwrap_or(self.locked)
+            },
+        }
+    }
+}
+
+pub trait Trait: frame_system::Trait {
+    type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+
+    /// The currency whose balances vest.
+    type Currency: LockableCurrency<Self::AccountId, Moment = Self::BlockNumber>;
+
+    /// Minimum balance of a vested transfer.
+    type MinVestedTransfer: Get<BalanceOf<Self>>;
+
+    /// Maximum number of schedules of an account.
+    type MaxVestingSchedules: Get<u32>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Vesting {
+        /// Vesting schedules of an account.
+        pub Vesting get(fn vesting):
+            map hasher(blake2_128_concat) T::AccountId => Vec<VestingSchedule<BalanceOf<T>, T::BlockNumber>>;
+    }
+    add_extra_genesis {
+        config(vesting): Vec<(T::AccountId, VestingSchedule<BalanceOf<T>, T::BlockNumber>)>;
+        build(|config: &GenesisConfig<T>| {
+            for (who, schedule) in &config.vesting {
+                <Vesting<T>>::mutate(who, |schedules| schedules.push(schedule.clone()));
+            }
+            for (who, _) in &config.vesting {
+                let _ = <Module<T>>::update_lock(who);
+            }
+        });
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        AccountId = <T as frame_system::Trait>::AccountId,
+        Balance = BalanceOf<T>,
+    {
+        /// The balance of an account still locked by its vesting schedules.
+        VestingUpdated(AccountId, Balance),
+        /// All vesting schedules of an account are over.
+        VestingCompleted(AccountId),
+    }
+);
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// The account has no vesting schedule.
+        NotVesting,
+        /// The vested balance is lower than `MinVestedTransfer`.
+        AmountLow,
+        /// The account already has `MaxVestingSchedules` schedules.
+        TooManySchedules,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        /// Unlock the vested balance of the sender.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn vest(origin) {
+            let who = ensure_signed(origin)?;
+            Self::update_lock(&who)?;
+        }
+
+        /// Unlock the vested balance of `target`.
+        #[weight = SimpleDispatchInfo::FixedNormal(50_000)]
+        fn vest_other(origin, target: T::AccountId) {
+            ensure_signed(origin)?;
+            Self::update_lock(&target)?;
+        }
+
+        /// Transfer `schedule.locked` to `target`, locked by `schedule`.
+        #[weight = SimpleDispatchInfo::FixedNormal(100_000)]
+        fn vested_transfer(
+            origin,
+            target: T::AccountId,
+            schedule: VestingSchedule<BalanceOf<T>, T::BlockNumber>,
+        ) {
+            let who = ensure_signed(origin)?;
+            ensure!(schedule.locked >= T::MinVestedTransfer::get(), Error::<T>::AmountLow);
+            ensure!(
+                (Self::vesting(&target).len() as u32) < T::MaxVestingSchedules::get(),
+                Error::<T>::TooManySchedules
+            );
+
+            T::Currency::transfer(&who, &target, schedule.locked, ExistenceRequirement::AllowDeath)?;
+            <Vesting<T>>::mutate(&target, |schedules| schedules.push(schedule));
+            Self::update_lock(&target)?;
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Balance of `who` still locked by its vesting schedules.
+    pub fn locked(who: &T::AccountId) -> BalanceOf<T> {
+        let now = <system::Module<T>>::block_number();
+        Self::vesting(who).iter()
+            .fold(Zero::zero(), |locked: BalanceOf<T>, schedule| locked.saturating_add(schedule.locked_at(now)))
+    }
+
+    /// Set the lock of `who` to the balance still locked by its schedules, and drop the
+    /// schedules that are over.
+    fn update_lock(who: &T::AccountId) -> DispatchResult {
+        let mut schedules = Self::vesting(who);
+        ensure!(!schedules.is_empty(), Error::<T>::NotVesting);
+
+        let now = <system::Module<T>>::block_number();
+        schedules.retain(|schedule| !schedule.locked_at(now).is_zero());
+        if schedules.is_empty() {
+            T::Currency::remove_lock(VESTING_ID, who);
+            <Vesting<T>>::remove(who);
+            Self::deposit_event(RawEvent::VestingCompleted(who.clone()));
+        } else {
+            let locked = schedules.iter()
+                .fold(Zero::zero(), |locked: BalanceOf<T>, schedule| locked.saturating_add(schedule.locked_at(now)));
+            T::Currency::set_lock(VESTING_ID, who, locked, WithdrawReasons::except(WithdrawReason::TransactionPayment));
+            <Vesting<T>>::insert(who, schedules);
+            Self::deposit_event(RawEvent::VestingUpdated(who.clone(), locked));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_support::{assert_noop, impl_outer_origin, parameter_types, weights::Weight};
+    use sp_core::H256;
+    use sp_runtime::{
+        testing::Header,
+        traits::{BlakeTwo256, IdentityLookup},
+        Perbill,
+    };
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: Weight = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+        pub const ExistentialDeposit: u64 = 1;
+        pub const MinVestedTransfer: u64 = 100;
+        pub const MaxVestingSchedules: u32 = 2;
+    }
+
+    impl frame_system::Trait for Test {
+        type AccountId = u64;
+        type Call = ();
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Header = Header;
+        type Event = ();
+        type Origin = Origin;
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type MaximumBlockLength = MaximumBlockLength;
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type Version = ();
+        type ModuleToIndex = ();
+        type AccountData = pallet_balances::AccountData<u64>;
+        type OnNewAccount = ();
+        type OnKilledAccount = ();
+    }
+
+    impl pallet_balances::Trait for Test {
+        type Balance = u64;
+        type DustRemoval = ();
+        type Event = ();
+        type ExistentialDeposit = ExistentialDeposit;
+        type AccountStore = System;
+    }
+
+    impl Trait for Test {
+        type Event = ();
+        type Currency = Balances;
+        type MinVestedTransfer = MinVestedTransfer;
+        type MaxVestingSchedules = MaxVestingSchedules;
+    }
+
+    type System = frame_system::Module<Test>;
+    type Balances = pallet_balances::Module<Test>;
+    type Vesting = Module<Test>;
+
+    fn linear(locked: u64, start: u64, duration: u64) -> VestingSchedule<u64, u64> {
+        VestingSchedule { locked, start, duration, kind: VestingKind::Linear }
+    }
+
+    fn cliff(locked: u64, start: u64, duration: u64) -> VestingSchedule<u64, u64> {
+        VestingSchedule { locked, start, duration, kind: VestingKind::Cliff }
+    }
+
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        pallet_balances::GenesisConfig::<Test> {
+            balances: vec![(1, 1_000), (2, 1_000), (3, 1_000)],
+        }.assimilate_storage(&mut t).unwrap();
+        GenesisConfig::<Test> {
+            vesting: vec![(1, linear(500, 10, 100)), (2, cliff(800, 0, 50))],
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    fn transfer(from: u64, to: u64, value: u64) -> DispatchResult {
+        Balances::transfer(Origin::signed(from), to, value)
+    }
+
+    #[test]
+    fn test_schedules_unlock_over_time() {
+        let schedule = linear(1_000, 10, 100);
+        assert_eq!(schedule.locked_at(0), 1_000);
+        assert_eq!(schedule.locked_at(10), 1_000);
+        assert_eq!(schedule.locked_at(35), 750);
+        assert_eq!(schedule.locked_at(109), 10);
+        assert_eq!(schedule.locked_at(110), 0);
+
+        let schedule = cliff(1_000, 10, 100);
+        assert_eq!(schedule.locked_at(109), 1_000);
+        assert_eq!(schedule.locked_at(110), 0);
+    }
+
+    #[test]
+    fn test_genesis_schedules_lock_balances() {
+        new_test_ext().execute_with(|| {
+            assert!(transfer(1, 3, 501).is_err());
+            assert!(transfer(1, 3, 500).is_ok());
+            assert!(transfer(2, 3, 201).is_err());
+
+            System::set_block_number(35);
+            assert!(Vesting::vest(Origin::signed(1)).is_ok());
+            assert_eq!(Vesting::locked(&1), 375);
+            assert!(transfer(1, 3, 125).is_ok());
+            assert!(transfer(1, 3, 1).is_err());
+
+            System::set_block_number(50);
+            assert!(Vesting::vest_other(Origin::signed(3), 2).is_ok());
+            assert!(Vesting::vesting(&2).is_empty());
+            assert!(transfer(2, 3, 1_000).is_ok());
+            assert_noop!(Vesting::vest(Origin::signed(2)), Error::<Test>::NotVesting);
+        });
+    }
+
+    #[test]
+    fn test_vested_transfer() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                Vesting::vested_transfer(Origin::signed(3), 4, linear(99, 0, 10)),
+                Error::<Test>::AmountLow
+            );
+            assert!(Vesting::vested_transfer(Origin::signed(3), 4, linear(200, 0, 10)).is_ok());
+            assert!(Vesting::vested_transfer(Origin::signed(3), 4, cliff(300, 0, 20)).is_ok());
+            assert_noop!(
+                Vesting::vested_transfer(Origin::signed(3), 4, cliff(100, 0, 20)),
+                Error::<Test>::TooManySchedules
+            );
+            assert_eq!(Balances::free_balance(&4), 500);
+            assert!(transfer(4, 3, 1).is_err());
+
+            System::set_block_number(10);
+            assert!(Vesting::vest(Origin::signed(4)).is_ok());
+            assert_eq!(Vesting::vesting(&4).len(), 1);
+            assert!(transfer(4, 3, 200).is_ok());
+            assert!(transfer(4, 3, 1).is_err());
+        });
+    }
+}
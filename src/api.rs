@@ -1,17 +1,40 @@
+use codec::{Decode, Encode};
 use jsonrpc_core::Result;
 use jsonrpc_derive::rpc;
-use node_primitives::{BlockNumber, Hash};
+use node_primitives::{AccountId, Balance, Block, BlockNumber, Hash};
+use parking_lot::Mutex;
+use rand::rngs::OsRng;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
+use futures::{FutureExt, TryFutureExt};
+use sc_client::{CallExecutor, Client, light::blockchain::future_header};
+use sc_client_api::light::{Fetcher, RemoteBlockchain, RemoteReadChildRequest};
+use serde::{Deserialize, Serialize};
+use sc_keystore::KeyStorePtr;
+use sp_core::{ed25519, sr25519, Bytes, H256};
 use sp_runtime::{generic::BlockId, traits::Block as BlockT};
 use std::sync::Arc;
+use std::time::Instant;
+use crate::relay::{self, RelaySender};
+use crate::sapling_keys::{self, SaplingNetwork};
+use zcash_primitives::zip32::{ExtendedFullViewingKey, ExtendedSpendingKey};
+use crate::frost::{
+    self, Identifier, PendingNonces, PublicKeyPackage, SignatureShare, SigningPackage,
+};
+use node_runtime::anchor::AnchorData;
+use node_runtime::commitment_tree::Frontier;
+use node_runtime::nfts::{self, ShieldedPool};
+use pallet_identity::{Data, Judgement, RegistrarIndex};
+pub use node_runtime::AnchorApi as AnchorRuntimeApi;
+pub use node_runtime::IdentityApi as IdentityRuntimeApi;
+pub use node_runtime::NftsApi as NftsRuntimeApi;
 
-//#[rpc]
-//pub trait AnchorApi {
-    ///// Returns an anchor given an anchor id from the runtime storage
-    //#[rpc(name = "anchor_getAnchorById")]
-    //fn get_anchor_by_id(&self, id: Hash) -> Result<AnchorData<Hash, BlockNumber>>;
-//}
+#[rpc]
+pub trait AnchorApi {
+    /// Returns an anchor given an anchor id from the runtime storage
+    #[rpc(name = "anchor_getAnchorById")]
+    fn get_anchor_by_id(&self, id: Hash) -> Result<AnchorData<Hash, BlockNumber>>;
+}
 
 /// A struct that implements the [`AnchorApi`].
 pub struct Anchor<C, P> {
@@ -29,23 +52,551 @@ impl<C, P> Anchor<C, P> {
     }
 }
 
-//impl<C, Block> AnchorApi for Anchor<C, Block>
-//where
-    //Block: BlockT,
-    //C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
-    //C::Api: AnchorRuntimeApi<Block>,
-//{
-    //fn get_anchor_by_id(&self, id: Hash) -> Result<AnchorData<Hash, BlockNumber>> {
-        //let api = self.client.runtime_api();
-        //let best = self.client.info().best_hash;
-        //let at = BlockId::hash(best);
-        //api.get_anchor_by_id(&at, id)
-            //.ok()
-            //.unwrap()
-            //.ok_or(jsonrpc_core::Error {
-                //code: jsonrpc_core::ErrorCode::InternalError,
-                //message: "Unable to find anchor".into(),
-                //data: Some(format!("{:?}", id).into()),
-            //})
-    //}
-//}
+impl<C, Block> AnchorApi for Anchor<C, Block>
+where
+    Block: BlockT,
+    C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+    C::Api: AnchorRuntimeApi<Block>,
+{
+    fn get_anchor_by_id(&self, id: Hash) -> Result<AnchorData<Hash, BlockNumber>> {
+        let api = self.client.runtime_api();
+        let best = self.client.info().best_hash;
+        let at = BlockId::hash(best);
+        api.get_anchor_by_id(&at, id)
+            .map_err(|e| jsonrpc_core::Error {
+                code: jsonrpc_core::ErrorCode::InternalError,
+                message: "Unable to query anchor".into(),
+                data: Some(format!("{:?}", e).into()),
+            })?
+            .ok_or(jsonrpc_core::Error {
+                code: jsonrpc_core::ErrorCode::InternalError,
+                message: "Unable to find anchor".into(),
+                data: Some(format!("{:?}", id).into()),
+            })
+    }
+}
+
+/// A field of an identity. Fields too large to be stored on chain are given by their hash.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IdentityField {
+    /// The field itself, lossily decoded as UTF-8.
+    Raw(String),
+    /// Hash of the field.
+    Hash(H256),
+}
+
+impl IdentityField {
+    fn from_data(data: &Data) -> Option<Self> {
+        match data {
+            Data::None => None,
+            Data::Raw(raw) => Some(IdentityField::Raw(String::from_utf8_lossy(raw).into_owned())),
+            Data::BlakeTwo256(hash) | Data::Sha256(hash) | Data::Keccak256(hash) | Data::ShaThree256(hash) =>
+                Some(IdentityField::Hash(H256::from(*hash))),
+        }
+    }
+}
+
+/// The judgement of a registrar on an identity.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IdentityJudgement {
+    /// The registrar hasn't judged the identity yet.
+    Unknown,
+    /// The registrar has been paid to judge the identity.
+    FeePaid,
+    Reasonable,
+    KnownGood,
+    OutOfDate,
+    LowQuality,
+    Erroneous,
+}
+
+impl From<&Judgement<Balance>> for IdentityJudgement {
+    fn from(judgement: &Judgement<Balance>) -> Self {
+        match judgement {
+            Judgement::Unknown => IdentityJudgement::Unknown,
+            Judgement::FeePaid(_) => IdentityJudgement::FeePaid,
+            Judgement::Reasonable => IdentityJudgement::Reasonable,
+            Judgement::KnownGood => IdentityJudgement::KnownGood,
+            Judgement::OutOfDate => IdentityJudgement::OutOfDate,
+            Judgement::LowQuality => IdentityJudgement::LowQuality,
+            Judgement::Erroneous => IdentityJudgement::Erroneous,
+        }
+    }
+}
+
+/// The identity of an account, as shown to explorers.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountIdentity {
+    pub display: Option<IdentityField>,
+    pub legal: Option<IdentityField>,
+    pub web: Option<IdentityField>,
+    pub email: Option<IdentityField>,
+    pub pgp_fingerprint: Option<Bytes>,
+    /// Judgements of registrars, by registrar index.
+    pub judgements: Vec<(RegistrarIndex, IdentityJudgement)>,
+}
+
+/// An identity registrar.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Registrar {
+    pub index: RegistrarIndex,
+    pub account: AccountId,
+    /// Fee of a judgement.
+    pub fee: Balance,
+}
+
+#[rpc]
+pub trait IdentityApi {
+    /// Returns the identity of `who`, at the best block unless `at` is given.
+    #[rpc(name = "identity_identityOf")]
+    fn identity_of(&self, who: AccountId, at: Option<Hash>) -> Result<Option<AccountIdentity>>;
+
+    /// Returns the identity registrars, at the best block unless `at` is given.
+    #[rpc(name = "identity_registrars")]
+    fn registrars(&self, at: Option<Hash>) -> Result<Vec<Registrar>>;
+}
+
+/// A struct that implements the [`IdentityApi`].
+pub struct Identity<C> {
+    client: Arc<C>,
+}
+
+impl<C> Identity<C> {
+    /// Create new `Identity` with the given reference to the client.
+    pub fn new(client: Arc<C>) -> Self {
+        Identity {
+            client,
+        }
+    }
+}
+
+fn identity_error(message: &str, e: impl std::fmt::Debug) -> jsonrpc_core::Error {
+    jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::InternalError,
+        message: message.into(),
+        data: Some(format!("{:?}", e).into()),
+    }
+}
+
+impl<C> IdentityApi for Identity<C>
+where
+    C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+    C::Api: IdentityRuntimeApi<Block>,
+{
+    fn identity_of(&self, who: AccountId, at: Option<Hash>) -> Result<Option<AccountIdentity>> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        let registration = self.client.runtime_api().identity_of(&at, who)
+            .map_err(|e| identity_error("Unable to query identity", e))?;
+        Ok(registration.map(|registration| AccountIdentity {
+            display: IdentityField::from_data(&registration.info.display),
+            legal: IdentityField::from_data(&registration.info.legal),
+            web: IdentityField::from_data(&registration.info.web),
+            email: IdentityField::from_data(&registration.info.email),
+            pgp_fingerprint: registration.info.pgp_fingerprint.map(|fingerprint| fingerprint.to_vec().into()),
+            judgements: registration.judgements.iter()
+                .map(|(index, judgement)| (*index, judgement.into()))
+                .collect(),
+        }))
+    }
+
+    fn registrars(&self, at: Option<Hash>) -> Result<Vec<Registrar>> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        let registrars = self.client.runtime_api().registrars(&at)
+            .map_err(|e| identity_error("Unable to query registrars", e))?;
+        // the slots of removed registrars are kept, so indices don't change.
+        Ok(registrars.into_iter().enumerate()
+            .filter_map(|(index, registrar)| registrar.map(|registrar| Registrar {
+                index: index as RegistrarIndex,
+                account: registrar.account,
+                fee: registrar.fee,
+            }))
+            .collect())
+    }
+}
+
+/// Proof of entries of the child trie of the shielded state, checkable against the state root
+/// of the header of block `at`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShieldedStateProof {
+    /// Block the proof was generated at.
+    pub at: Hash,
+    /// Trie nodes of the proof.
+    pub proof: Vec<Bytes>,
+}
+
+#[rpc]
+pub trait NftsApi {
+    /// Returns a proof of whether `nullifier` has been spent in `pool`, at the best block unless
+    /// `at` is given.
+    #[rpc(name = "nfts_nullifierProof")]
+    fn nullifier_proof(&self, pool: ShieldedPool, nullifier: H256, at: Option<Hash>) -> Result<ShieldedStateProof>;
+
+    /// Returns a proof of the tree frontier of `pool`, which holds the tree size and root, at the
+    /// best block unless `at` is given.
+    #[rpc(name = "nfts_treeProof")]
+    fn tree_proof(&self, pool: ShieldedPool, at: Option<Hash>) -> Result<ShieldedStateProof>;
+}
+
+/// A struct that implements the [`NftsApi`] for full nodes.
+pub struct Nfts<B, E, RA> {
+    client: Arc<Client<B, E, Block, RA>>,
+}
+
+impl<B, E, RA> Nfts<B, E, RA> {
+    /// Create new `Nfts` with the given reference to the client.
+    pub fn new(client: Arc<Client<B, E, Block, RA>>) -> Self {
+        Nfts {
+            client,
+        }
+    }
+}
+
+fn nfts_error(message: &str, e: impl std::fmt::Debug) -> jsonrpc_core::Error {
+    jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::InternalError,
+        message: message.into(),
+        data: Some(format!("{:?}", e).into()),
+    }
+}
+
+impl<B, E, RA> Nfts<B, E, RA> where
+    B: sc_client_api::backend::Backend<Block> + Send + Sync + 'static,
+    E: CallExecutor<Block> + Send + Sync + 'static,
+    RA: Send + Sync + 'static,
+    Client<B, E, Block, RA>: ProvideRuntimeApi<Block>,
+    <Client<B, E, Block, RA> as ProvideRuntimeApi<Block>>::Api: NftsRuntimeApi<Block>,
+{
+    /// Prove the child trie entry at the key returned by `key`.
+    fn prove(
+        &self,
+        at: Option<Hash>,
+        key: impl FnOnce(&BlockId<Block>) -> Result<Vec<u8>>,
+    ) -> Result<ShieldedStateProof> {
+        let at = at.unwrap_or_else(|| self.client.info().best_hash);
+        let id = BlockId::hash(at);
+        let key = key(&id)?;
+        let proof = self.client
+            .read_child_proof(&id, nfts::CHILD_STORAGE_KEY, nfts::child_info(), &[key])
+            .map_err(|e| nfts_error("Unable to prove shielded state", e))?;
+        Ok(ShieldedStateProof {
+            at,
+            proof: proof.iter_nodes().map(Into::into).collect(),
+        })
+    }
+}
+
+impl<B, E, RA> NftsApi for Nfts<B, E, RA> where
+    B: sc_client_api::backend::Backend<Block> + Send + Sync + 'static,
+    E: CallExecutor<Block> + Send + Sync + 'static,
+    RA: Send + Sync + 'static,
+    Client<B, E, Block, RA>: ProvideRuntimeApi<Block>,
+    <Client<B, E, Block, RA> as ProvideRuntimeApi<Block>>::Api: NftsRuntimeApi<Block>,
+{
+    fn nullifier_proof(&self, pool: ShieldedPool, nullifier: H256, at: Option<Hash>) -> Result<ShieldedStateProof> {
+        self.prove(at, |id| {
+            self.client.runtime_api().nullifier_key(id, pool, nullifier.into())
+                .map_err(|e| nfts_error("Unable to query nullifier key", e))
+        })
+    }
+
+    fn tree_proof(&self, pool: ShieldedPool, at: Option<Hash>) -> Result<ShieldedStateProof> {
+        self.prove(at, |id| {
+            self.client.runtime_api().tree_key(id, pool)
+                .map_err(|e| nfts_error("Unable to query tree key", e))
+        })
+    }
+}
+
+/// Future result of a light client query.
+pub type FutureResult<T> = Box<dyn jsonrpc_core::futures::Future<Item = T, Error = jsonrpc_core::Error> + Send>;
+
+#[rpc]
+pub trait NftsLightApi {
+    /// Returns whether `nullifier` has been spent in `pool` as of the last finalized block.
+    #[rpc(name = "nfts_isNullifierSpent")]
+    fn is_nullifier_spent(&self, pool: ShieldedPool, nullifier: H256) -> FutureResult<bool>;
+
+    /// Returns the root of the tree of `pool` as of the last finalized block.
+    #[rpc(name = "nfts_treeRoot")]
+    fn tree_root(&self, pool: ShieldedPool) -> FutureResult<H256>;
+
+    /// Returns the number of note commitments in the tree of `pool` as of the last finalized
+    /// block.
+    #[rpc(name = "nfts_treeSize")]
+    fn tree_size(&self, pool: ShieldedPool) -> FutureResult<u64>;
+}
+
+/// A struct that implements the [`NftsLightApi`].
+///
+/// Values are read from full nodes with read proofs of the child trie of the shielded state.
+/// The fetcher checks the proofs against the state root of the last finalized header.
+pub struct NftsLight<C, F> {
+    client: Arc<C>,
+    remote_blockchain: Arc<dyn RemoteBlockchain<Block>>,
+    fetcher: Arc<F>,
+}
+
+impl<C, F> NftsLight<C, F> {
+    /// Create new `NftsLight` reading through `fetcher`.
+    pub fn new(client: Arc<C>, remote_blockchain: Arc<dyn RemoteBlockchain<Block>>, fetcher: Arc<F>) -> Self {
+        NftsLight {
+            client,
+            remote_blockchain,
+            fetcher,
+        }
+    }
+}
+
+impl<C, F> NftsLight<C, F> where
+    C: HeaderBackend<Block> + Send + Sync + 'static,
+    F: Fetcher<Block> + 'static,
+{
+    /// Read `key` from the child trie of the shielded state at the last finalized block and map
+    /// the value with `f`.
+    fn read<T: Send + 'static>(
+        &self,
+        key: Vec<u8>,
+        f: impl FnOnce(Option<Vec<u8>>) -> Result<T> + Send + 'static,
+    ) -> FutureResult<T> {
+        let block = self.client.info().finalized_hash;
+        let remote_blockchain = self.remote_blockchain.clone();
+        let fetcher = self.fetcher.clone();
+        let read = async move {
+            let header = future_header(&*remote_blockchain, &*fetcher, BlockId::hash(block)).await
+                .map_err(|e| nfts_error("Unable to fetch finalized header", e))?
+                .ok_or_else(|| nfts_error("Unknown finalized header", block))?;
+            let (child_info, child_type) = nfts::child_info().info();
+            let mut values = fetcher.remote_read_child(RemoteReadChildRequest {
+                block,
+                header,
+                storage_key: nfts::CHILD_STORAGE_KEY.to_vec(),
+                child_info: child_info.to_vec(),
+                child_type,
+                keys: vec![key.clone()],
+                retry_count: None,
+            }).await.map_err(|e| nfts_error("Unable to read shielded state", e))?;
+            f(values.remove(&key).and_then(|value| value))
+        };
+        Box::new(read.boxed().compat())
+    }
+}
+
+impl<C, F> NftsLightApi for NftsLight<C, F> where
+    C: HeaderBackend<Block> + Send + Sync + 'static,
+    F: Fetcher<Block> + 'static,
+{
+    fn is_nullifier_spent(&self, pool: ShieldedPool, nullifier: H256) -> FutureResult<bool> {
+        self.read(nfts::nullifier_key(pool, nullifier.as_fixed_bytes()), |value| Ok(value.is_some()))
+    }
+
+    fn tree_root(&self, pool: ShieldedPool) -> FutureResult<H256> {
+        self.read(nfts::tree_key(pool), move |value| Ok(pool.root_of(&decode_tree(value)?).into()))
+    }
+
+    fn tree_size(&self, pool: ShieldedPool) -> FutureResult<u64> {
+        self.read(nfts::tree_key(pool), |value| Ok(decode_tree(value)?.size))
+    }
+}
+
+fn decode_tree(value: Option<Vec<u8>>) -> Result<Frontier> {
+    value.map_or(Ok(Frontier::default()), |value| {
+        Frontier::decode(&mut &value[..]).map_err(|e| nfts_error("Unable to decode tree", e))
+    })
+}
+
+#[rpc]
+pub trait SaplingApi {
+    /// Returns the Bech32 extended full viewing key of `account` of the Sapling key stored in the
+    /// keystore under the sr25519 `public` key.
+    #[rpc(name = "sapling_viewingKey")]
+    fn viewing_key(&self, public: H256, account: u32) -> Result<String>;
+
+    /// Signs `sighash` with the Sapling key stored under `public`, and returns the
+    /// `spend_auth_sig` of a spend of a note of `account` randomized by `alpha`.
+    #[rpc(name = "sapling_signSpend")]
+    fn sign_spend(&self, public: H256, account: u32, alpha: H256, sighash: H256) -> Result<Bytes>;
+}
+
+/// A struct that implements the [`SaplingApi`].
+///
+/// Spending keys never leave the keystore, but anyone reaching the API can authorize spends, so
+/// the API is only exposed by full nodes started with `--unsafe-signing-rpc`.
+pub struct Sapling {
+    keystore: KeyStorePtr,
+    network: &'static SaplingNetwork,
+}
+
+impl Sapling {
+    /// Create new `Sapling` signing with the keys of `keystore`.
+    pub fn new(keystore: KeyStorePtr, network: &'static SaplingNetwork) -> Self {
+        Sapling {
+            keystore,
+            network,
+        }
+    }
+
+    fn spending_key(&self, public: H256, account: u32) -> Result<ExtendedSpendingKey> {
+        let public = sr25519::Public::from_raw(public.into());
+        sapling_keys::spending_key_from_keystore(&self.keystore, &public, self.network.coin_type, account)
+            .map_err(sapling_error)
+    }
+}
+
+fn sapling_error(e: sapling_keys::Error) -> jsonrpc_core::Error {
+    jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::InvalidParams,
+        message: e.to_string(),
+        data: None,
+    }
+}
+
+impl SaplingApi for Sapling {
+    fn viewing_key(&self, public: H256, account: u32) -> Result<String> {
+        let key = ExtendedFullViewingKey::from(&self.spending_key(public, account)?);
+        Ok(sapling_keys::encode_extended_full_viewing_key(self.network, &key))
+    }
+
+    fn sign_spend(&self, public: H256, account: u32, alpha: H256, sighash: H256) -> Result<Bytes> {
+        let key = self.spending_key(public, account)?;
+        let signature = sapling_keys::spend_auth_sig(&key, alpha.as_fixed_bytes(), sighash.as_fixed_bytes())
+            .map_err(sapling_error)?;
+        Ok(signature.to_vec().into())
+    }
+}
+
+#[rpc]
+pub trait RelayApi {
+    /// Hands an encoded unsigned shielded transaction to the relay, which gossips it after a
+    /// random delay instead of submitting it to the local transaction pool. Fails while the relay
+    /// holds `relay::MAX_PENDING` transactions it has not batched yet.
+    #[rpc(name = "nfts_relayTransaction")]
+    fn relay_transaction(&self, extrinsic: Bytes) -> Result<()>;
+}
+
+/// A struct that implements the [`RelayApi`].
+pub struct Relay {
+    sender: Mutex<RelaySender>,
+}
+
+impl Relay {
+    /// Create new `Relay` handing transactions to the relay through `sender`.
+    pub fn new(sender: RelaySender) -> Self {
+        Relay {
+            sender: Mutex::new(sender),
+        }
+    }
+}
+
+impl RelayApi for Relay {
+    fn relay_transaction(&self, extrinsic: Bytes) -> Result<()> {
+        if !relay::is_shielded_transaction(&extrinsic) {
+            return Err(jsonrpc_core::Error {
+                code: jsonrpc_core::ErrorCode::InvalidParams,
+                message: "Not an unsigned shielded transaction".into(),
+                data: None,
+            });
+        }
+        self.sender.lock().try_send(extrinsic.to_vec()).map_err(|e| jsonrpc_core::Error {
+            code: jsonrpc_core::ErrorCode::InternalError,
+            message: if e.is_full() { "Relay is busy" } else { "Relay is not running" }.into(),
+            data: Some(format!("{:?}", e).into()),
+        })
+    }
+}
+
+#[rpc]
+pub trait FrostApi {
+    /// Commits to fresh nonces as signer `identifier` and returns the SCALE encoded
+    /// `SigningCommitments`. The nonces stay on the node until `frost_sign` uses them, for at
+    /// most `frost::NONCES_LIFETIME`.
+    #[rpc(name = "frost_commit")]
+    fn commit(&self, identifier: Identifier) -> Result<Bytes>;
+
+    /// Signs a SCALE encoded `SigningPackage` with the key share kept in the keystore under the
+    /// ed25519 `public` key, using the nonces committed to by `frost_commit`, and returns the
+    /// SCALE encoded `SignatureShare`. The share must be one of the SCALE encoded
+    /// `PublicKeyPackage`.
+    #[rpc(name = "frost_sign")]
+    fn sign(&self, public: H256, package: Bytes, public_key_package: Bytes) -> Result<Bytes>;
+
+    /// Aggregates SCALE encoded `SignatureShare`s into the 64 byte `spend_auth_sig` of a spend.
+    #[rpc(name = "frost_aggregate")]
+    fn aggregate(&self, package: Bytes, shares: Vec<Bytes>, public_key_package: Bytes) -> Result<Bytes>;
+}
+
+/// A struct that implements the [`FrostApi`].
+///
+/// Signs with the key shares of the keystore, so the API is only exposed by full nodes started
+/// with `--unsafe-signing-rpc`.
+pub struct Frost {
+    keystore: KeyStorePtr,
+    nonces: Mutex<PendingNonces>,
+}
+
+impl Frost {
+    /// Create new `Frost` signing with the key shares of `keystore`.
+    pub fn new(keystore: KeyStorePtr) -> Self {
+        Frost {
+            keystore,
+            nonces: Default::default(),
+        }
+    }
+}
+
+fn decode<T: Decode>(bytes: &Bytes) -> Result<T> {
+    T::decode(&mut &bytes[..]).map_err(|e| jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::InvalidParams,
+        message: "Unable to decode parameter".into(),
+        data: Some(format!("{:?}", e).into()),
+    })
+}
+
+fn frost_error(e: frost::Error) -> jsonrpc_core::Error {
+    jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::InvalidParams,
+        message: e.to_string(),
+        data: None,
+    }
+}
+
+impl FrostApi for Frost {
+    fn commit(&self, identifier: Identifier) -> Result<Bytes> {
+        let (nonces, commitments) = frost::commit(identifier, &mut OsRng);
+        self.nonces.lock()
+            .insert(commitments.clone(), nonces, Instant::now())
+            .map_err(frost_error)?;
+        Ok(commitments.encode().into())
+    }
+
+    fn sign(&self, public: H256, package: Bytes, public_key_package: Bytes) -> Result<Bytes> {
+        let package: SigningPackage = decode(&package)?;
+        let public_key_package: PublicKeyPackage = decode(&public_key_package)?;
+        let public = ed25519::Public::from_raw(public.into());
+        let key_share = frost::key_share_from_keystore(&self.keystore, &public, &public_key_package)
+            .map_err(frost_error)?;
+        let commitments = package.commitments.iter()
+            .find(|commitments| commitments.identifier == key_share.identifier)
+            .ok_or(frost_error(frost::Error::MissingCommitment(key_share.identifier)))?;
+        // nonces are removed even if signing fails, they must never be used twice.
+        let nonces = self.nonces.lock()
+            .take(commitments, Instant::now())
+            .ok_or(frost_error(frost::Error::MissingCommitment(key_share.identifier)))?;
+        frost::sign(&package, &nonces, &key_share)
+            .map(|share| share.encode().into())
+            .map_err(frost_error)
+    }
+
+    fn aggregate(&self, package: Bytes, shares: Vec<Bytes>, public_key_package: Bytes) -> Result<Bytes> {
+        let package: SigningPackage = decode(&package)?;
+        let shares = shares.iter().map(decode).collect::<Result<Vec<SignatureShare>>>()?;
+        let public_key_package: PublicKeyPackage = decode(&public_key_package)?;
+        frost::aggregate(&package, &shares, &public_key_package)
+            .map(|signature| signature.to_vec().into())
+            .map_err(frost_error)
+    }
+}
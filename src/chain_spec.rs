@@ -1,18 +1,20 @@
 use sp_core::{Pair, Public, sr25519};
 use node_runtime::{
 	AuthorityDiscoveryConfig, BabeConfig, BalancesConfig, CouncilConfig, DemocracyConfig,
-	GrandpaConfig, ImOnlineConfig, MultiAccount, MultiAccountConfig, SessionConfig, SessionKeys,
-	StakerStatus, StakingConfig, SystemConfig, WASM_BINARY,
+	GrandpaConfig, ImOnlineConfig, IndicesConfig, MultiAccount, MultiAccountConfig, NftsConfig, SessionConfig, SessionKeys,
+	StakerStatus, StakingConfig, SystemConfig, TreasuryConfig, VestingConfig, WASM_BINARY,
 };
-use node_runtime::constants::currency::*;
+use node_runtime::constants::{currency::*, time::DAYS};
+use node_runtime::vesting::{VestingKind, VestingSchedule};
 use sc_service;
+use crate::sapling_keys::SaplingNetwork;
 use sp_finality_grandpa::{AuthorityId as GrandpaId};
 use sp_consensus_babe::{AuthorityId as BabeId};
 use pallet_im_online::sr25519::{AuthorityId as ImOnlineId};
 use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
 use sp_runtime::{Perbill, traits::{Verify, IdentifyAccount}};
 
-pub use node_primitives::{AccountId, Balance, Hash, Signature};
+pub use node_primitives::{AccountId, AccountIndex, Balance, BlockNumber, Hash, Signature};
 pub use node_runtime::GenesisConfig;
 
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
@@ -27,6 +29,40 @@ pub enum Alternative {
 	LocalTestnet,
 }
 
+/// Id of the chain spec of the production chain.
+pub const MAINNET_ID: &str = "mainnet";
+
+/// Sapling key and address encodings of the production chain.
+///
+/// They differ from those of Zcash, so that keys and addresses of one chain are never taken for
+/// those of the other. The coin type is not registered in SLIP-44, it only differs from the 133 of
+/// Zcash so that a seed gives unrelated keys on both chains.
+pub const MAINNET_SAPLING: SaplingNetwork = SaplingNetwork {
+	coin_type: 1133,
+	hrp_payment_address: "zcs",
+	hrp_extended_spending_key: "zc-secret-extended-key-main",
+	hrp_extended_full_viewing_key: "zcviews",
+};
+
+/// Sapling key and address encodings of dev and local testnet chains. Like every test network,
+/// they use the SLIP-44 coin type 1.
+pub const DEV_SAPLING: SaplingNetwork = SaplingNetwork {
+	coin_type: 1,
+	hrp_payment_address: "zctestsapling",
+	hrp_extended_spending_key: "zc-secret-extended-key-test",
+	hrp_extended_full_viewing_key: "zcviewtestsapling",
+};
+
+/// Sapling key and address encodings of the chain `id`, as given to `--chain` or set in a chain
+/// spec. Other chains have none.
+pub fn sapling_network(id: &str) -> Option<&'static SaplingNetwork> {
+	match id {
+		"dev" | "local" | "local_testnet" => Some(&DEV_SAPLING),
+		MAINNET_ID => Some(&MAINNET_SAPLING),
+		_ => None,
+	}
+}
+
 /// Helper function to generate a crypto pair from seed
 pub fn get_from_seed<TPublic: Public>(seed: &str) -> <TPublic::Pair as Pair>::Public {
     TPublic::Pair::from_string(&format!("//{}", seed), None)
@@ -90,11 +126,27 @@ fn session_keys(
 	SessionKeys { grandpa, babe, im_online, authority_discovery }
 }
 
+/// A vesting schedule of the genesis.
+pub type GenesisVesting = (AccountId, VestingSchedule<Balance, BlockNumber>);
+
+/// Helper function to lock `locked` of the balance of `who` from block `start`, unlocking it
+/// linearly over `duration` blocks.
+pub fn linear_vesting(who: AccountId, locked: Balance, start: BlockNumber, duration: BlockNumber) -> GenesisVesting {
+	(who, VestingSchedule { locked, start, duration, kind: VestingKind::Linear })
+}
+
+/// Helper function to lock `locked` of the balance of `who` until block `start + duration`.
+pub fn cliff_vesting(who: AccountId, locked: Balance, start: BlockNumber, duration: BlockNumber) -> GenesisVesting {
+	(who, VestingSchedule { locked, start, duration, kind: VestingKind::Cliff })
+}
+
 /// Helper function to create GenesisConfig for testing
 pub fn testnet_genesis(
 	// StashId, ControllerId, GrandpaId, BabeId, ImOnlineId, AuthorityDiscoveryId
 	initial_authorities: Vec<(AccountId, AccountId, GrandpaId, BabeId, ImOnlineId, AuthorityDiscoveryId)>,
     endowed_accounts: Option<Vec<AccountId>>,
+    // locks on the endowments, which must not exceed them.
+    vesting: Vec<GenesisVesting>,
 ) -> GenesisConfig {
     let mut endowed_accounts: Vec<AccountId> = endowed_accounts.unwrap_or_else(|| {
 		vec![
@@ -133,6 +185,14 @@ pub fn testnet_genesis(
                 .chain(initial_authorities.iter().map(|x| (x.0.clone(), STASH)))
                 .collect(),
         }),
+        vesting: Some(VestingConfig { vesting }),
+        // Endowed accounts get the first indices, in order, so they can be referred to by them.
+        pallet_indices: Some(IndicesConfig {
+            indices: endowed_accounts.iter().cloned()
+                .enumerate()
+                .map(|(index, account)| (index as AccountIndex, account))
+                .collect(),
+        }),
         pallet_session: Some(SessionConfig {
 			keys: initial_authorities.iter().map(|x| {
 				(x.0.clone(), x.0.clone(), session_keys(x.2.clone(), x.3.clone(), x.4.clone(), x.5.clone()))
@@ -159,6 +219,8 @@ pub fn testnet_genesis(
 			.. Default::default()
         }),
         pallet_democracy: Some(DemocracyConfig::default()),
+        // Creates the treasury account, which is then funded by slashes and reward remainders.
+        pallet_treasury: Some(TreasuryConfig::default()),
 		pallet_collective_Instance1: Some(CouncilConfig {
 			members: endowed_accounts.iter()
 						.take((num_endowed_accounts + 1) / 2)
@@ -182,8 +244,14 @@ pub fn testnet_genesis(
 			multi_accounts: vec![
 				// Add the first 3 accounts to a 2-of-3 multi account
 				(endowed_accounts[0].clone(), 2, vec![endowed_accounts[1].clone(), endowed_accounts[2].clone()]),
+				// Add the next 3 accounts to a 2-of-3 emergency multi account, which can pause the
+				// shielded pool
+				(endowed_accounts[3].clone(), 2, vec![endowed_accounts[4].clone(), endowed_accounts[5].clone()]),
 			],
 		}),
+		nfts: Some(NftsConfig {
+			emergency_account: MultiAccount::multi_account_id(2),
+		}),
     }
 }
 
@@ -202,6 +270,7 @@ fn development_config_genesis() -> GenesisConfig {
 			get_authority_keys_from_seed("Alice"),
 		],
 		None,
+		vec![],
 	)
 }
 
@@ -226,6 +295,10 @@ fn local_testnet_genesis() -> GenesisConfig {
 			get_authority_keys_from_seed("Bob"),
 		],
 		None,
+		vec![
+			linear_vesting(get_account_id_from_seed::<sr25519::Public>("Eve"), 10_000_000 * RAD, 0, 365 * DAYS),
+			cliff_vesting(get_account_id_from_seed::<sr25519::Public>("Ferdie"), 10_000_000 * RAD, 0, 180 * DAYS),
+		],
 	)
 }
 
@@ -265,6 +338,7 @@ pub(crate) mod tests {
 				get_authority_keys_from_seed("Alice"),
 			],
 			None,
+			vec![],
 		)
 	}
 
@@ -327,6 +401,29 @@ pub(crate) mod tests {
 			"4dpEcgqFor2TJw9uWSjx2JpjkNmTic2UjJAK1j9fRtcTUoRu");
 	}
 
+	#[test]
+	fn test_sapling_networks() {
+		assert_eq!(sapling_network(development_config().id()), Some(&DEV_SAPLING));
+		assert_eq!(sapling_network(local_testnet_config().id()), Some(&DEV_SAPLING));
+		assert_eq!(sapling_network("local"), Some(&DEV_SAPLING));
+		assert_eq!(sapling_network(MAINNET_ID), Some(&MAINNET_SAPLING));
+		assert_eq!(sapling_network("flaming-fir"), None);
+
+		for network in &[MAINNET_SAPLING, DEV_SAPLING] {
+			assert_ne!(network.coin_type, 133);
+			for hrp in &[
+				network.hrp_payment_address,
+				network.hrp_extended_spending_key,
+				network.hrp_extended_full_viewing_key,
+			] {
+				assert!(![
+					"zs", "secret-extended-key-main", "zxviews",
+					"ztestsapling", "secret-extended-key-test", "zxviewtestsapling",
+				].contains(hrp));
+			}
+		}
+	}
+
 	#[test]
 	fn test_create_development_chain_spec() {
 		development_config().build_storage().unwrap();
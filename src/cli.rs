@@ -1,4 +1,4 @@
-use sc_cli::{RunCmd, Subcommand};
+use sc_cli::RunCmd;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -8,4 +8,87 @@ pub struct Cli {
 
 	#[structopt(flatten)]
 	pub run: RunCmd,
+
+	/// Expose the `frost_*` and `sapling_*` RPCs, which sign with the keys of the keystore and
+	/// derive viewing keys from them. Only enable this on a full node whose RPC is reachable by
+	/// its owner alone.
+	#[structopt(long = "unsafe-signing-rpc")]
+	pub unsafe_signing_rpc: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Subcommand {
+	#[structopt(flatten)]
+	Base(sc_cli::Subcommand),
+
+	/// Split a fresh spend authorizing key among the members of a multi account.
+	#[structopt(name = "frost-keygen")]
+	FrostKeygen(FrostKeygenCmd),
+
+	/// Aggregate signature shares into the `spend_auth_sig` of a spend.
+	#[structopt(name = "frost-aggregate")]
+	FrostAggregate(FrostAggregateCmd),
+
+	/// Derive a Sapling spending key, viewing key and payment address from a secret URI.
+	#[structopt(name = "sapling-key")]
+	SaplingKey(SaplingKeyCmd),
+
+	/// Decode a Sapling payment address into its raw bytes.
+	#[structopt(name = "sapling-address")]
+	SaplingAddress(SaplingAddressCmd),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct FrostKeygenCmd {
+	/// Number of members needed to authorize a spend.
+	#[structopt(long)]
+	pub threshold: u16,
+
+	/// Number of members holding a key share.
+	#[structopt(long)]
+	pub signers: u16,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct FrostAggregateCmd {
+	/// Hex encoded signing package.
+	#[structopt(long)]
+	pub package: String,
+
+	/// Hex encoded signature share of a signer, once per signer.
+	#[structopt(long = "share")]
+	pub shares: Vec<String>,
+
+	/// Hex encoded public key package.
+	#[structopt(long)]
+	pub public_key_package: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SaplingKeyCmd {
+	/// Secret URI of the seed, such as `//Alice` or a mnemonic phrase.
+	#[structopt(long)]
+	pub suri: String,
+
+	/// Account index in the key path.
+	#[structopt(long, default_value = "0")]
+	pub account: u32,
+
+	/// Diversifier index to search a payment address from.
+	#[structopt(long, default_value = "0")]
+	pub diversifier: u64,
+
+	/// Chain whose encodings are used: `mainnet`, or the test chains `dev` and `local`.
+	#[structopt(long, default_value = "dev")]
+	pub chain: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SaplingAddressCmd {
+	/// Bech32 encoded payment address.
+	pub address: String,
+
+	/// Chain whose encodings are used: `mainnet`, or the test chains `dev` and `local`.
+	#[structopt(long, default_value = "dev")]
+	pub chain: String,
 }
@@ -14,10 +14,15 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
+use codec::{Decode, Encode};
+use rand::rngs::OsRng;
 use sc_cli::VersionInfo;
 use crate::service;
 use crate::chain_spec;
-use crate::cli::Cli;
+use crate::cli::{Cli, FrostAggregateCmd, FrostKeygenCmd, SaplingAddressCmd, SaplingKeyCmd, Subcommand};
+use crate::frost;
+use crate::sapling_keys::{self, SaplingNetwork};
+use zcash_primitives::zip32::ExtendedFullViewingKey;
 
 /// Parse and run command line arguments
 pub fn run(version: VersionInfo) -> sc_cli::Result<()> {
@@ -26,7 +31,7 @@ pub fn run(version: VersionInfo) -> sc_cli::Result<()> {
     let mut config = sc_service::Configuration::from_version(&version);
 
     match opt.subcommand {
-        Some(subcommand) => {
+        Some(Subcommand::Base(subcommand)) => {
 			subcommand.init(&version)?;
 			subcommand.update_config(&mut config, chain_spec::load_spec, &version)?;
 			subcommand.run(
@@ -34,15 +39,96 @@ pub fn run(version: VersionInfo) -> sc_cli::Result<()> {
 				|config: _| Ok(new_full_start!(config).0),
 			)
 		},
+		Some(Subcommand::FrostKeygen(cmd)) => frost_keygen(cmd),
+		Some(Subcommand::FrostAggregate(cmd)) => frost_aggregate(cmd),
+		Some(Subcommand::SaplingKey(cmd)) => sapling_key(cmd),
+		Some(Subcommand::SaplingAddress(cmd)) => sapling_address(cmd),
 		None => {
+			let signing_rpc = opt.unsafe_signing_rpc;
 			opt.run.init(&version)?;
 			opt.run.update_config(&mut config, chain_spec::load_spec, &version)?;
 			opt.run.run(
 				config,
 				service::new_light,
-				service::new_full,
+				|config| service::new_full(config, signing_rpc),
 				&version,
 			)
 		},
     }
 }
+
+fn frost_error(e: frost::Error) -> sc_cli::Error {
+	sc_cli::Error::Input(e.to_string())
+}
+
+fn decode_hex<T: Decode>(name: &str, data: &str) -> sc_cli::Result<T> {
+	let bytes = hex::decode(data.trim_start_matches("0x"))
+		.map_err(|e| sc_cli::Error::Input(format!("Invalid hex in {}: {}", name, e)))?;
+	T::decode(&mut &bytes[..])
+		.map_err(|e| sc_cli::Error::Input(format!("Unable to decode {}: {:?}", name, e)))
+}
+
+/// Print the key shares of a fresh spend authorizing key and its public key package.
+fn frost_keygen(cmd: FrostKeygenCmd) -> sc_cli::Result<()> {
+	let (shares, public_key_package) = frost::keygen_with_dealer(cmd.threshold, cmd.signers, &mut OsRng)
+		.map_err(frost_error)?;
+
+	println!("Spend validating key (ak): 0x{}", hex::encode(public_key_package.group_key));
+	println!("Public key package: 0x{}", hex::encode(public_key_package.encode()));
+	for share in shares {
+		println!(
+			"Key share of signer {}: insert the secret URI {} under the public key 0x{} as `frst`",
+			share.identifier,
+			share.keystore_suri(),
+			hex::encode(share.keystore_public()),
+		);
+	}
+	Ok(())
+}
+
+/// Print the `spend_auth_sig` aggregated from the signature shares.
+fn frost_aggregate(cmd: FrostAggregateCmd) -> sc_cli::Result<()> {
+	let package: frost::SigningPackage = decode_hex("package", &cmd.package)?;
+	let shares = cmd.shares.iter()
+		.map(|share| decode_hex("share", share))
+		.collect::<sc_cli::Result<Vec<frost::SignatureShare>>>()?;
+	let public_key_package: frost::PublicKeyPackage =
+		decode_hex("public key package", &cmd.public_key_package)?;
+
+	let signature = frost::aggregate(&package, &shares, &public_key_package).map_err(frost_error)?;
+	println!("0x{}", hex::encode(&signature[..]));
+	Ok(())
+}
+
+fn sapling_error(e: sapling_keys::Error) -> sc_cli::Error {
+	sc_cli::Error::Input(e.to_string())
+}
+
+fn sapling_network(chain: &str) -> sc_cli::Result<&'static SaplingNetwork> {
+	chain_spec::sapling_network(chain)
+		.ok_or_else(|| sc_cli::Error::Input(format!("No Sapling encodings for chain {}", chain)))
+}
+
+/// Print the Sapling keys derived from a secret URI and a payment address.
+fn sapling_key(cmd: SaplingKeyCmd) -> sc_cli::Result<()> {
+	let network = sapling_network(&cmd.chain)?;
+	let spending_key = sapling_keys::spending_key_from_suri(&cmd.suri, network.coin_type, cmd.account)
+		.map_err(sapling_error)?;
+	let viewing_key = ExtendedFullViewingKey::from(&spending_key);
+	let (diversifier, address) = sapling_keys::payment_address(&viewing_key, cmd.diversifier)
+		.map_err(sapling_error)?;
+
+	println!("Extended spending key: {}", sapling_keys::encode_extended_spending_key(network, &spending_key));
+	println!("Extended full viewing key: {}", sapling_keys::encode_extended_full_viewing_key(network, &viewing_key));
+	println!("Payment address (diversifier {}): {}", diversifier, sapling_keys::encode_payment_address(network, &address));
+	Ok(())
+}
+
+/// Print the raw bytes of a payment address, as expected by `disclose_payment` and
+/// `disclose_transacted_payment`.
+fn sapling_address(cmd: SaplingAddressCmd) -> sc_cli::Result<()> {
+	let network = sapling_network(&cmd.chain)?;
+	let address = sapling_keys::decode_payment_address(network, &cmd.address).map_err(sapling_error)?;
+	println!("0x{}", hex::encode(&address.to_bytes()[..]));
+	Ok(())
+}
@@ -0,0 +1,549 @@
+//! Threshold spend authorization for shielded notes, using FROST over RedJubjub.
+//!
+//! The spend authorizing key `ask` of a multi account is split among its members by a trusted
+//! dealer, and any `threshold` of them can jointly produce the `spend_auth_sig` of a Sapling
+//! spend. Only `ask` is split: the proof generation key `(ak, nsk)` is held by every member, so
+//! any member can create the spend proof but none can authorize the spend alone.
+//!
+//! Signing takes two rounds. Every signer first `commit`s to a pair of nonces. A coordinator
+//! collects the commitments of the signers into a `SigningPackage`, along with the sighash and
+//! the randomizer `alpha` of the spend, and every signer `sign`s the package. The coordinator
+//! then `aggregate`s the signature shares into a signature under the randomized key
+//! `rk = ak + [alpha] G`, as expected by the runtime.
+//!
+//! The protocol is FROST (Komlo and Goldberg), with the challenge computed over `rk` and the
+//! randomizer added to the aggregated signature.
+//!
+//! Key shares are kept in the keystore under `FROST_KEY_TYPE`, inserted with `author_insertKey`
+//! as the hex encoded share secret along with the ed25519 public key it resolves to. The node
+//! signs with them and never hands them out.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use codec::{Decode, Encode};
+use ff::Field;
+use group::{Group, GroupEncoding};
+use jubjub::{ExtendedPoint, Fr, SubgroupPoint};
+use rand::{CryptoRng, RngCore};
+use sc_keystore::KeyStorePtr;
+use sp_core::{crypto::KeyTypeId, ed25519, Pair};
+use zcash_primitives::constants::SPENDING_KEY_GENERATOR;
+
+/// Index of a signer, starting at 1.
+pub type Identifier = u16;
+
+/// Personalization of the RedJubjub challenge hash.
+const CHALLENGE_PERSONALIZATION: &[u8; 16] = b"Zcash_RedJubjubH";
+/// Personalization of the FROST binding factor hash.
+const BINDING_PERSONALIZATION: &[u8; 16] = b"FROST_RedJubjubB";
+
+/// Key type of the key shares in the keystore.
+pub const FROST_KEY_TYPE: KeyTypeId = KeyTypeId(*b"frst");
+/// Maximum number of commitments awaiting a signature.
+pub const MAX_PENDING_NONCES: usize = 64;
+/// Time after which committed nonces are dropped if they have not been used.
+pub const NONCES_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+/// Errors of the signing protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// The threshold is zero or larger than the number of signers.
+	InvalidThreshold,
+	/// A scalar or point is not canonically encoded.
+	InvalidEncoding,
+	/// A signer identifier is zero or appears twice.
+	InvalidIdentifier(Identifier),
+	/// Fewer signers than the threshold have committed.
+	NotEnoughSigners,
+	/// The package has no commitment of this signer, or not the one matching its nonces.
+	MissingCommitment(Identifier),
+	/// A committed signer has not provided a signature share.
+	MissingShare(Identifier),
+	/// The signature share of a signer is invalid.
+	InvalidShare(Identifier),
+	/// The keystore has no key share under this public key.
+	UnknownKey,
+	/// The key share is not one of the public key package.
+	UnknownShare,
+	/// Too many commitments are awaiting a signature.
+	TooManyCommitments,
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::InvalidThreshold => write!(f, "Threshold must be between 1 and the number of signers"),
+			Error::InvalidEncoding => write!(f, "Invalid scalar or point encoding"),
+			Error::InvalidIdentifier(id) => write!(f, "Invalid or duplicate signer {}", id),
+			Error::NotEnoughSigners => write!(f, "Not enough signers"),
+			Error::MissingCommitment(id) => write!(f, "Missing commitment of signer {}", id),
+			Error::MissingShare(id) => write!(f, "Missing signature share of signer {}", id),
+			Error::InvalidShare(id) => write!(f, "Invalid signature share of signer {}", id),
+			Error::UnknownKey => write!(f, "No key share in the keystore under this public key"),
+			Error::UnknownShare => write!(f, "Key share is not part of the public key package"),
+			Error::TooManyCommitments => write!(f, "Too many commitments awaiting a signature"),
+		}
+	}
+}
+
+/// The share of the spend authorizing key held by a signer.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct KeyShare {
+	pub identifier: Identifier,
+	pub secret: [u8; 32],
+	/// The spend validating key `ak`.
+	pub group_key: [u8; 32],
+}
+
+impl KeyShare {
+	/// Secret URI under which the share is inserted into the keystore.
+	pub fn keystore_suri(&self) -> String {
+		format!("0x{}", hex::encode(self.secret))
+	}
+
+	/// Public key under which the share is inserted into the keystore.
+	pub fn keystore_public(&self) -> ed25519::Public {
+		ed25519::Pair::from_seed(&self.secret).public()
+	}
+}
+
+/// Public data of a split key, needed to aggregate signatures.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct PublicKeyPackage {
+	pub threshold: u16,
+	/// The spend validating key `ak`.
+	pub group_key: [u8; 32],
+	/// Public key of the share of every signer.
+	pub verifying_shares: Vec<(Identifier, [u8; 32])>,
+}
+
+/// Nonces of a signer for a single signature. They must never be reused.
+#[derive(Clone)]
+pub struct SigningNonces {
+	hiding: Fr,
+	binding: Fr,
+}
+
+/// Commitments to the nonces of a signer.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SigningCommitments {
+	pub identifier: Identifier,
+	pub hiding: [u8; 32],
+	pub binding: [u8; 32],
+}
+
+/// What the signers sign: the sighash and randomizer of a spend, and the commitments of all
+/// signers.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct SigningPackage {
+	pub commitments: Vec<SigningCommitments>,
+	pub message: Vec<u8>,
+	/// The randomizer `alpha` of the spend.
+	pub randomizer: [u8; 32],
+}
+
+/// The share of a signature made by a signer.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct SignatureShare {
+	pub identifier: Identifier,
+	pub share: [u8; 32],
+}
+
+fn scalar(bytes: &[u8; 32]) -> Result<Fr, Error> {
+	Option::from(Fr::from_bytes(bytes)).ok_or(Error::InvalidEncoding)
+}
+
+fn point(bytes: &[u8; 32]) -> Result<SubgroupPoint, Error> {
+	Option::from(SubgroupPoint::from_bytes(bytes)).ok_or(Error::InvalidEncoding)
+}
+
+fn hash_to_scalar(personalization: &[u8; 16], parts: &[&[u8]]) -> Fr {
+	let mut state = blake2b_simd::Params::new()
+		.hash_length(64)
+		.personal(personalization)
+		.to_state();
+	for part in parts {
+		state.update(part);
+	}
+	Fr::from_bytes_wide(state.finalize().as_array())
+}
+
+/// RedJubjub challenge of a signature with nonce commitment `r` under `key`.
+fn challenge(r: &[u8; 32], key: &[u8; 32], message: &[u8]) -> Fr {
+	hash_to_scalar(CHALLENGE_PERSONALIZATION, &[&r[..], &key[..], message])
+}
+
+/// The randomized key `rk = ak + [alpha] G` of a spend.
+pub fn randomized_key(group_key: &[u8; 32], randomizer: &[u8; 32]) -> Result<[u8; 32], Error> {
+	let key = point(group_key)? + SPENDING_KEY_GENERATOR * scalar(randomizer)?;
+	Ok(key.to_bytes())
+}
+
+/// Split `ask` into `signers` shares, any `threshold` of which can sign.
+pub fn split_key<R: RngCore + CryptoRng>(
+	ask: &[u8; 32],
+	threshold: u16,
+	signers: u16,
+	rng: &mut R,
+) -> Result<(Vec<KeyShare>, PublicKeyPackage), Error> {
+	if threshold == 0 || threshold > signers {
+		return Err(Error::InvalidThreshold);
+	}
+
+	let mut coefficients = vec![scalar(ask)?];
+	coefficients.extend((1..threshold).map(|_| Fr::random(&mut *rng)));
+	let group_key = (SPENDING_KEY_GENERATOR * coefficients[0]).to_bytes();
+
+	let mut shares = Vec::with_capacity(signers as usize);
+	let mut verifying_shares = Vec::with_capacity(signers as usize);
+	for identifier in 1..=signers {
+		let x = Fr::from(u64::from(identifier));
+		let secret = coefficients.iter().rev().fold(Fr::zero(), |acc, coefficient| acc * x + coefficient);
+		shares.push(KeyShare { identifier, secret: secret.to_bytes(), group_key });
+		verifying_shares.push((identifier, (SPENDING_KEY_GENERATOR * secret).to_bytes()));
+	}
+
+	Ok((shares, PublicKeyPackage { threshold, group_key, verifying_shares }))
+}
+
+/// Generate a fresh `ask` and split it into `signers` shares, any `threshold` of which can sign.
+pub fn keygen_with_dealer<R: RngCore + CryptoRng>(
+	threshold: u16,
+	signers: u16,
+	rng: &mut R,
+) -> Result<(Vec<KeyShare>, PublicKeyPackage), Error> {
+	let ask = Fr::random(&mut *rng).to_bytes();
+	split_key(&ask, threshold, signers, rng)
+}
+
+/// First round: commit to fresh nonces.
+pub fn commit<R: RngCore + CryptoRng>(
+	identifier: Identifier,
+	rng: &mut R,
+) -> (SigningNonces, SigningCommitments) {
+	let nonces = SigningNonces { hiding: Fr::random(&mut *rng), binding: Fr::random(&mut *rng) };
+	let commitments = SigningCommitments {
+		identifier,
+		hiding: (SPENDING_KEY_GENERATOR * nonces.hiding).to_bytes(),
+		binding: (SPENDING_KEY_GENERATOR * nonces.binding).to_bytes(),
+	};
+	(nonces, commitments)
+}
+
+/// Nonces committed to by the signers of this node, until they sign or the nonces expire.
+#[derive(Default)]
+pub struct PendingNonces {
+	nonces: HashMap<SigningCommitments, (Instant, SigningNonces)>,
+}
+
+impl PendingNonces {
+	/// Keep the `nonces` committed to at `now`.
+	pub fn insert(
+		&mut self,
+		commitments: SigningCommitments,
+		nonces: SigningNonces,
+		now: Instant,
+	) -> Result<(), Error> {
+		self.nonces.retain(|_, (committed, _)| now.duration_since(*committed) < NONCES_LIFETIME);
+		if self.nonces.len() >= MAX_PENDING_NONCES {
+			return Err(Error::TooManyCommitments);
+		}
+		self.nonces.insert(commitments, (now, nonces));
+		Ok(())
+	}
+
+	/// Remove the nonces of `commitments`, unless they have expired by `now`.
+	pub fn take(&mut self, commitments: &SigningCommitments, now: Instant) -> Option<SigningNonces> {
+		self.nonces.remove(commitments)
+			.filter(|(committed, _)| now.duration_since(*committed) < NONCES_LIFETIME)
+			.map(|(_, nonces)| nonces)
+	}
+}
+
+/// The key share kept in `keystore` under `public`, as one of the signers of
+/// `public_key_package`.
+pub fn key_share_from_keystore(
+	keystore: &KeyStorePtr,
+	public: &ed25519::Public,
+	public_key_package: &PublicKeyPackage,
+) -> Result<KeyShare, Error> {
+	let pair = keystore.read()
+		.key_pair_by_type::<ed25519::Pair>(public, FROST_KEY_TYPE)
+		.map_err(|_| Error::UnknownKey)?;
+	let secret = *pair.seed();
+	let verifying_share = (SPENDING_KEY_GENERATOR * scalar(&secret)?).to_bytes();
+	let identifier = public_key_package.verifying_shares.iter()
+		.find(|(_, share)| *share == verifying_share)
+		.map(|(identifier, _)| *identifier)
+		.ok_or(Error::UnknownShare)?;
+	Ok(KeyShare { identifier, secret, group_key: public_key_package.group_key })
+}
+
+/// Values derived from a signing package that every signer and the coordinator agree on.
+struct SigningState {
+	identifiers: Vec<Identifier>,
+	binding_factors: Vec<Fr>,
+	group_commitment: [u8; 32],
+	randomized_key: [u8; 32],
+	challenge: Fr,
+}
+
+impl SigningState {
+	fn new(package: &SigningPackage, group_key: &[u8; 32]) -> Result<Self, Error> {
+		let mut seen = BTreeSet::new();
+		for commitment in &package.commitments {
+			if commitment.identifier == 0 || !seen.insert(commitment.identifier) {
+				return Err(Error::InvalidIdentifier(commitment.identifier));
+			}
+		}
+
+		let encoded_commitments = package.commitments.encode();
+		let mut group_commitment = SubgroupPoint::identity();
+		let mut binding_factors = Vec::with_capacity(package.commitments.len());
+		for commitment in &package.commitments {
+			let binding_factor = hash_to_scalar(BINDING_PERSONALIZATION, &[
+				&commitment.identifier.to_le_bytes()[..],
+				&package.message[..],
+				&package.randomizer[..],
+				&encoded_commitments[..],
+			]);
+			group_commitment += point(&commitment.hiding)? + point(&commitment.binding)? * binding_factor;
+			binding_factors.push(binding_factor);
+		}
+
+		let group_commitment = group_commitment.to_bytes();
+		let randomized_key = randomized_key(group_key, &package.randomizer)?;
+		Ok(SigningState {
+			identifiers: package.commitments.iter().map(|c| c.identifier).collect(),
+			binding_factors,
+			challenge: challenge(&group_commitment, &randomized_key, &package.message),
+			group_commitment,
+			randomized_key,
+		})
+	}
+
+	/// Lagrange coefficient of the signer at `position` for interpolating at zero.
+	fn lagrange_coefficient(&self, position: usize) -> Fr {
+		let x_i = Fr::from(u64::from(self.identifiers[position]));
+		let (numerator, denominator) = self.identifiers.iter()
+			.enumerate()
+			.filter(|(j, _)| *j != position)
+			.fold((Fr::one(), Fr::one()), |(numerator, denominator), (_, identifier)| {
+				let x_j = Fr::from(u64::from(*identifier));
+				(numerator * x_j, denominator * (x_j - x_i))
+			});
+		// identifiers are distinct, so the denominator is not zero.
+		numerator * denominator.invert().unwrap()
+	}
+}
+
+/// Second round: sign `package` with the nonces committed to in the first round.
+pub fn sign(
+	package: &SigningPackage,
+	nonces: &SigningNonces,
+	share: &KeyShare,
+) -> Result<SignatureShare, Error> {
+	let state = SigningState::new(package, &share.group_key)?;
+	let position = package.commitments.iter()
+		.position(|c| c.identifier == share.identifier)
+		.ok_or(Error::MissingCommitment(share.identifier))?;
+	let commitment = &package.commitments[position];
+	if commitment.hiding != (SPENDING_KEY_GENERATOR * nonces.hiding).to_bytes() ||
+		commitment.binding != (SPENDING_KEY_GENERATOR * nonces.binding).to_bytes()
+	{
+		return Err(Error::MissingCommitment(share.identifier));
+	}
+
+	let z = nonces.hiding +
+		nonces.binding * state.binding_factors[position] +
+		state.lagrange_coefficient(position) * scalar(&share.secret)? * state.challenge;
+	Ok(SignatureShare { identifier: share.identifier, share: z.to_bytes() })
+}
+
+/// Check the signature shares of all committed signers and aggregate them into the 64 byte
+/// `spend_auth_sig` under the randomized key.
+pub fn aggregate(
+	package: &SigningPackage,
+	shares: &[SignatureShare],
+	public_key_package: &PublicKeyPackage,
+) -> Result<[u8; 64], Error> {
+	if package.commitments.len() < public_key_package.threshold as usize {
+		return Err(Error::NotEnoughSigners);
+	}
+	let state = SigningState::new(package, &public_key_package.group_key)?;
+
+	let mut z = scalar(&package.randomizer)? * state.challenge;
+	for (position, commitment) in package.commitments.iter().enumerate() {
+		let identifier = commitment.identifier;
+		let share = shares.iter()
+			.find(|share| share.identifier == identifier)
+			.ok_or(Error::MissingShare(identifier))?;
+		let verifying_share = public_key_package.verifying_shares.iter()
+			.find(|(id, _)| *id == identifier)
+			.ok_or(Error::InvalidIdentifier(identifier))?;
+
+		let z_i = scalar(&share.share)?;
+		let expected = point(&commitment.hiding)? +
+			point(&commitment.binding)? * state.binding_factors[position] +
+			point(&verifying_share.1)? * (state.lagrange_coefficient(position) * state.challenge);
+		if SPENDING_KEY_GENERATOR * z_i != expected {
+			return Err(Error::InvalidShare(identifier));
+		}
+		z += z_i;
+	}
+
+	let mut signature = [0u8; 64];
+	signature[..32].copy_from_slice(&state.group_commitment);
+	signature[32..].copy_from_slice(&z.to_bytes());
+	debug_assert!(verify(&state.randomized_key, &package.message, &signature));
+	Ok(signature)
+}
+
+/// Verify a RedJubjub spend authorization signature under `key`.
+pub fn verify(key: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+	let mut r_bytes = [0u8; 32];
+	r_bytes.copy_from_slice(&signature[..32]);
+	let mut s_bytes = [0u8; 32];
+	s_bytes.copy_from_slice(&signature[32..]);
+
+	let r: Option<ExtendedPoint> = ExtendedPoint::from_bytes(&r_bytes).into();
+	let key: Option<ExtendedPoint> = ExtendedPoint::from_bytes(key).into();
+	match (r, key, scalar(&s_bytes)) {
+		(Some(r), Some(key_point), Ok(s)) => {
+			let c = challenge(&r_bytes, &key_point.to_bytes(), message);
+			let check = ExtendedPoint::from(SPENDING_KEY_GENERATOR) * s - r - key_point * c;
+			bool::from(check.mul_by_cofactor().is_identity())
+		},
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::rngs::OsRng;
+
+	fn sign_with(
+		signers: &[Identifier],
+		shares: &[KeyShare],
+		public_key_package: &PublicKeyPackage,
+	) -> (SigningPackage, Result<[u8; 64], Error>) {
+		let mut rng = OsRng;
+		let (nonces, commitments): (Vec<_>, Vec<_>) = signers.iter()
+			.map(|identifier| commit(*identifier, &mut rng))
+			.unzip();
+		let package = SigningPackage {
+			commitments,
+			message: b"sighash".to_vec(),
+			randomizer: Fr::random(&mut rng).to_bytes(),
+		};
+		let signature_shares = signers.iter().zip(&nonces)
+			.map(|(identifier, nonces)| sign(&package, nonces, &shares[*identifier as usize - 1]).unwrap())
+			.collect::<Vec<_>>();
+		let signature = aggregate(&package, &signature_shares, public_key_package);
+		(package, signature)
+	}
+
+	#[test]
+	fn threshold_signature_verifies_under_randomized_key() {
+		let (shares, public_key_package) = keygen_with_dealer(2, 3, &mut OsRng).unwrap();
+		for signers in &[vec![1, 2], vec![2, 3], vec![1, 2, 3]] {
+			let (package, signature) = sign_with(signers, &shares, &public_key_package);
+			let signature = signature.unwrap();
+			let rk = randomized_key(&public_key_package.group_key, &package.randomizer).unwrap();
+			assert!(verify(&rk, &package.message, &signature));
+			assert!(!verify(&public_key_package.group_key, &package.message, &signature));
+		}
+	}
+
+	#[test]
+	fn aggregate_requires_threshold_and_valid_shares() {
+		let (shares, public_key_package) = keygen_with_dealer(2, 3, &mut OsRng).unwrap();
+		let (_, signature) = sign_with(&[1], &shares, &public_key_package);
+		assert_eq!(signature.err(), Some(Error::NotEnoughSigners));
+
+		let mut rng = OsRng;
+		let (nonces_1, commitments_1) = commit(1, &mut rng);
+		let (nonces_2, commitments_2) = commit(2, &mut rng);
+		let package = SigningPackage {
+			commitments: vec![commitments_1, commitments_2],
+			message: b"sighash".to_vec(),
+			randomizer: Fr::random(&mut rng).to_bytes(),
+		};
+		let share_1 = sign(&package, &nonces_1, &shares[0]).unwrap();
+		// signer 2 signs with nonces it did not commit to.
+		assert_eq!(sign(&package, &nonces_1, &shares[1]), Err(Error::MissingCommitment(2)));
+		let mut share_2 = sign(&package, &nonces_2, &shares[1]).unwrap();
+		share_2.share = (scalar(&share_2.share).unwrap() + Fr::one()).to_bytes();
+		assert_eq!(
+			aggregate(&package, &[share_1.clone(), share_2], &public_key_package).err(),
+			Some(Error::InvalidShare(2))
+		);
+		assert_eq!(aggregate(&package, &[share_1], &public_key_package).err(), Some(Error::MissingShare(2)));
+	}
+
+	#[test]
+	fn split_key_checks_threshold() {
+		let ask = Fr::random(&mut OsRng).to_bytes();
+		assert_eq!(split_key(&ask, 0, 3, &mut OsRng).unwrap_err(), Error::InvalidThreshold);
+		assert_eq!(split_key(&ask, 4, 3, &mut OsRng).unwrap_err(), Error::InvalidThreshold);
+
+		let (_, public_key_package) = split_key(&ask, 3, 3, &mut OsRng).unwrap();
+		assert_eq!(
+			public_key_package.group_key,
+			(SPENDING_KEY_GENERATOR * scalar(&ask).unwrap()).to_bytes()
+		);
+	}
+
+	#[test]
+	fn keystore_shares_are_found_in_the_package() {
+		let keystore_path = tempfile::tempdir().expect("Creates keystore path");
+		let keystore = sc_keystore::Store::open(keystore_path.path(), None).expect("Creates keystore");
+		let (shares, public_key_package) = keygen_with_dealer(2, 3, &mut OsRng).unwrap();
+		let public = shares[1].keystore_public();
+		keystore.write()
+			.insert_unknown(FROST_KEY_TYPE, &shares[1].keystore_suri(), public.as_ref())
+			.unwrap();
+
+		assert_eq!(key_share_from_keystore(&keystore, &public, &public_key_package), Ok(shares[1].clone()));
+		assert_eq!(
+			key_share_from_keystore(&keystore, &shares[0].keystore_public(), &public_key_package),
+			Err(Error::UnknownKey)
+		);
+		let (_, other_package) = keygen_with_dealer(2, 3, &mut OsRng).unwrap();
+		assert_eq!(
+			key_share_from_keystore(&keystore, &public, &other_package),
+			Err(Error::UnknownShare)
+		);
+	}
+
+	#[test]
+	fn pending_nonces_are_bounded_and_expire() {
+		let mut pending = PendingNonces::default();
+		let start = Instant::now();
+		let mut commitments = Vec::new();
+		for _ in 0..MAX_PENDING_NONCES {
+			let (nonces, commitment) = commit(1, &mut OsRng);
+			pending.insert(commitment.clone(), nonces, start).unwrap();
+			commitments.push(commitment);
+		}
+		let (nonces, commitment) = commit(1, &mut OsRng);
+		assert_eq!(
+			pending.insert(commitment.clone(), nonces.clone(), start).err(),
+			Some(Error::TooManyCommitments)
+		);
+
+		// nonces are used once.
+		assert!(pending.take(&commitments[0], start).is_some());
+		assert!(pending.take(&commitments[0], start).is_none());
+
+		// expired nonces can't be used and make room for new ones.
+		let later = start + NONCES_LIFETIME;
+		assert!(pending.take(&commitments[1], later).is_none());
+		pending.insert(commitment.clone(), nonces, later).unwrap();
+		assert!(pending.take(&commitments[2], later).is_none());
+		assert!(pending.take(&commitment, later).is_some());
+	}
+}
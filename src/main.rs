@@ -3,6 +3,11 @@
 
 mod api;
 mod chain_spec;
+mod frost;
+mod pool;
+mod relay;
+mod reward_prover;
+mod sapling_keys;
 #[macro_use]
 mod service;
 mod cli;
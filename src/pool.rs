@@ -0,0 +1,216 @@
+//! Transaction pool support for shielded transactions.
+//!
+//! The runtime only runs the cheap, state dependent checks of a shielded transaction when the
+//! pool validates it. Its proofs are checked once, through the `NftsApi` runtime API, before the
+//! first validation. Proofs that passed keep passing, so the result is cached and revalidation
+//! after every block does not redo the pairings. A transaction whose proofs can't be checked is
+//! not let in.
+
+use std::collections::{HashSet, VecDeque};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use codec::{Decode, Encode};
+use futures::future::{self, Future};
+use log::warn;
+use node_primitives::Block;
+use node_runtime::{nfts, Call, NftsApi, UncheckedExtrinsic};
+use parking_lot::Mutex;
+use sc_transaction_pool::txpool::{BlockHash, ChainApi, ExtrinsicFor, NumberFor};
+use sp_api::ProvideRuntimeApi;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use sp_runtime::transaction_validity::{
+	InvalidTransaction, TransactionValidity, TransactionValidityError, UnknownTransaction,
+};
+
+/// Number of shielded transactions whose verified proofs are remembered.
+const VERIFIED_CACHE_SIZE: usize = 4096;
+
+/// Hashes of shielded transactions with verified proofs, oldest first.
+#[derive(Default)]
+struct VerifiedCache {
+	hashes: HashSet<[u8; 32]>,
+	order: VecDeque<[u8; 32]>,
+}
+
+impl VerifiedCache {
+	fn contains(&self, hash: &[u8; 32]) -> bool {
+		self.hashes.contains(hash)
+	}
+
+	fn insert(&mut self, hash: [u8; 32]) {
+		if !self.hashes.insert(hash) {
+			return;
+		}
+		self.order.push_back(hash);
+		if self.order.len() > VERIFIED_CACHE_SIZE {
+			if let Some(oldest) = self.order.pop_front() {
+				self.hashes.remove(&oldest);
+			}
+		}
+	}
+}
+
+/// A `ChainApi` that verifies the proofs of shielded transactions before handing them to
+/// `Inner`.
+pub struct ShieldedChainApi<Client, Inner> {
+	client: Arc<Client>,
+	inner: Inner,
+	verified: Mutex<VerifiedCache>,
+}
+
+impl<Client, Inner> ShieldedChainApi<Client, Inner> {
+	/// Create a new `ShieldedChainApi` wrapping `inner`.
+	pub fn new(client: Arc<Client>, inner: Inner) -> Self {
+		ShieldedChainApi {
+			client,
+			inner,
+			verified: Default::default(),
+		}
+	}
+}
+
+#[cfg(test)]
+impl<Client, Inner> ShieldedChainApi<Client, Inner> {
+	/// Let `tx` in as if its proofs were verified.
+	pub(crate) fn assume_verified(&self, tx: &nfts::ShieldedTransaction) {
+		self.verified.lock().insert(sp_core::blake2_256(&tx.encode()));
+	}
+}
+
+/// The shielded transaction carried by `uxt`, if any.
+fn shielded_transaction(uxt: &<Block as BlockT>::Extrinsic) -> Option<nfts::ShieldedTransaction> {
+	// an opaque extrinsic holds the extrinsic without its length prefix, which encoding adds back.
+	match UncheckedExtrinsic::decode(&mut &uxt.encode()[..]) {
+		Ok(UncheckedExtrinsic { function: Call::Nfts(nfts::Call::transact(tx)), .. }) |
+		Ok(UncheckedExtrinsic { function: Call::Nfts(nfts::Call::transact_unsigned(tx)), .. }) => Some(tx),
+		_ => None,
+	}
+}
+
+impl<Client, Inner> ShieldedChainApi<Client, Inner> where
+	Client: ProvideRuntimeApi<Block>,
+	Client::Api: NftsApi<Block>,
+{
+	/// Check that `uxt` is not a shielded transaction or carries valid proofs.
+	fn check_proofs(&self, at: &BlockId<Block>, uxt: &<Block as BlockT>::Extrinsic) -> Result<(), TransactionValidityError> {
+		let tx = match shielded_transaction(uxt) {
+			Some(tx) => tx,
+			// anything else is left to the runtime.
+			None => return Ok(()),
+		};
+
+		let hash = sp_core::blake2_256(&tx.encode());
+		if self.verified.lock().contains(&hash) {
+			return Ok(());
+		}
+		match self.client.runtime_api().verify_proofs(at, tx) {
+			Ok(true) => {
+				self.verified.lock().insert(hash);
+				Ok(())
+			}
+			Ok(false) => Err(InvalidTransaction::BadProof.into()),
+			Err(e) => {
+				warn!("Failed to verify shielded transaction proofs: {:?}", e);
+				Err(UnknownTransaction::CannotLookup.into())
+			}
+		}
+	}
+}
+
+impl<Client, Inner> ChainApi for ShieldedChainApi<Client, Inner> where
+	Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	Client::Api: NftsApi<Block>,
+	Inner: ChainApi<Block = Block> + 'static,
+{
+	type Block = Block;
+	type Hash = Inner::Hash;
+	type Error = Inner::Error;
+	type ValidationFuture = Pin<Box<dyn Future<Output = Result<TransactionValidity, Self::Error>> + Send>>;
+
+	fn validate_transaction(&self, at: &BlockId<Block>, uxt: ExtrinsicFor<Self>) -> Self::ValidationFuture {
+		match self.check_proofs(at, &uxt) {
+			Ok(()) => Box::pin(self.inner.validate_transaction(at, uxt)),
+			Err(e) => Box::pin(future::ready(Ok(Err(e)))),
+		}
+	}
+
+	fn block_id_to_number(&self, at: &BlockId<Block>) -> Result<Option<NumberFor<Self>>, Self::Error> {
+		self.inner.block_id_to_number(at)
+	}
+
+	fn block_id_to_hash(&self, at: &BlockId<Block>) -> Result<Option<BlockHash<Self>>, Self::Error> {
+		self.inner.block_id_to_hash(at)
+	}
+
+	fn hash_and_length(&self, uxt: &ExtrinsicFor<Self>) -> (Self::Hash, usize) {
+		self.inner.hash_and_length(uxt)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::service::Executor;
+	use sc_executor::{NativeExecutor, WasmExecutionMethod};
+	use sp_runtime::OpaqueExtrinsic;
+
+	fn opaque(xt: UncheckedExtrinsic) -> OpaqueExtrinsic {
+		Decode::decode(&mut &xt.encode()[..]).unwrap()
+	}
+
+	fn transaction_with_bad_proof() -> nfts::ShieldedTransaction {
+		// a spend of nullifier [1; 32] with an all zero proof and signature.
+		let mut spend = [0u8; 4 * 32 + 192 + 64];
+		spend[64..96].copy_from_slice(&[1u8; 32]);
+		nfts::ShieldedTransaction {
+			sapling_spends: vec![Decode::decode(&mut &spend[..]).unwrap()],
+			sapling_outputs: vec![],
+			sapling_value_balance: 0,
+			sapling_binding_sig: [0u8; 64],
+			orchard: None,
+			transparent_account: None,
+		}
+	}
+
+	#[test]
+	fn shielded_transactions_are_found_in_opaque_extrinsics() {
+		let tx = transaction_with_bad_proof();
+		let transact = opaque(UncheckedExtrinsic::new_unsigned(Call::Nfts(nfts::Call::transact(tx.clone()))));
+		assert_eq!(shielded_transaction(&transact), Some(tx));
+
+		let remark = opaque(UncheckedExtrinsic::new_unsigned(Call::System(frame_system::Call::remark(vec![]))));
+		assert_eq!(shielded_transaction(&remark), None);
+	}
+
+	#[test]
+	fn bad_proofs_are_rejected() {
+		let client = sc_client::new_in_mem::<_, Block, _, node_runtime::RuntimeApi>(
+			NativeExecutor::<Executor>::new(WasmExecutionMethod::Interpreted, None),
+			&crate::chain_spec::development_config(),
+			None,
+		).expect("Creates in memory client");
+		let pool_api = ShieldedChainApi::new(Arc::new(client), ());
+		let at = BlockId::number(0);
+
+		let transact = opaque(UncheckedExtrinsic::new_unsigned(
+			Call::Nfts(nfts::Call::transact(transaction_with_bad_proof()))
+		));
+		assert_eq!(pool_api.check_proofs(&at, &transact), Err(InvalidTransaction::BadProof.into()));
+		assert!(pool_api.verified.lock().order.is_empty());
+
+		let remark = opaque(UncheckedExtrinsic::new_unsigned(Call::System(frame_system::Call::remark(vec![]))));
+		assert_eq!(pool_api.check_proofs(&at, &remark), Ok(()));
+	}
+
+	#[test]
+	fn verified_cache_forgets_oldest() {
+		let mut cache = VerifiedCache::default();
+		for i in 0..=VERIFIED_CACHE_SIZE {
+			cache.insert(sp_core::blake2_256(&(i as u64).encode()));
+		}
+		assert!(!cache.contains(&sp_core::blake2_256(&0u64.encode())));
+		assert!(cache.contains(&sp_core::blake2_256(&1u64.encode())));
+		assert_eq!(cache.order.len(), VERIFIED_CACHE_SIZE);
+	}
+}
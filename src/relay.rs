@@ -0,0 +1,349 @@
+//! Relay of shielded transactions.
+//!
+//! Unsigned shielded transactions don't reveal their sender on chain, but the node they are first
+//! submitted to does, and transaction propagation makes it easy to find. Shielded transactions
+//! handed to the relay are instead gossiped over a dedicated protocol. Every node holds the
+//! transactions it relays or receives for a random delay, batches them, and then gossips its own
+//! and injects the received ones into its local transaction pool, so the node a transaction
+//! first shows up in is unlikely to be the one it came from.
+//!
+//! The runtime only runs the cheap checks of a shielded transaction, its proofs are checked by the
+//! transaction pool of the node, see `crate::pool`. A received transaction is therefore only
+//! gossiped on once the local pool has accepted it.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use codec::Decode;
+use futures::{channel::mpsc, prelude::*, stream::FuturesUnordered};
+use futures_timer::Delay;
+use log::{debug, trace};
+use node_primitives::{Block, Hash};
+use node_runtime::{nfts, Call, UncheckedExtrinsic};
+use parking_lot::Mutex;
+use rand::{seq::SliceRandom, Rng};
+use sc_network::PeerId;
+use sc_network_gossip::{
+	GossipEngine, Network, TopicNotification, ValidationResult, Validator, ValidatorContext,
+};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{BlakeTwo256, Block as BlockT, Hash as HashT},
+	ConsensusEngineId,
+};
+use sp_transaction_pool::TransactionPool;
+
+/// Engine id of the relay protocol.
+pub const ENGINE_ID: ConsensusEngineId = *b"shrl";
+/// Name of the relay protocol.
+const PROTOCOL_NAME: &[u8] = b"/zcash-chain/shielded-relay/1";
+
+/// Bounds of the random delay a batch is held for, in milliseconds.
+const MIN_DELAY: u64 = 500;
+const MAX_DELAY: u64 = 5_000;
+/// Number of transactions that flushes a batch before its delay is over.
+const MAX_BATCH: usize = 64;
+/// How long a relayed transaction is kept and gossiped to new peers.
+const MESSAGE_TTL: Duration = Duration::from_secs(120);
+/// Maximum number of transactions kept and gossiped to new peers at once.
+const MAX_KEPT: usize = 4096;
+/// Number of transactions handed to the relay that it holds before refusing more.
+pub const MAX_PENDING: usize = MAX_BATCH;
+
+/// Sends shielded transactions to the relay.
+pub type RelaySender = mpsc::Sender<Vec<u8>>;
+/// Receives the shielded transactions to relay.
+pub type RelayReceiver = mpsc::Receiver<Vec<u8>>;
+
+/// Create the channel shielded transactions are handed to the relay through.
+pub fn channel() -> (RelaySender, RelayReceiver) {
+	mpsc::channel(MAX_PENDING)
+}
+
+/// The only topic of the relay protocol.
+fn topic() -> Hash {
+	BlakeTwo256::hash(b"shielded-relay")
+}
+
+/// Whether `data` is an encoded unsigned shielded transaction, a `transact_unsigned` extrinsic.
+pub fn is_shielded_transaction(data: &[u8]) -> bool {
+	match UncheckedExtrinsic::decode(&mut &data[..]) {
+		Ok(UncheckedExtrinsic { signature: None, function: Call::Nfts(nfts::Call::transact_unsigned(_)) }) => true,
+		_ => false,
+	}
+}
+
+/// The extrinsic to submit to the transaction pool for the relayed `data`.
+fn relayed_extrinsic(data: &[u8]) -> Option<<Block as BlockT>::Extrinsic> {
+	<Block as BlockT>::Extrinsic::decode(&mut &data[..]).ok()
+}
+
+/// Hands received shielded transactions to the relay without keeping them. The relay gossips the
+/// transactions handed to it and those the pool accepted, which are kept for `MESSAGE_TTL`.
+#[derive(Default)]
+struct RelayValidator {
+	kept: Mutex<HashMap<Hash, Instant>>,
+}
+
+impl RelayValidator {
+	/// Keep `data` for `MESSAGE_TTL`. `false` if `MAX_KEPT` other transactions are kept.
+	fn keep(&self, data: &[u8]) -> bool {
+		let mut kept = self.kept.lock();
+		let hash = BlakeTwo256::hash(data);
+		if kept.len() >= MAX_KEPT && !kept.contains_key(&hash) {
+			return false;
+		}
+		kept.entry(hash).or_insert_with(Instant::now);
+		true
+	}
+}
+
+impl Validator<Block> for RelayValidator {
+	fn validate(
+		&self,
+		_context: &mut dyn ValidatorContext<Block>,
+		sender: &PeerId,
+		data: &[u8],
+	) -> ValidationResult<Hash> {
+		if !is_shielded_transaction(data) {
+			trace!(target: "shielded-relay", "Discarding invalid message from {}", sender);
+			return ValidationResult::Discard;
+		}
+		// the proofs are unchecked until the pool accepts the transaction, see `ShieldedRelay`.
+		ValidationResult::ProcessAndDiscard(topic())
+	}
+
+	fn message_expired<'a>(&'a self) -> Box<dyn FnMut(Hash, &[u8]) -> bool + 'a> {
+		let mut kept = self.kept.lock();
+		kept.retain(|_, first_kept| first_kept.elapsed() < MESSAGE_TTL);
+		Box::new(move |_topic, data| !kept.contains_key(&BlakeTwo256::hash(data)))
+	}
+}
+
+/// A transaction held by the relay.
+enum Relayed {
+	/// Handed to this node, to be gossiped.
+	Local(Vec<u8>),
+	/// Received from a peer, to be injected into the transaction pool.
+	Remote(Vec<u8>),
+}
+
+/// The relay of shielded transactions of a full node.
+pub struct ShieldedRelay<Client, Pool> {
+	gossip: GossipEngine<Block>,
+	validator: Arc<RelayValidator>,
+	client: Arc<Client>,
+	pool: Arc<Pool>,
+	incoming: Pin<Box<dyn Stream<Item = TopicNotification> + Send>>,
+	local: Option<RelayReceiver>,
+	batch: Vec<Relayed>,
+	delay: Option<Delay>,
+	/// Submissions to the pool, resolving to the transactions the pool accepted.
+	submissions: FuturesUnordered<Pin<Box<dyn Future<Output = Vec<Vec<u8>>> + Send>>>,
+}
+
+impl<Client, Pool> ShieldedRelay<Client, Pool> where
+	Client: HeaderBackend<Block>,
+	Pool: TransactionPool<Block = Block>,
+{
+	/// Start relaying over `network` the transactions received through `local`.
+	pub fn new<N: Network<Block> + Send + Clone + 'static>(
+		network: N,
+		executor: &impl futures::task::Spawn,
+		client: Arc<Client>,
+		pool: Arc<Pool>,
+		local: RelayReceiver,
+	) -> Self {
+		let validator = Arc::new(RelayValidator::default());
+		let gossip = GossipEngine::new(network, executor, ENGINE_ID, PROTOCOL_NAME, validator.clone());
+		let incoming = gossip.messages_for(topic()).boxed();
+		ShieldedRelay {
+			gossip,
+			validator,
+			client,
+			pool,
+			incoming,
+			local: Some(local),
+			batch: Vec::new(),
+			delay: None,
+			submissions: FuturesUnordered::new(),
+		}
+	}
+
+	/// Gossip `data` to the peers, unless `MAX_KEPT` transactions are gossiped already.
+	fn gossip_transaction(&mut self, data: Vec<u8>) {
+		if self.validator.keep(&data) {
+			self.gossip.gossip_message(topic(), data, false);
+		} else {
+			debug!(target: "shielded-relay", "Too many relayed transactions, dropping one");
+		}
+	}
+
+	/// Gossip the local transactions of the batch and submit the remote ones, in random order.
+	fn flush(&mut self) {
+		let mut batch = std::mem::replace(&mut self.batch, Vec::new());
+		batch.shuffle(&mut rand::thread_rng());
+		debug!(target: "shielded-relay", "Relaying a batch of {} transactions", batch.len());
+
+		let (mut remote, mut remote_data) = (Vec::new(), Vec::new());
+		for relayed in batch {
+			match relayed {
+				Relayed::Local(data) => self.gossip_transaction(data),
+				Relayed::Remote(data) => if let Some(xt) = relayed_extrinsic(&data) {
+					remote.push(xt);
+					remote_data.push(data);
+				},
+			}
+		}
+		if remote.is_empty() {
+			return;
+		}
+
+		let at = BlockId::hash(self.client.info().best_hash);
+		let submission = self.pool.submit_at(&at, remote).map(move |result| match result {
+			Ok(results) => results.into_iter().zip(remote_data).filter_map(|(result, data)| match result {
+				Ok(_) => Some(data),
+				Err(e) => {
+					// mostly transactions that made it to the pool through another relay.
+					trace!(target: "shielded-relay", "Relayed transaction rejected: {:?}", e);
+					None
+				},
+			}).collect(),
+			Err(e) => {
+				debug!(target: "shielded-relay", "Unable to submit relayed transactions: {:?}", e);
+				Vec::new()
+			},
+		});
+		self.submissions.push(Box::pin(submission));
+	}
+}
+
+impl<Client, Pool> Future for ShieldedRelay<Client, Pool> where
+	Client: HeaderBackend<Block>,
+	Pool: TransactionPool<Block = Block>,
+{
+	type Output = ();
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+		let this = Pin::into_inner(self);
+
+		loop {
+			match this.incoming.poll_next_unpin(cx) {
+				Poll::Ready(Some(notification)) => this.batch.push(Relayed::Remote(notification.message)),
+				// the network is gone.
+				Poll::Ready(None) => return Poll::Ready(()),
+				Poll::Pending => break,
+			}
+		}
+		while let Some(local) = this.local.as_mut() {
+			match local.poll_next_unpin(cx) {
+				Poll::Ready(Some(data)) => this.batch.push(Relayed::Local(data)),
+				Poll::Ready(None) => this.local = None,
+				Poll::Pending => break,
+			}
+		}
+
+		if !this.batch.is_empty() && this.delay.is_none() {
+			let delay = rand::thread_rng().gen_range(MIN_DELAY..MAX_DELAY);
+			this.delay = Some(Delay::new(Duration::from_millis(delay)));
+		}
+		let delay_over = this.delay.as_mut().map_or(false, |delay| delay.poll_unpin(cx).is_ready());
+		if delay_over || this.batch.len() >= MAX_BATCH {
+			this.delay = None;
+			this.flush();
+		}
+
+		while let Poll::Ready(Some(accepted)) = this.submissions.poll_next_unpin(cx) {
+			for data in accepted {
+				this.gossip_transaction(data);
+			}
+		}
+		Poll::Pending
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use crate::pool::ShieldedChainApi;
+	use crate::service::Executor;
+	use node_runtime::constants::currency::RAD;
+	use sc_executor::{NativeExecutor, WasmExecutionMethod};
+
+	fn transaction(value_balance: i64) -> nfts::ShieldedTransaction {
+		// a spend of nullifier [1; 32] with an all zero proof and signature.
+		let mut spend = [0u8; 4 * 32 + 192 + 64];
+		spend[64..96].copy_from_slice(&[1u8; 32]);
+		nfts::ShieldedTransaction {
+			sapling_spends: vec![Decode::decode(&mut &spend[..]).unwrap()],
+			sapling_outputs: vec![],
+			sapling_value_balance: value_balance,
+			sapling_binding_sig: [0u8; 64],
+			orchard: None,
+			transparent_account: None,
+		}
+	}
+
+	#[test]
+	fn only_unsigned_shielded_transactions_are_relayed() {
+		let transact = UncheckedExtrinsic::new_unsigned(Call::Nfts(nfts::Call::transact_unsigned(transaction(0))));
+		assert!(is_shielded_transaction(&transact.encode()));
+
+		// `transact` is only dispatched with a signed origin.
+		let signed_only = UncheckedExtrinsic::new_unsigned(Call::Nfts(nfts::Call::transact(transaction(0))));
+		assert!(!is_shielded_transaction(&signed_only.encode()));
+		let remark = UncheckedExtrinsic::new_unsigned(Call::System(frame_system::Call::remark(vec![])));
+		assert!(!is_shielded_transaction(&remark.encode()));
+		assert!(!is_shielded_transaction(&[0u8; 4]));
+	}
+
+	#[test]
+	fn kept_transactions_are_bounded() {
+		let validator = RelayValidator::default();
+		for i in 0..MAX_KEPT {
+			assert!(validator.keep(&(i as u64).encode()));
+		}
+		assert!(!validator.keep(&(MAX_KEPT as u64).encode()));
+		// keeping a kept transaction again doesn't need room.
+		assert!(validator.keep(&0u64.encode()));
+
+		let mut expired = validator.message_expired();
+		assert!(!expired(topic(), &0u64.encode()));
+		assert!(expired(topic(), &(MAX_KEPT as u64).encode()));
+	}
+
+	#[test]
+	fn relay_channel_is_bounded() {
+		let (mut sender, _receiver) = channel();
+		let sent = (0..2 * MAX_PENDING).take_while(|_| sender.try_send(Vec::new()).is_ok()).count();
+		assert!(sent < 2 * MAX_PENDING);
+	}
+
+	#[test]
+	fn relayed_transactions_reach_the_pool() {
+		let client = Arc::new(sc_client::new_in_mem::<_, Block, _, node_runtime::RuntimeApi>(
+			NativeExecutor::<Executor>::new(WasmExecutionMethod::Interpreted, None),
+			&crate::chain_spec::development_config(),
+			None,
+		).expect("Creates in memory client"));
+		let pool_api = ShieldedChainApi::new(client.clone(), sc_transaction_pool::FullChainApi::new(client.clone()));
+		let tx = transaction(RAD as i64);
+		// the proofs are checked by `ShieldedChainApi`, see its tests.
+		pool_api.assume_verified(&tx);
+		let pool = sc_transaction_pool::BasicPool::new(Default::default(), Arc::new(pool_api));
+
+		let data = UncheckedExtrinsic::new_unsigned(Call::Nfts(nfts::Call::transact_unsigned(tx))).encode();
+		assert!(is_shielded_transaction(&data));
+		let xt = relayed_extrinsic(&data).expect("Decodes relayed transaction");
+
+		let at = BlockId::hash(client.info().best_hash);
+		let results = futures::executor::block_on(pool.submit_at(&at, vec![xt])).expect("Submits to the pool");
+		assert!(results[0].is_ok(), "Relayed transaction rejected: {:?}", results[0]);
+		assert_eq!(pool.status().ready, 1);
+	}
+}
@@ -0,0 +1,135 @@
+//! Proves the outputs of shielded staking rewards.
+//!
+//! The runtime only knows the note of a reward. Validators prove its output and encrypt the note
+//! for the recipient here, and leave the output in the persistent offchain storage, where the
+//! offchain worker of the `shielded_rewards` module picks it up and submits it.
+
+use std::sync::Arc;
+
+use codec::Encode;
+use futures::{future, Future, StreamExt};
+use group::GroupEncoding;
+use log::{debug, warn};
+use node_primitives::Block;
+use node_runtime::{shielded_rewards::{self, RewardNote}, NftsApi};
+use rand::rngs::OsRng;
+use sc_client_api::BlockchainEvents;
+use sp_api::ProvideRuntimeApi;
+use sp_core::offchain::{OffchainStorage, STORAGE_PREFIX};
+use sp_runtime::generic::BlockId;
+use zcash_primitives::{
+	consensus::MainNetwork,
+	memo::MemoBytes,
+	sapling::{note_encryption::sapling_note_encryption, prover::TxProver, PaymentAddress, Rseed},
+	transaction::components::{sapling::GrothProofBytes, OutputDescription},
+};
+use zcash_proofs::prover::LocalTxProver;
+
+/// The encoded output of the note of a reward, in the layout the runtime decodes.
+fn prove_output(prover: &LocalTxProver, note: &RewardNote) -> Option<Vec<u8>> {
+	let mut address = [0u8; 43];
+	if note.address.len() != address.len() {
+		return None;
+	}
+	address.copy_from_slice(&note.address);
+	let to = PaymentAddress::from_bytes(&address)?;
+	let sapling_note = to.create_note(note.value, Rseed::AfterZip212(note.rseed))?;
+	let cmu = sapling_note.cmu();
+
+	// no outgoing viewing key: the output can only be recovered by its recipient.
+	let encryptor = sapling_note_encryption::<_, MainNetwork>(
+		None,
+		sapling_note.clone(),
+		to.clone(),
+		MemoBytes::empty(),
+		&mut OsRng,
+	);
+	let mut ctx = prover.new_sapling_proving_context();
+	let (zkproof, cv) = prover.output_proof(
+		&mut ctx,
+		*encryptor.esk(),
+		to,
+		sapling_note.rcm(),
+		note.value,
+	);
+	let cv = cv.commitment().into();
+
+	let output: OutputDescription<GrothProofBytes> = OutputDescription {
+		cv,
+		cmu,
+		ephemeral_key: encryptor.epk().to_bytes().into(),
+		enc_ciphertext: encryptor.encrypt_note_plaintext(),
+		out_ciphertext: encryptor.encrypt_outgoing_plaintext(&cv, &cmu, &mut OsRng),
+		zkproof,
+	};
+	let mut encoded = Vec::new();
+	output.write_v4(&mut encoded).ok()?;
+	Some(encoded)
+}
+
+/// Prove the outputs of the pending rewards on every new best block, and store the ones that
+/// haven't been proven yet in `storage`.
+pub fn start<Client, Storage>(
+	client: Arc<Client>,
+	mut storage: Storage,
+	prover: LocalTxProver,
+) -> impl Future<Output = ()> where
+	Client: BlockchainEvents<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	Client::Api: NftsApi<Block>,
+	Storage: OffchainStorage + 'static,
+{
+	client.import_notification_stream()
+		.filter(|notification| future::ready(notification.is_new_best))
+		.for_each(move |notification| {
+			let at = BlockId::hash(notification.hash);
+			match client.runtime_api().pending_reward_notes(&at) {
+				Ok(notes) => for note in notes {
+					let key = shielded_rewards::reward_output_key(note.index);
+					if storage.get(STORAGE_PREFIX, &key).is_some() {
+						continue;
+					}
+					match prove_output(&prover, &note) {
+						Some(output) => {
+							debug!(target: "reward-prover", "Proved the output of reward {}", note.index);
+							storage.set(STORAGE_PREFIX, &key, &output);
+						},
+						None => warn!(target: "reward-prover", "Invalid note for reward {}", note.index),
+					}
+				},
+				Err(e) => warn!(target: "reward-prover", "Unable to fetch pending rewards: {:?}", e),
+			}
+			future::ready(())
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Decode;
+	use node_runtime::nfts::SaplingOutputDescription;
+	use zcash_primitives::zip32::{DiversifierIndex, ExtendedFullViewingKey, ExtendedSpendingKey};
+
+	#[test]
+	fn proven_outputs_commit_to_the_reward_note() {
+		let prover = match LocalTxProver::with_default_location() {
+			Some(prover) => prover,
+			// the Sapling parameters are not installed.
+			None => return,
+		};
+		let key = ExtendedFullViewingKey::from(&ExtendedSpendingKey::master(&[7u8; 32]));
+		let (_, address) = key.address(DiversifierIndex::new()).unwrap();
+		let note = RewardNote {
+			index: 3,
+			address: address.to_bytes().to_vec(),
+			value: 1_000,
+			rseed: shielded_rewards::reward_rseed(3),
+		};
+
+		let output = prove_output(&prover, &note).unwrap();
+		let output = SaplingOutputDescription::decode(&mut &output[..]).unwrap();
+		assert_eq!(
+			Some(output.note_commitment()),
+			shielded_rewards::note_commitment(&note.address, note.value, note.rseed),
+		);
+	}
+}
@@ -0,0 +1,272 @@
+//! Sapling spending keys and payment addresses.
+//!
+//! Keys are ZIP-32 extended spending keys at `m/32'/coin_type'/account'`. The seed of the master
+//! key is either raw bytes or derived from a secret URI, so that `//Alice` style dev seeds give
+//! the same Sapling keys on every node, like `get_from_seed` does for session keys.
+//!
+//! Keys and addresses are encoded in Bech32, with the human readable parts of the chain they
+//! belong to. See `chain_spec::sapling_network`.
+//!
+//! Spending keys can be kept in the keystore under `SAPLING_KEY_TYPE`, inserted with
+//! `author_insertKey` as a secret URI along with the sr25519 public key it resolves to. The
+//! keystore password, if any, is part of the derivation, so the keystore files alone do not give
+//! the keys away. The node signs spends with them and never hands them out.
+
+use std::fmt;
+
+use bech32::{FromBase32, ToBase32, Variant};
+use node_runtime::nfts::SAPLING_KEY_TYPE;
+use rand::rngs::OsRng;
+use sc_keystore::KeyStorePtr;
+use sp_core::{sr25519, Pair};
+use zcash_primitives::sapling::{redjubjub::PrivateKey, spend_sig, PaymentAddress};
+use zcash_primitives::zip32::{
+	ChildIndex, DiversifierIndex, ExtendedFullViewingKey, ExtendedSpendingKey,
+};
+
+/// Purpose of ZIP-32 Sapling key paths.
+const ZIP32_PURPOSE: u32 = 32;
+/// Personalization of the hash turning a secret URI into a seed.
+const SEED_PERSONALIZATION: &[u8; 16] = b"ZcashChainSeedSK";
+/// Length of a raw Sapling payment address.
+const PAYMENT_ADDRESS_LEN: usize = 43;
+
+/// Bech32 human readable parts and coin type of the Sapling keys of a chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaplingNetwork {
+	/// SLIP-44 coin type used in key paths.
+	pub coin_type: u32,
+	pub hrp_payment_address: &'static str,
+	pub hrp_extended_spending_key: &'static str,
+	pub hrp_extended_full_viewing_key: &'static str,
+}
+
+/// Errors of key derivation and encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// The secret URI can't be parsed.
+	InvalidSuri,
+	/// The string is not valid Bech32.
+	InvalidBech32,
+	/// The string is Bech32 with another human readable part.
+	WrongHrp(String),
+	/// The decoded bytes are not a valid key or address.
+	InvalidEncoding,
+	/// No valid diversifier exists from the requested index on.
+	NoDiversifier,
+	/// The keystore has no Sapling key under this public key.
+	UnknownKey,
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::InvalidSuri => write!(f, "Invalid secret URI"),
+			Error::InvalidBech32 => write!(f, "Invalid Bech32 string"),
+			Error::WrongHrp(hrp) => write!(f, "Unexpected human readable part {}", hrp),
+			Error::InvalidEncoding => write!(f, "Invalid key or address encoding"),
+			Error::NoDiversifier => write!(f, "No valid diversifier from this index on"),
+			Error::UnknownKey => write!(f, "No Sapling key in the keystore under this public key"),
+		}
+	}
+}
+
+/// The extended spending key of `account` derived from `seed`.
+pub fn spending_key_from_seed(seed: &[u8], coin_type: u32, account: u32) -> ExtendedSpendingKey {
+	ExtendedSpendingKey::from_path(
+		&ExtendedSpendingKey::master(seed),
+		&[
+			ChildIndex::Hardened(ZIP32_PURPOSE),
+			ChildIndex::Hardened(coin_type),
+			ChildIndex::Hardened(account),
+		],
+	)
+}
+
+/// The extended spending key of `account` derived from a secret URI such as `//Alice` or a
+/// mnemonic phrase with derivation junctions.
+///
+/// The seed is a hash of the sr25519 secret key the URI resolves to.
+pub fn spending_key_from_suri(suri: &str, coin_type: u32, account: u32) -> Result<ExtendedSpendingKey, Error> {
+	let pair = sr25519::Pair::from_string(suri, None).map_err(|_| Error::InvalidSuri)?;
+	Ok(spending_key_from_pair(&pair, coin_type, account))
+}
+
+/// The extended spending key of `account` derived from the Sapling key stored in `keystore` under
+/// the sr25519 `public` key.
+pub fn spending_key_from_keystore(
+	keystore: &KeyStorePtr,
+	public: &sr25519::Public,
+	coin_type: u32,
+	account: u32,
+) -> Result<ExtendedSpendingKey, Error> {
+	let pair = keystore.read()
+		.key_pair_by_type::<sr25519::Pair>(public, SAPLING_KEY_TYPE)
+		.map_err(|_| Error::UnknownKey)?;
+	Ok(spending_key_from_pair(&pair, coin_type, account))
+}
+
+fn spending_key_from_pair(pair: &sr25519::Pair, coin_type: u32, account: u32) -> ExtendedSpendingKey {
+	let seed = blake2b_simd::Params::new()
+		.hash_length(32)
+		.personal(SEED_PERSONALIZATION)
+		.hash(&pair.to_raw_vec());
+	spending_key_from_seed(seed.as_bytes(), coin_type, account)
+}
+
+/// The `spend_auth_sig` of a spend of a note of `key` over `sighash`, under the spend validating
+/// key randomized by `alpha`.
+pub fn spend_auth_sig(key: &ExtendedSpendingKey, alpha: &[u8; 32], sighash: &[u8; 32]) -> Result<[u8; 64], Error> {
+	let alpha = Option::from(jubjub::Fr::from_bytes(alpha)).ok_or(Error::InvalidEncoding)?;
+	let signature = spend_sig(PrivateKey(key.expsk.ask), alpha, sighash, &mut OsRng);
+
+	let mut bytes = [0u8; 64];
+	signature.write(&mut bytes[..]).expect("a signature is 64 bytes; qed");
+	Ok(bytes)
+}
+
+/// The first valid diversified payment address of `key` from diversifier `index` on, along with
+/// its diversifier index.
+pub fn payment_address(key: &ExtendedFullViewingKey, index: u64) -> Result<(u64, PaymentAddress), Error> {
+	let mut diversifier = DiversifierIndex::new();
+	diversifier.0[..8].copy_from_slice(&index.to_le_bytes());
+	let (diversifier, address) = key.address(diversifier).map_err(|_| Error::NoDiversifier)?;
+
+	let mut found = [0u8; 8];
+	found.copy_from_slice(&diversifier.0[..8]);
+	Ok((u64::from_le_bytes(found), address))
+}
+
+fn encode(hrp: &str, data: &[u8]) -> String {
+	bech32::encode(hrp, data.to_base32(), Variant::Bech32).expect("human readable parts are valid; qed")
+}
+
+fn decode(hrp: &str, s: &str) -> Result<Vec<u8>, Error> {
+	let (found, data, variant) = bech32::decode(s).map_err(|_| Error::InvalidBech32)?;
+	if found != hrp {
+		return Err(Error::WrongHrp(found));
+	}
+	if variant != Variant::Bech32 {
+		return Err(Error::InvalidBech32);
+	}
+	Vec::<u8>::from_base32(&data).map_err(|_| Error::InvalidBech32)
+}
+
+/// Bech32 encoding of a payment address.
+pub fn encode_payment_address(network: &SaplingNetwork, address: &PaymentAddress) -> String {
+	encode(network.hrp_payment_address, &address.to_bytes())
+}
+
+/// Decode a Bech32 payment address of `network`.
+pub fn decode_payment_address(network: &SaplingNetwork, s: &str) -> Result<PaymentAddress, Error> {
+	let bytes = decode(network.hrp_payment_address, s)?;
+	if bytes.len() != PAYMENT_ADDRESS_LEN {
+		return Err(Error::InvalidEncoding);
+	}
+	let mut raw = [0u8; PAYMENT_ADDRESS_LEN];
+	raw.copy_from_slice(&bytes);
+	PaymentAddress::from_bytes(&raw).ok_or(Error::InvalidEncoding)
+}
+
+/// Bech32 encoding of an extended spending key.
+pub fn encode_extended_spending_key(network: &SaplingNetwork, key: &ExtendedSpendingKey) -> String {
+	let mut bytes = Vec::new();
+	key.write(&mut bytes).expect("writing to a Vec never fails; qed");
+	encode(network.hrp_extended_spending_key, &bytes)
+}
+
+/// Decode a Bech32 extended spending key of `network`.
+pub fn decode_extended_spending_key(network: &SaplingNetwork, s: &str) -> Result<ExtendedSpendingKey, Error> {
+	let bytes = decode(network.hrp_extended_spending_key, s)?;
+	ExtendedSpendingKey::read(&bytes[..]).map_err(|_| Error::InvalidEncoding)
+}
+
+/// Bech32 encoding of an extended full viewing key.
+pub fn encode_extended_full_viewing_key(network: &SaplingNetwork, key: &ExtendedFullViewingKey) -> String {
+	let mut bytes = Vec::new();
+	key.write(&mut bytes).expect("writing to a Vec never fails; qed");
+	encode(network.hrp_extended_full_viewing_key, &bytes)
+}
+
+/// Decode a Bech32 extended full viewing key of `network`.
+pub fn decode_extended_full_viewing_key(network: &SaplingNetwork, s: &str) -> Result<ExtendedFullViewingKey, Error> {
+	let bytes = decode(network.hrp_extended_full_viewing_key, s)?;
+	ExtendedFullViewingKey::read(&bytes[..]).map_err(|_| Error::InvalidEncoding)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chain_spec::{DEV_SAPLING, MAINNET_SAPLING};
+	use group::GroupEncoding;
+	use zcash_primitives::constants::SPENDING_KEY_GENERATOR;
+	use zcash_primitives::sapling::redjubjub::{PublicKey, Signature};
+
+	#[test]
+	fn dev_seeds_are_deterministic() {
+		let alice = spending_key_from_suri("//Alice", DEV_SAPLING.coin_type, 0).unwrap();
+		assert!(alice == spending_key_from_suri("//Alice", DEV_SAPLING.coin_type, 0).unwrap());
+		assert!(alice != spending_key_from_suri("//Bob", DEV_SAPLING.coin_type, 0).unwrap());
+		assert!(alice != spending_key_from_suri("//Alice", DEV_SAPLING.coin_type, 1).unwrap());
+		assert!(alice != spending_key_from_suri("//Alice", MAINNET_SAPLING.coin_type, 0).unwrap());
+		assert_eq!(spending_key_from_suri("not a uri", DEV_SAPLING.coin_type, 0).err(), Some(Error::InvalidSuri));
+	}
+
+	#[test]
+	fn diversified_addresses_round_trip() {
+		let key = ExtendedFullViewingKey::from(&spending_key_from_seed(&[7u8; 32], DEV_SAPLING.coin_type, 0));
+		let (first_index, first) = payment_address(&key, 0).unwrap();
+		let (next_index, next) = payment_address(&key, first_index + 1).unwrap();
+		assert!(next_index > first_index);
+		assert!(first != next);
+
+		for address in &[first, next] {
+			let encoded = encode_payment_address(&DEV_SAPLING, address);
+			assert!(encoded.starts_with(DEV_SAPLING.hrp_payment_address));
+			assert!(decode_payment_address(&DEV_SAPLING, &encoded).unwrap() == *address);
+			assert_eq!(
+				decode_payment_address(&MAINNET_SAPLING, &encoded).err(),
+				Some(Error::WrongHrp(DEV_SAPLING.hrp_payment_address.into()))
+			);
+		}
+	}
+
+	#[test]
+	fn keystore_keys_sign_spends() {
+		let keystore_path = tempfile::tempdir().expect("Creates keystore path");
+		let keystore = sc_keystore::Store::open(keystore_path.path(), None).expect("Creates keystore");
+		let public = sr25519::Pair::from_string("//Alice", None).unwrap().public();
+		keystore.write().insert_unknown(SAPLING_KEY_TYPE, "//Alice", public.as_ref()).unwrap();
+
+		let key = spending_key_from_keystore(&keystore, &public, DEV_SAPLING.coin_type, 0).unwrap();
+		assert!(key == spending_key_from_suri("//Alice", DEV_SAPLING.coin_type, 0).unwrap());
+		let bob = sr25519::Pair::from_string("//Bob", None).unwrap().public();
+		assert_eq!(
+			spending_key_from_keystore(&keystore, &bob, DEV_SAPLING.coin_type, 0).err(),
+			Some(Error::UnknownKey)
+		);
+
+		let alpha = jubjub::Fr::from(7u64);
+		let sighash = [3u8; 32];
+		let signature = spend_auth_sig(&key, &alpha.to_bytes(), &sighash).unwrap();
+
+		let rk = PublicKey::from_private(&PrivateKey(key.expsk.ask), SPENDING_KEY_GENERATOR)
+			.randomize(alpha, SPENDING_KEY_GENERATOR);
+		let mut message = rk.0.to_bytes().to_vec();
+		message.extend_from_slice(&sighash);
+		let signature = Signature::read(&signature[..]).unwrap();
+		assert!(rk.verify(&message, &signature, SPENDING_KEY_GENERATOR));
+	}
+
+	#[test]
+	fn keys_round_trip() {
+		let spending_key = spending_key_from_seed(&[7u8; 32], MAINNET_SAPLING.coin_type, 0);
+		let encoded = encode_extended_spending_key(&MAINNET_SAPLING, &spending_key);
+		assert!(decode_extended_spending_key(&MAINNET_SAPLING, &encoded).unwrap() == spending_key);
+
+		let viewing_key = ExtendedFullViewingKey::from(&spending_key);
+		let encoded = encode_extended_full_viewing_key(&MAINNET_SAPLING, &viewing_key);
+		let decoded = decode_extended_full_viewing_key(&MAINNET_SAPLING, &encoded).unwrap();
+		assert!(payment_address(&decoded, 0).unwrap().1 == payment_address(&viewing_key, 0).unwrap().1);
+	}
+}
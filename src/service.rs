@@ -28,17 +28,42 @@ native_executor_instance!(
 	pub Executor,
 	node_runtime::api::dispatch,
 	node_runtime::native_version,
+	node_runtime::host_functions::shielded_crypto::HostFunctions,
 );
 
+/// Add the `frost_*` and `sapling_*` RPCs, which sign with the keys of `keystore`, to `io` if
+/// `signing_rpc` is set by `--unsafe-signing-rpc`.
+pub fn extend_with_signing_rpc<M: jsonrpc_core::Metadata>(
+	io: &mut jsonrpc_core::IoHandler<M>,
+	signing_rpc: bool,
+	keystore: sc_keystore::KeyStorePtr,
+	sapling_network: Option<&'static crate::sapling_keys::SaplingNetwork>,
+) {
+	use crate::api::{Frost, FrostApi, Sapling, SaplingApi};
+
+	if !signing_rpc {
+		return;
+	}
+	io.extend_with(FrostApi::to_delegate(Frost::new(keystore.clone())));
+	match sapling_network {
+		Some(network) => io.extend_with(SaplingApi::to_delegate(Sapling::new(keystore, network))),
+		None => log::warn!("No Sapling encodings for this chain, the Sapling RPC is disabled"),
+	}
+}
+
 /// Starts a `ServiceBuilder` for a full service.
 ///
 /// Use this macro if you don't actually need the full service, but just the builder in order to
 /// be able to perform chain operations.
 macro_rules! new_full_start {
 	($config:expr) => {{
+		new_full_start!($config, false)
+	}};
+	($config:expr, $signing_rpc:expr) => {{
 		type RpcExtension = jsonrpc_core::IoHandler<sc_rpc::Metadata>;
 		let mut import_setup = None;
 		let inherent_data_providers = sp_inherents::InherentDataProviders::new();
+		let (relay_sender, relay_receiver) = crate::relay::channel();
 
 		let builder = sc_service::ServiceBuilder::new_full::<
 			node_primitives::Block, node_runtime::RuntimeApi, crate::service::Executor
@@ -47,7 +72,10 @@ macro_rules! new_full_start {
 				Ok(sc_client::LongestChain::new(backend.clone()))
 			})?
 			.with_transaction_pool(|config, client, _fetcher| {
-				let pool_api = sc_transaction_pool::FullChainApi::new(client.clone());
+				let pool_api = crate::pool::ShieldedChainApi::new(
+					client.clone(),
+					sc_transaction_pool::FullChainApi::new(client.clone()),
+				);
 				Ok(sc_transaction_pool::BasicPool::new(config, std::sync::Arc::new(pool_api)))
 			})?
 			.with_import_queue(|_config, client, mut select_chain, _transaction_pool| {
@@ -90,6 +118,7 @@ macro_rules! new_full_start {
                 let select_chain = builder.select_chain().cloned()
                     .expect("SelectChain is present for full services or set up failed; qed.");
                 let keystore = builder.keystore();
+                let sapling_network = crate::chain_spec::sapling_network(builder.config().chain_spec.id());
                 let babe_config = sc_consensus_babe::BabeLink::config(babe_link).clone();
                 let shared_epoch_changes = sc_consensus_babe::BabeLink::epoch_changes(babe_link).clone();
 
@@ -101,6 +130,19 @@ macro_rules! new_full_start {
                 io.extend_with(
                     TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone()))
                 );
+                io.extend_with(
+                    crate::api::AnchorApi::to_delegate(crate::api::Anchor::new(client.clone()))
+                );
+                crate::service::extend_with_signing_rpc(&mut io, $signing_rpc, keystore.clone(), sapling_network);
+                io.extend_with(
+                    crate::api::IdentityApi::to_delegate(crate::api::Identity::new(client.clone()))
+                );
+                io.extend_with(
+                    crate::api::NftsApi::to_delegate(crate::api::Nfts::new(client.clone()))
+                );
+                io.extend_with(
+                    crate::api::RelayApi::to_delegate(crate::api::Relay::new(relay_sender))
+                );
                 io.extend_with(
                     sc_consensus_babe_rpc::BabeApi::to_delegate(
                         sc_consensus_babe_rpc::BabeRPCHandler::new(client.clone(), shared_epoch_changes, keystore, babe_config, select_chain)
@@ -109,7 +151,7 @@ macro_rules! new_full_start {
                 Ok(io)
             })?;
 
-		(builder, import_setup, inherent_data_providers)
+		(builder, import_setup, inherent_data_providers, relay_receiver)
 	}}
 }
 
@@ -118,7 +160,7 @@ macro_rules! new_full_start {
 /// We need to use a macro because the test suit doesn't work with an opaque service. It expects
 /// concrete types instead.
 macro_rules! new_full {
-	($config:expr, $with_startup_data: expr) => {{
+	($config:expr, $signing_rpc:expr, $with_startup_data: expr) => {{
 		use futures::prelude::*;
 		use sc_network::Event;
 
@@ -141,7 +183,9 @@ macro_rules! new_full {
 		// never actively participate in any consensus process.
 		let participates_in_consensus = is_authority && !$config.sentry_mode;
 
-		let (builder, mut import_setup, inherent_data_providers) = new_full_start!($config);
+		let (builder, mut import_setup, inherent_data_providers, relay_receiver) = new_full_start!($config, $signing_rpc);
+
+		let offchain_storage = sc_client_api::backend::Backend::offchain_storage(&**builder.backend());
 
 		let service = builder
 			.with_finality_proof_provider(|client, backend|
@@ -154,6 +198,15 @@ macro_rules! new_full {
 
 		($with_startup_data)(&block_import, &babe_link);
 
+		let relay = crate::relay::ShieldedRelay::new(
+			service.network(),
+			&service.spawn_task_handle(),
+			service.client(),
+			service.transaction_pool(),
+			relay_receiver,
+		);
+		service.spawn_task("shielded-relay", relay);
+
 		if participates_in_consensus {
 			let proposer = sc_basic_authorship::ProposerFactory::new(
 				service.client(),
@@ -197,6 +250,17 @@ macro_rules! new_full {
 			);
 
 			service.spawn_task("authority-discovery", authority_discovery);
+
+			// the outputs of shielded staking rewards are submitted by the offchain workers of
+			// validators, which need the Sapling parameters to prove them.
+			match (offchain_storage, zcash_proofs::prover::LocalTxProver::with_default_location()) {
+				(Some(storage), Some(prover)) => service.spawn_task(
+					"reward-prover",
+					crate::reward_prover::start(service.client(), storage, prover),
+				),
+				(_, None) => log::warn!("Sapling parameters not found, shielded rewards won't be proven"),
+				(None, _) => {},
+			}
 		}
 
 		// if the node isn't actively participating in consensus then it doesn't
@@ -251,8 +315,8 @@ macro_rules! new_full {
 
 		Ok((service, inherent_data_providers))
 	}};
-	($config:expr) => {{
-		new_full!($config, |_, _| {})
+	($config:expr, $signing_rpc:expr) => {{
+		new_full!($config, $signing_rpc, |_, _| {})
 	}}
 }
 
@@ -267,15 +331,19 @@ type ConcreteClient =
 	>;
 type ConcreteBackend = Backend<ConcreteBlock>;
 type ConcreteTransactionPool = sc_transaction_pool::BasicPool<
-	sc_transaction_pool::FullChainApi<ConcreteClient, ConcreteBlock>,
+	crate::pool::ShieldedChainApi<
+		ConcreteClient,
+		sc_transaction_pool::FullChainApi<ConcreteClient, ConcreteBlock>,
+	>,
 	ConcreteBlock
 >;
 
 /// A specialized configuration object for setting up the node..
 pub type NodeConfiguration = Configuration<GenesisConfig>;
 
-/// Builds a new service for a full client.
-pub fn new_full(config: NodeConfiguration)
+/// Builds a new service for a full client, exposing the RPCs using the keys of the keystore if
+/// `signing_rpc` is set.
+pub fn new_full(config: NodeConfiguration, signing_rpc: bool)
 -> Result<
 	Service<
 		ConcreteBlock,
@@ -293,7 +361,7 @@ pub fn new_full(config: NodeConfiguration)
 	ServiceError,
 >
 {
-	new_full!(config).map(|(service, _)| service)
+	new_full!(config, signing_rpc).map(|(service, _)| service)
 }
 
 /// Builds a new service for a light client.
@@ -363,6 +431,11 @@ pub fn new_light(config: NodeConfiguration)
 				.ok_or_else(|| "Trying to start node RPC without active remote blockchain")?;
 
             let mut io = jsonrpc_core::IoHandler::default();
+            io.extend_with(
+                crate::api::NftsLightApi::to_delegate(
+                    crate::api::NftsLight::new(client.clone(), remote_blockchain.clone(), fetcher.clone())
+                )
+            );
             io.extend_with(
                 SystemApi::<AccountId, Index>::to_delegate(LightSystem::new(client, remote_blockchain, fetcher, pool))
             );
@@ -460,7 +533,7 @@ mod tests {
 		};
 		sc_service_test::sync(
 			sc_chain_spec::integration_test_config(),
-			|config| new_full(config),
+			|config| new_full(config, false),
 			|mut config| new_light(config),
 			block_factory,
 			extrinsic_factory,
@@ -482,7 +555,6 @@ mod tests {
 		let mut slot_num = 1u64;
 
 		// For the extrinsics factory
-		let bob = Arc::new(AccountKeyring::Bob.pair());
 		let charlie = Arc::new(AccountKeyring::Charlie.pair());
 		let mut index = 0;
 
@@ -490,7 +562,7 @@ mod tests {
 			chain_spec,
 			|config| {
 				let mut setup_handles = None;
-				new_full!(config, |
+				new_full!(config, false, |
 					block_import: &sc_consensus_babe::BabeBlockImport<Block, _, _>,
 					babe_link: &sc_consensus_babe::BabeLink<Block>,
 				| {
@@ -577,7 +649,8 @@ mod tests {
 			},
 			|service, _| {
 				let amount = 5 * CENTI_RAD;
-				let to: Address = AccountPublic::from(bob.public()).into_account().into();
+				// Bob, by the index the genesis gives him.
+				let to = Address::Index(1);
 				let from: Address = AccountPublic::from(charlie.public()).into_account().into();
 				let genesis_hash = service.client().block_hash(0).unwrap().unwrap();
 				let best_block_id = BlockId::number(service.client().chain_info().best_number);
@@ -591,7 +664,8 @@ mod tests {
 				let check_era = frame_system::CheckEra::from(Era::Immortal);
 				let check_nonce = frame_system::CheckNonce::from(index);
 				let check_weight = frame_system::CheckWeight::new();
-				let payment = pallet_transaction_payment::ChargeTransactionPayment::from(0);
+				let payment = node_runtime::fees::ChargeTransactionPayment::from(0);
+				let check_shielded = node_runtime::nfts::CheckShielded::new();
 				let extra = (
 					check_version,
 					check_genesis,
@@ -599,11 +673,12 @@ mod tests {
 					check_nonce,
 					check_weight,
 					payment,
+					check_shielded,
 				);
 				let raw_payload = SignedPayload::from_raw(
 					function,
 					extra,
-					(version, genesis_hash, genesis_hash, (), (), ())
+					(version, genesis_hash, genesis_hash, (), (), (), ())
 				);
 				let signature = raw_payload.using_encoded(|payload|	{
 					signer.sign(payload)
@@ -623,12 +698,37 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn signing_rpcs_need_the_flag() {
+		let keystore_path = tempfile::tempdir().expect("Creates keystore path");
+		let keystore = sc_keystore::Store::open(keystore_path.path(), None).expect("Creates keystore");
+		let methods = |signing_rpc| {
+			let mut io = jsonrpc_core::IoHandler::new();
+			super::extend_with_signing_rpc(
+				&mut io,
+				signing_rpc,
+				keystore.clone(),
+				crate::chain_spec::sapling_network("dev"),
+			);
+			["frost_commit", "frost_sign", "sapling_viewingKey", "sapling_signSpend"].iter()
+				.filter(|method| {
+					let request = format!(r#"{{"jsonrpc":"2.0","method":"{}","params":[],"id":1}}"#, method);
+					let response = io.handle_request_sync(&request).expect("Calls get a response");
+					!response.contains("-32601")
+				})
+				.count()
+		};
+
+		assert_eq!(methods(false), 0);
+		assert_eq!(methods(true), 4);
+	}
+
 	#[test]
 	#[ignore]
 	fn test_consensus() {
 		sc_service_test::consensus(
 			crate::chain_spec::tests::integration_test_config_with_two_authorities(),
-			|config| new_full(config),
+			|config| new_full(config, false),
 			|config| new_light(config),
 			vec![
 				"//Alice".into(),